@@ -0,0 +1,226 @@
+use mve::ui::inline;
+use mve::ui::xml::lexer::Position;
+use proc_macro::{Delimiter, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+use std::path::PathBuf;
+
+/// Builds an element from markup written inline, e.g. `ui! { <label class="x">Hi</label> }`.
+/// The markup is compiled like a UI file, using the `mve.toml` and `assets/ui/ui.meta` of the
+/// crate the macro is used in, and expands to a block evaluating to the element.
+///
+/// The markup has to consist of valid Rust tokens, so strings need double quotes and text can't
+/// contain unbalanced quotes or brackets.
+#[proc_macro]
+pub fn ui(input: TokenStream) -> TokenStream {
+    let mut source = Source::default();
+    source.push_stream(input);
+
+    let dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    match inline::compile(&source.text, &dir) {
+        Ok(code) => code
+            .parse()
+            .unwrap_or_else(|e| error(Span::call_site(), &format!("{e}"))),
+        Err(e) => {
+            let span = e
+                .position
+                .and_then(|p| source.span(p))
+                .unwrap_or_else(Span::call_site);
+            error(span, &e.message)
+        }
+    }
+}
+
+/// The markup rebuilt from the tokens of the macro input, laid out at the lines and columns the
+/// tokens were written at, so positions in it can be mapped back to the tokens.
+struct Source<S = Span> {
+    text: String,
+    /// The line of the first token, which is line 1 of the text.
+    first_line: usize,
+    line: usize,
+    column: usize,
+    /// The start of every token in the text, in order.
+    spans: Vec<(Position, S)>,
+}
+
+impl<S> Default for Source<S> {
+    fn default() -> Self {
+        Source {
+            text: String::new(),
+            first_line: 0,
+            line: 0,
+            column: 0,
+            spans: Vec::new(),
+        }
+    }
+}
+
+impl Source {
+    fn push_stream(&mut self, stream: TokenStream) {
+        for token in stream {
+            match token {
+                TokenTree::Group(group) => {
+                    let (open, close) = match group.delimiter() {
+                        Delimiter::Parenthesis => ("(", ")"),
+                        Delimiter::Brace => ("{", "}"),
+                        Delimiter::Bracket => ("[", "]"),
+                        Delimiter::None => ("", ""),
+                    };
+                    self.push_token(open, group.span_open());
+                    self.push_stream(group.stream());
+                    self.push_token(close, group.span_close());
+                }
+                TokenTree::Ident(ident) => self.push_token(&ident.to_string(), ident.span()),
+                TokenTree::Punct(punct) => self.push_token(&punct.to_string(), punct.span()),
+                TokenTree::Literal(literal) => {
+                    let text = literal.span().source_text();
+                    self.push_token(&text.unwrap_or(literal.to_string()), literal.span())
+                }
+            }
+        }
+    }
+
+    fn push_token(&mut self, text: &str, span: Span) {
+        self.push(text, span.line(), span.column(), span);
+    }
+}
+
+impl<S: Copy> Source<S> {
+    /// Appends the text of a token written at the line and column.
+    fn push(&mut self, text: &str, line: usize, column: usize, span: S) {
+        if text.is_empty() {
+            return;
+        }
+        if self.spans.is_empty() {
+            self.first_line = line;
+            self.line = line;
+            self.column = 1;
+        }
+
+        if line > self.line {
+            self.text.push_str(&"\n".repeat(line - self.line));
+            self.line = line;
+            self.column = 1;
+        }
+        if line == self.line && column >= self.column {
+            self.text.push_str(&" ".repeat(column - self.column));
+            self.column = column;
+        } else {
+            // Tokens from other macros don't have to be in order, keep them apart at least.
+            self.text.push(' ');
+            self.column += 1;
+        }
+
+        self.spans.push((
+            Position {
+                line: self.line - self.first_line + 1,
+                column: self.column,
+            },
+            span,
+        ));
+        for c in text.chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.text.push_str(text);
+    }
+
+    /// The span of the token at or before the position.
+    fn span(&self, position: Position) -> Option<S> {
+        self.spans
+            .iter()
+            .rev()
+            .find(|(start, _)| (start.line, start.column) <= (position.line, position.column))
+            .or(self.spans.first())
+            .map(|(_, span)| *span)
+    }
+}
+
+/// Expands to `compile_error!` with the message at the span.
+fn error(span: Span, message: &str) -> TokenStream {
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+    let mut literal = Literal::string(message);
+    literal.set_span(span);
+    let mut group =
+        proc_macro::Group::new(Delimiter::Parenthesis, TokenTree::Literal(literal).into());
+    group.set_span(span);
+
+    [
+        TokenTree::Ident(proc_macro::Ident::new("compile_error", span)),
+        TokenTree::Punct(bang),
+        TokenTree::Group(group),
+    ]
+    .into_iter()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(tokens: &[(&str, usize, usize)]) -> Source<usize> {
+        let mut source = Source::default();
+        for (i, (text, line, column)) in tokens.iter().enumerate() {
+            source.push(text, *line, *column, i);
+        }
+        source
+    }
+
+    #[test]
+    fn lays_out_tokens() {
+        let source = source(&[
+            ("<", 3, 9),
+            ("label", 3, 10),
+            ("class", 3, 16),
+            ("=", 3, 21),
+            ("\"x\"", 3, 22),
+            (">", 3, 25),
+            ("Hi", 4, 13),
+            ("<", 5, 9),
+            ("/", 5, 10),
+            ("label", 5, 11),
+            (">", 5, 16),
+        ]);
+        assert_eq!(
+            source.text,
+            "        <label class=\"x\">\n            Hi\n        </label>"
+        );
+    }
+
+    #[test]
+    fn maps_positions_to_tokens() {
+        let source = source(&[("<", 3, 9), ("label", 3, 10), ("Hi", 4, 13), (">", 4, 20)]);
+        let at = |line, column| source.span(Position { line, column });
+        assert_eq!(at(1, 1), Some(0));
+        assert_eq!(at(1, 9), Some(0));
+        assert_eq!(at(1, 12), Some(1));
+        assert_eq!(at(2, 14), Some(2));
+        assert_eq!(at(2, 20), Some(3));
+        assert_eq!(at(9, 1), Some(3));
+    }
+
+    #[test]
+    fn separates_tokens_out_of_order() {
+        let source = source(&[("<", 3, 9), ("label", 3, 10), ("/", 2, 1), (">", 3, 15)]);
+        assert_eq!(source.text, "        <label / >");
+        assert_eq!(
+            source.span(Position {
+                line: 1,
+                column: 16
+            }),
+            Some(2)
+        );
+        assert_eq!(
+            source.span(Position {
+                line: 1,
+                column: 18
+            }),
+            Some(3)
+        );
+    }
+}
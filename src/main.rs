@@ -1,7 +1,79 @@
-pub mod ui;
+use std::process::exit;
+
+use mve::ui;
+
+const USAGE: &str =
+    "Usage: mve [build|meta|check|fmt|lint|lang|lsp|clean] [--no-meta] [--rustfmt] [--dry-run] [--force] [--check]";
 
 fn main() {
-    //ui::meta::generate_meta();
+    let mut command = None;
+    let mut options = ui::CompileOptions::default();
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--no-meta" => options.generate_meta = false,
+            "--rustfmt" => options.rustfmt = true,
+            "--dry-run" => options.dry_run = true,
+            "--force" => options.force = true,
+            "--check" => options.check = true,
+            "build" | "meta" | "check" | "fmt" | "lint" | "lang" | "lsp" | "clean"
+                if command.is_none() =>
+            {
+                command = Some(arg)
+            }
+            _ => {
+                eprintln!("Unknown argument '{}'\n{}", arg, USAGE);
+                exit(1);
+            }
+        }
+    }
+
+    if options.check && command.as_deref() != Some("fmt") {
+        eprintln!("'--check' can only be used with fmt\n{}", USAGE);
+        exit(1);
+    }
 
-    ui::compile();
+    match command.as_deref() {
+        Some("meta") => {
+            if let Err(e) = ui::meta::generate_meta() {
+                eprintln!("Failed to generate {} file: {}", ui::consts::META_FILE, e);
+                exit(1);
+            }
+        }
+        Some("check") => {
+            if !ui::check(options) {
+                exit(1);
+            }
+        }
+        Some("fmt") => {
+            if !ui::format(options) {
+                exit(1);
+            }
+        }
+        Some("lint") => {
+            if !ui::lint() {
+                exit(1);
+            }
+        }
+        Some("lang") => {
+            if !ui::lang() {
+                exit(1);
+            }
+        }
+        Some("lsp") => {
+            if !ui::lsp() {
+                exit(1);
+            }
+        }
+        Some("clean") => {
+            if !ui::clean(options) {
+                exit(1);
+            }
+        }
+        _ => {
+            if !ui::compile(options) {
+                exit(1);
+            }
+        }
+    }
 }
@@ -0,0 +1,160 @@
+use crate::ui::consts::{SOURCE_MARKER, UI_COMPILED_PATH};
+use crate::ui::header;
+use crate::ui::meta::workspace::find_workspace_root;
+use serde::Deserialize;
+use std::fs::read_to_string;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    message: Option<Diagnostic>,
+}
+
+#[derive(Deserialize)]
+struct Diagnostic {
+    message: String,
+    level: String,
+    code: Option<DiagnosticCode>,
+    spans: Vec<Span>,
+    children: Vec<Diagnostic>,
+    rendered: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DiagnosticCode {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct Span {
+    file_name: String,
+    line_start: usize,
+    column_start: usize,
+    is_primary: bool,
+    label: Option<String>,
+}
+
+/// A position in a UI file a generated line was produced from.
+struct Origin {
+    source: String,
+    position: String,
+}
+
+/// Runs `cargo check` on the project and reports its diagnostics. Diagnostics pointing into the
+/// generated code are reported at the position in the UI file the code was generated from,
+/// using the source markers written by the code generation. Returns whether the check passed.
+pub fn run() -> bool {
+    let mut child = match Command::new("cargo")
+        .args(["check", "--message-format=json"])
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("Failed to execute cargo check: {}", e);
+            return false;
+        }
+    };
+
+    let root = find_workspace_root().unwrap_or_else(|| PathBuf::from("."));
+    let generated = Path::new(UI_COMPILED_PATH)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(UI_COMPILED_PATH));
+
+    let mut errors = 0;
+    let stdout = child.stdout.take().expect("Failed to read cargo output");
+    for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+        let Ok(message) = serde_json::from_str::<CargoMessage>(&line) else {
+            continue;
+        };
+        let Some(diagnostic) = message.message else {
+            continue;
+        };
+        if message.reason != "compiler-message" {
+            continue;
+        }
+        if diagnostic.level == "error" {
+            errors += 1;
+        }
+
+        let primary = diagnostic.spans.iter().find(|s| s.is_primary);
+        let origin = primary.and_then(|span| {
+            let file = root.join(&span.file_name).canonicalize().ok()?;
+            if !file.starts_with(&generated) {
+                return None;
+            }
+            find_origin(&file, span.line_start)
+        });
+
+        match (origin, primary) {
+            (Some(origin), Some(span)) => report(&diagnostic, &origin, span),
+            _ => {
+                if let Some(rendered) = &diagnostic.rendered {
+                    eprint!("{}", rendered);
+                }
+            }
+        }
+    }
+
+    let success = child.wait().is_ok_and(|status| status.success());
+    success && errors == 0
+}
+
+/// Finds the closest source marker at or above the given line of a generated file.
+fn find_origin(file: &Path, line: usize) -> Option<Origin> {
+    let contents = read_to_string(file).ok()?;
+    let source = header::source(&contents)?.to_string();
+    let position = contents
+        .lines()
+        .take(line)
+        .filter_map(|line| Some(line.split_once(SOURCE_MARKER)?.1.trim().to_string()))
+        .last()?;
+    Some(Origin { source, position })
+}
+
+fn report(diagnostic: &Diagnostic, origin: &Origin, span: &Span) {
+    match &diagnostic.code {
+        Some(code) => eprintln!(
+            "{}[{}]: {}",
+            diagnostic.level, code.code, diagnostic.message
+        ),
+        None => eprintln!("{}: {}", diagnostic.level, diagnostic.message),
+    }
+    eprintln!("  --> {}:{}", origin.source, origin.position);
+    if let Some(label) = span.label.as_ref().filter(|l| !l.is_empty()) {
+        eprintln!("   = {}", label);
+    }
+    for child in &diagnostic.children {
+        eprintln!("   = {}: {}", child.level, child.message);
+    }
+    eprintln!(
+        "   = note: in generated code at {}:{}:{}\n",
+        span.file_name, span.line_start, span.column_start
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_lines_to_markers() {
+        let body = format!(
+            "fn generate() {{\n    let a = 1; {SOURCE_MARKER}2:5\n    let b = 2;\n    let c = 3; {SOURCE_MARKER}4:9\n}}\n"
+        );
+        let contents = header::stamp(Path::new("menu.ui"), &body);
+        let offset = contents.lines().count() - body.lines().count();
+        let file = std::env::temp_dir().join(format!("mve-check-{}.rs", std::process::id()));
+        std::fs::write(&file, &contents).unwrap();
+
+        let at = |line: usize| find_origin(&file, offset + line).map(|o| (o.source, o.position));
+        assert_eq!(at(1), None);
+        assert_eq!(at(2), Some(("menu.ui".to_string(), "2:5".to_string())));
+        assert_eq!(at(3), Some(("menu.ui".to_string(), "2:5".to_string())));
+        assert_eq!(at(5), Some(("menu.ui".to_string(), "4:9".to_string())));
+        let _ = std::fs::remove_file(&file);
+    }
+}
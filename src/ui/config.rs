@@ -0,0 +1,164 @@
+use crate::ui::consts::{CONFIG_PATH, RUNTIME_PATH};
+use serde::Deserialize;
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// Project configuration read from the optional `mve.toml` next to the project's Cargo.toml.
+/// Every setting has a default, so the file only needs to contain what differs.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub modules: ModuleConfig,
+    pub runtime: RuntimeConfig,
+    pub format: FormatConfig,
+    pub lint: LintConfig,
+    pub lang: LangConfig,
+    pub style: StyleConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModuleConfig {
+    pub same_stem: SameStem,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuntimeConfig {
+    /// Path of the crate or module providing the UI runtime, such as a re-export in the engine
+    /// crate of the project. Generated code imports it under [`RUNTIME_ALIAS`].
+    ///
+    /// [`RUNTIME_ALIAS`]: crate::ui::consts::RUNTIME_ALIAS
+    pub path: String,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        RuntimeConfig {
+            path: RUNTIME_PATH.to_string(),
+        }
+    }
+}
+
+/// Settings of the locale files in `assets/lang`.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct LangConfig {
+    /// The locale selected at startup, which missing translations fall back to. The first
+    /// locale in alphabetical order if unset.
+    pub default: Option<String>,
+}
+
+/// Settings of the stylesheets in `assets/ui`.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct StyleConfig {
+    /// The theme selected at startup, which is made of the styles outside of `@theme` blocks
+    /// unless a block declares it. `default` if unset.
+    pub default_theme: Option<String>,
+}
+
+/// Settings of `mve fmt`.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormatConfig {
+    /// Spaces per nesting level.
+    pub indent: usize,
+    /// Tags longer than this are split to put every attribute on its own line.
+    pub max_width: usize,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        FormatConfig {
+            indent: 4,
+            max_width: 100,
+        }
+    }
+}
+
+/// The level of every rule of `mve lint`, named as in suppression comments.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    pub duplicate_id: Level,
+    pub unused_class: Level,
+    pub empty_class: Level,
+    pub nested_wrappers: Level,
+    pub text_container: Level,
+    pub redundant_attribute: Level,
+    /// How many wrappers without attributes may be nested directly in each other.
+    pub max_wrapper_depth: usize,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig {
+            duplicate_id: Level::Error,
+            unused_class: Level::Warn,
+            empty_class: Level::Warn,
+            nested_wrappers: Level::Warn,
+            text_container: Level::Warn,
+            redundant_attribute: Level::Warn,
+            max_wrapper_depth: 2,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Level {
+    Allow,
+    Warn,
+    Error,
+}
+
+/// What to do with UI files in the same directory that only differ in their extension, such as
+/// `menu.ui` and `menu.xml`.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SameStem {
+    /// Refuse to compile and report the files.
+    #[default]
+    Error,
+    /// Append the extension to the module name, generating `menu_ui` and `menu_xml`.
+    Extension,
+}
+
+impl Config {
+    pub fn load() -> Result<Self, String> {
+        Self::load_from(Path::new(""))
+    }
+
+    /// Loads the config of the project in `dir`, for when mve doesn't run in the project itself.
+    pub fn load_from(dir: &Path) -> Result<Self, String> {
+        match read_to_string(dir.join(CONFIG_PATH)) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|e| format!("Failed to parse {}: {}", CONFIG_PATH, e)),
+            Err(_) => Ok(Config::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses() {
+        let config: Config = toml::from_str("[modules]\nsame_stem = \"extension\"").unwrap();
+        assert!(config.modules.same_stem == SameStem::Extension);
+        let config: Config = toml::from_str("").unwrap();
+        assert!(config.modules.same_stem == SameStem::Error);
+        assert_eq!(config.runtime.path, RUNTIME_PATH);
+        let config: Config = toml::from_str("[runtime]\npath = \"engine::core\"").unwrap();
+        assert_eq!(config.runtime.path, "engine::core");
+    }
+
+    #[test]
+    fn unknown_fields_are_errors() {
+        assert!(toml::from_str::<Config>("[module]").is_err());
+        assert!(toml::from_str::<Config>("[modules]\nsame_stems = \"error\"").is_err());
+        assert!(toml::from_str::<Config>("[modules]\nsame_stem = \"rename\"").is_err());
+    }
+}
@@ -1,33 +1,83 @@
+use crate::ui::meta::file::ElementMeta;
 use mvutils::once::Lazy;
 use std::string::ToString;
 use std::sync::RwLock;
 
+/// The built-in elements with their paths relative to the runtime crate.
 const STATIC_ELEMENTS: [(&str, &str); 4] = [
-    ("element", "mvcore::ui::elements::UiElementImpl"),
-    ("", "mvcore::ui::elements::Wrapper"),
+    ("element", "ui::elements::UiElementImpl"),
+    ("", "ui::elements::Wrapper"),
     ("label", "ui::Label"),
     ("button", "ui::Button"),
 ];
 
 pub const META_FILE: &str = "ui.meta";
 pub const META_PATH: &str = "assets/ui/ui.meta";
+pub const META_VERSION: u32 = 2;
 pub const UI_ANNOTATION: &str = "ui_element";
+pub const OVERRIDE_ARGUMENT: &str = "override";
+pub const CONTAINER_ARGUMENT: &str = "container";
+pub const EVENTS_ARGUMENT: &str = "events";
 pub const UI_PATH: &str = "assets/ui";
 pub const UI_COMPILED_PATH: &str = "src/ui/generated";
+/// The module the generated code is in, elements have to be visible from it.
+pub const GENERATED_MODULE: &str = "crate::ui::generated";
+/// The generated modules with constants for every id and class.
+pub const IDS_MODULE: &str = "ids";
+pub const CLASSES_MODULE: &str = "classes";
+/// The generated module with the string table of every locale.
+pub const STRINGS_MODULE: &str = "strings";
+pub const STRINGS_PATH: &str = "crate::ui::generated::strings";
+pub const LANG_PATH: &str = "assets/lang";
+/// The generated module selecting the theme of the stylesheets.
+pub const THEMES_MODULE: &str = "themes";
+pub const THEMES_PATH: &str = "crate::ui::generated::themes";
+/// Marks text or an attribute value as the key of a translated string, as in `@str/menu.start`.
+pub const STRING_PREFIX: &str = "@str/";
+
+pub const SOURCE_PATH: &str = "src";
+pub const MANIFEST_FILE: &str = "Cargo.toml";
+pub const CONFIG_PATH: &str = "mve.toml";
 
 pub const UI_MOD_PATH: &str = "src/ui/mod.rs";
+pub const UI_FILE_PATH: &str = "src/ui.rs";
+pub const ADDED_MARKER: &str = "// added by mve";
+pub const GENERATED_PREFIX: &str = "// @generated by mve from ";
+pub const CHECKSUM_PREFIX: &str = "// checksum: ";
+/// Marks a generated line with the `line:column` of the UI markup it was generated from.
+pub const SOURCE_MARKER: &str = "// ui: ";
+/// Starts a comment suppressing lints, such as `<!-- mve-lint: allow(unused_class) -->`.
+pub const LINT_SUPPRESSION: &str = "mve-lint:";
 pub const LIB_PATH: &str = "src/lib.rs";
 pub const MAIN_PATH: &str = "src/main.rs";
 
-pub const ATTRIBUTE_PATH: &str = "mvcore::ui::attributes::Attributes";
-pub const STYLE_PATH: &str = "mvcore::ui::style::Style";
+/// The name the runtime crate is imported as in generated code, see [`RUNTIME_PATH`].
+pub const RUNTIME_ALIAS: &str = "runtime";
+pub const RUNTIME_PATH: &str = "mvcore";
+pub const ATTRIBUTE_PATH: &str = "runtime::ui::attributes::Attributes";
+pub const STYLE_PATH: &str = "runtime::ui::style::Style";
+pub const UI_ELEMENT_PATH: &str = "runtime::ui::elements::UiElement";
+pub const CHILD_PATH: &str = "runtime::ui::elements::Child";
+pub const HANDLE_PATH: &str = "runtime::ui::elements::Handle";
 
 pub const UI_EXTENSIONS: [&str; 3] = [".xml", ".ui", ".mui"];
 pub const STYLE_EXTENSIONS: [&str; 3] = [".style", ".st", ".mst"];
 
-pub static ELEMENTS: Lazy<RwLock<Vec<(String, String)>>> = Lazy::new(|| {
+/// Keywords which can't be used as plain identifiers in any edition.
+pub const RUST_KEYWORDS: [&str; 51] = [
+    "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where",
+    "while", "async", "await", "dyn", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "typeof", "unsized", "virtual", "yield", "try",
+];
+/// Keywords which can't be used as raw identifiers either.
+pub const NON_RAW_KEYWORDS: [&str; 4] = ["crate", "self", "Self", "super"];
+
+pub static ELEMENTS: Lazy<RwLock<Vec<ElementMeta>>> = Lazy::new(|| builtin_elements().into());
+
+pub fn builtin_elements() -> Vec<ElementMeta> {
     STATIC_ELEMENTS
-        .map(|(tag, path)| (tag.to_string(), path.to_string()))
+        .map(|(tag, path)| ElementMeta::new(tag.to_string(), format!("{RUNTIME_ALIAS}::{path}")))
         .to_vec()
-        .into()
-});
+}
@@ -0,0 +1,116 @@
+use crate::ui::consts::{CHECKSUM_PREFIX, GENERATED_PREFIX, UI_COMPILED_PATH};
+use std::fs::{read_dir, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+/// Prepends the `@generated` header naming the source and the checksum of the body.
+pub fn stamp(source: &Path, body: &str) -> String {
+    format!(
+        "{}{}\n{}{:016x}\n{}",
+        GENERATED_PREFIX,
+        source.to_string_lossy().replace('\\', "/"),
+        CHECKSUM_PREFIX,
+        checksum(body),
+        body
+    )
+}
+
+/// Recomputes the checksum of a file after it has been changed by mve itself, e.g. by rustfmt.
+pub fn restamp(path: &Path) -> Result<(), std::io::Error> {
+    let contents = read_to_string(path)?;
+    if let Some((source, _, body)) = split(&contents) {
+        write(path, stamp(Path::new(source), body))?;
+    }
+    Ok(())
+}
+
+/// The source a generated file was generated from, if it has a header.
+pub fn source(contents: &str) -> Option<&str> {
+    split(contents).map(|(source, _, _)| source)
+}
+
+/// Finds the generated files whose contents no longer match their checksum. Files without a
+/// header are not checked.
+pub fn find_modified() -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut res = Vec::new();
+    if Path::new(UI_COMPILED_PATH).exists() {
+        collect_modified(Path::new(UI_COMPILED_PATH), &mut res)?;
+    }
+    res.sort();
+    Ok(res)
+}
+
+fn collect_modified(dir: &Path, res: &mut Vec<PathBuf>) -> Result<(), std::io::Error> {
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_modified(&path, res)?;
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            let contents = read_to_string(&path)?;
+            if let Some((_, sum, body)) = split(&contents) {
+                if sum != format!("{:016x}", checksum(body)) {
+                    res.push(path);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Splits a generated file into its source, checksum and body.
+fn split(contents: &str) -> Option<(&str, &str, &str)> {
+    let (first, rest) = contents.split_once('\n')?;
+    let source = first.strip_prefix(GENERATED_PREFIX)?;
+    let (second, body) = rest.split_once('\n').unwrap_or((rest, ""));
+    let sum = second.strip_prefix(CHECKSUM_PREFIX)?;
+    Some((source, sum.trim_end(), body))
+}
+
+/// 64 bit FNV-1a, which is stable across Rust versions unlike the std hashers.
+fn checksum(body: &str) -> u64 {
+    body.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let body = "pub fn generate() {}\n";
+        let stamped = stamp(Path::new("assets\\ui\\menu.ui"), body);
+        let (source, sum, rest) = split(&stamped).unwrap();
+        assert_eq!(source, "assets/ui/menu.ui");
+        assert_eq!(sum, format!("{:016x}", checksum(body)));
+        assert_eq!(rest, body);
+        assert!(split(body).is_none());
+        assert_ne!(checksum(body), checksum("pub fn generate() { }\n"));
+    }
+
+    #[test]
+    fn finds_modified_files() {
+        let dir = std::env::temp_dir().join(format!("mve-header-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        let source = Path::new("assets/ui/menu.ui");
+        write(dir.join("kept.rs"), stamp(source, "fn a() {}\n")).unwrap();
+        write(
+            dir.join("sub/edited.rs"),
+            stamp(source, "fn a() {}\n") + "fn b() {}\n",
+        )
+        .unwrap();
+        write(dir.join("mod.rs"), "pub mod kept;\n").unwrap();
+        write(dir.join("notes.txt"), stamp(source, "") + "edited").unwrap();
+
+        let mut res = Vec::new();
+        collect_modified(&dir, &mut res).unwrap();
+        assert_eq!(res, [dir.join("sub/edited.rs")]);
+
+        restamp(&dir.join("sub/edited.rs")).unwrap();
+        res.clear();
+        collect_modified(&dir, &mut res).unwrap();
+        assert!(res.is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+use crate::ui::config::Config;
+use crate::ui::consts::{builtin_elements, CONFIG_PATH, ELEMENTS, META_FILE, META_PATH, UI_PATH};
+use crate::ui::style::cascade::Styles;
+use crate::ui::xml::lexer::Position;
+use crate::ui::xml::tree::{Child, Node};
+use crate::ui::xml::{self, codegen};
+use mvutils::utils::Recover;
+use std::fmt::Display;
+use std::fs::{read_dir, File};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// The project loaded last, the elements of its meta file are the ones in [`ELEMENTS`].
+static PROJECT: Mutex<Option<Project>> = Mutex::new(None);
+
+/// The runtime path and stylesheets of a project, with the files they were loaded from and when
+/// those were modified, so the project is loaded again when one of them changes.
+struct Project {
+    dir: PathBuf,
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    loaded: Result<(String, Styles), String>,
+}
+
+#[derive(Debug)]
+pub struct InlineError {
+    pub message: String,
+    /// Where in the markup the error is, or `None` if it isn't about the markup.
+    pub position: Option<Position>,
+}
+
+impl Display for InlineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.position {
+            Some(position) => write!(f, "{}: {}", position, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Compiles markup written inline in Rust code, as by the `ui!` macro, to a block expression
+/// evaluating to the element. The config and meta file of the project in `dir` are kept for
+/// the following calls, until they or the stylesheets change or a different project is used.
+pub fn compile(ui: &str, dir: &Path) -> Result<String, InlineError> {
+    let mut project = PROJECT.lock().recover();
+    let files = files(dir);
+    let project = match &mut *project {
+        Some(project) if project.dir == dir && project.files == files => project,
+        project => {
+            *ELEMENTS.write().recover() = builtin_elements();
+            project.insert(Project {
+                dir: dir.to_path_buf(),
+                files,
+                loaded: load(dir),
+            })
+        }
+    };
+    let (runtime, styles) = project.loaded.as_ref().map_err(|message| InlineError {
+        message: message.clone(),
+        position: None,
+    })?;
+
+    let tree = xml::parse(ui).map_err(|e| InlineError {
+        message: e.message,
+        position: Some(e.position),
+    })?;
+    if tree.is_empty() {
+        return Err(InlineError {
+            message: "Expected at least one element".to_string(),
+            position: None,
+        });
+    }
+    tree.iter().try_for_each(check_tags)?;
+    codegen::expression(tree, runtime, styles).map_err(|e| InlineError {
+        message: e.message,
+        position: Some(e.position),
+    })
+}
+
+fn load(dir: &Path) -> Result<(String, Styles), String> {
+    let config = Config::load_from(dir)?;
+    if let Ok(file) = File::open(dir.join(META_PATH)) {
+        super::load_meta(file).map_err(|e| format!("Failed to load {} file: {}", META_FILE, e))?;
+    }
+    let styles = super::load_styles(dir, &config).map_err(|errors| errors.join("\n"))?;
+    Ok((config.runtime.path, styles))
+}
+
+/// The config, meta and style files of the project in `dir` with their modification times.
+fn files(dir: &Path) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut files = vec![dir.join(CONFIG_PATH), dir.join(META_PATH)];
+    if let Ok(ui_dir) = read_dir(dir.join(UI_PATH)) {
+        let _ = super::process(ui_dir, "".into(), &mut files, &mut Vec::new());
+    }
+    files
+        .into_iter()
+        .map(|file| {
+            let modified = file.metadata().and_then(|m| m.modified()).ok();
+            (file, modified)
+        })
+        .collect()
+}
+
+/// Reports the first tag which isn't a known element, which the code generator would panic on.
+fn check_tags(node: &Node) -> Result<(), InlineError> {
+    let children = match node {
+        Node::Named(named) => {
+            if !ELEMENTS.read().recover().iter().any(|e| e.tag == named.tag) {
+                return Err(InlineError {
+                    message: format!("Unknown element '{}'", named.tag),
+                    position: Some(named.position),
+                });
+            }
+            &named.children
+        }
+        Node::Unnamed(unnamed) => &unnamed.children,
+    };
+    for child in children {
+        if let Child::Node(child) = child {
+            check_tags(child)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,491 @@
+use crate::ui::consts::{LANG_PATH, STRING_PREFIX};
+use crate::ui::linker::module_name;
+use crate::ui::rust::{self, Block, Expr, Ident, Item, Path, Type};
+use crate::ui::style::tree::{unquote, StyleSheet};
+use crate::ui::xml::codegen::struct_name;
+use crate::ui::xml::lexer::{Literal, Position};
+use crate::ui::xml::tree::{Child, Node};
+use hashbrown::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path as FilePath, PathBuf};
+
+/// The docs of the generated functions selecting the locale and returning the selected one.
+const SELECTION_DOCS: [&str; 2] = [
+    "Selects the locale strings are looked up in by its code, like `\"en\"`. Returns false if there\n\
+     is no such locale.",
+    "The code of the selected locale.",
+];
+
+/// The key of a translated string if the text is a reference to one, like `@str/menu.start`.
+pub fn reference(text: &str) -> Option<&str> {
+    text.strip_prefix(STRING_PREFIX)
+}
+
+/// The name of the variant of the generated `Key` enum for a key, e.g. `MenuStart` for
+/// `menu.start`.
+pub fn variant(key: &str) -> Option<Ident> {
+    module_name(key).map(|name| struct_name(&name))
+}
+
+/// The strings of every locale file in `assets/lang`, like `en.toml`. Nested tables are
+/// flattened, so `start` in the `[menu]` table has the key `menu.start`.
+pub struct Locales {
+    /// The strings of every locale by key, with the default locale first.
+    locales: Vec<(String, BTreeMap<String, String>)>,
+    /// The keys of every locale.
+    keys: BTreeSet<String>,
+}
+
+impl Locales {
+    /// Loads the locale files, or returns `None` if there are none. Fails with every file that
+    /// can't be read and every key that gives no usable name or the same name as another key.
+    pub fn load(default: Option<&str>) -> Result<Option<Self>, Vec<String>> {
+        let Ok(dir) = read_dir(LANG_PATH) else {
+            return Ok(None);
+        };
+        let mut files: Vec<PathBuf> = dir
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        files.sort();
+
+        let mut errors = Vec::new();
+        let mut sources = Vec::new();
+        for file in files {
+            match read_to_string(&file) {
+                Ok(source) => sources.push((file, source)),
+                Err(e) => errors.push(format!(
+                    "Failed to read locale file {}: {}",
+                    file.display(),
+                    e
+                )),
+            }
+        }
+        match Self::parse(sources, default) {
+            Ok(locales) if errors.is_empty() => Ok(locales),
+            Ok(_) => Err(errors),
+            Err(e) => {
+                errors.extend(e);
+                Err(errors)
+            }
+        }
+    }
+
+    /// Parses the contents of the locale files, named by locale code, with the same errors as
+    /// [`Locales::load`].
+    fn parse(
+        sources: Vec<(PathBuf, String)>,
+        default: Option<&str>,
+    ) -> Result<Option<Self>, Vec<String>> {
+        let mut errors = Vec::new();
+        let mut locales = Vec::new();
+        for (file, source) in sources {
+            let code = file.file_stem().unwrap_or_default().to_string_lossy();
+            let table = match toml::from_str::<toml::Table>(&source) {
+                Ok(table) => table,
+                Err(e) => {
+                    errors.push(format!(
+                        "Failed to read locale file {}: {}",
+                        file.display(),
+                        e.message()
+                    ));
+                    continue;
+                }
+            };
+            let mut strings = BTreeMap::new();
+            flatten(&file, "", table, &mut strings, &mut errors);
+            locales.push((code.to_string(), strings));
+        }
+        if locales.is_empty() && errors.is_empty() {
+            return Ok(None);
+        }
+
+        if let Some(default) = default {
+            match locales.iter().position(|(code, _)| code == default) {
+                Some(i) => {
+                    let locale = locales.remove(i);
+                    locales.insert(0, locale);
+                }
+                None => errors.push(format!(
+                    "The default locale '{}' has no file in {}",
+                    default, LANG_PATH
+                )),
+            }
+        }
+
+        let keys: BTreeSet<String> = locales
+            .iter()
+            .flat_map(|(_, strings)| strings.keys().cloned())
+            .collect();
+        let mut names: HashMap<String, String> = HashMap::new();
+        for key in &keys {
+            match variant(key) {
+                None => errors.push(format!(
+                    "Key '{}' in {} contains no usable characters",
+                    key, LANG_PATH
+                )),
+                Some(name) => match names.get(&name.to_string()) {
+                    Some(other) => errors.push(format!(
+                        "Keys '{}' and '{}' in {} both give the name {}, rename one of them",
+                        other, key, LANG_PATH, name
+                    )),
+                    None => {
+                        names.insert(name.to_string(), key.clone());
+                    }
+                },
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(Some(Locales { locales, keys }))
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The codes of the locales, the default one first.
+    pub fn codes(&self) -> impl Iterator<Item = &str> {
+        self.locales.iter().map(|(code, _)| code.as_str())
+    }
+
+    /// The keys which are missing in the locale, out of those of every locale.
+    pub fn missing(&self, code: &str) -> Vec<&str> {
+        let Some((_, strings)) = self.locales.iter().find(|(c, _)| c == code) else {
+            return Vec::new();
+        };
+        self.keys
+            .iter()
+            .filter(|key| !strings.contains_key(*key))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// The `strings` module, with a `Key` variant for every key, the string of every key in
+    /// every locale, and functions to select the locale and look up strings. Missing
+    /// translations fall back to the default locale, or the first locale that has the key.
+    pub fn generate(&self) -> String {
+        let str_type = || Type::Ref(Box::new(Type::Path(Path::parse("str"))));
+        let mut variants = Vec::new();
+        let mut rows = Vec::new();
+        for key in &self.keys {
+            let fallback = self
+                .locales
+                .iter()
+                .find_map(|(_, strings)| strings.get(key))
+                .cloned()
+                .unwrap_or_default();
+            variants.push((
+                variant(key).expect("Keys are checked when loading"),
+                Some(format!("{:?}", fallback)),
+            ));
+            rows.push(Expr::Array(
+                self.locales
+                    .iter()
+                    .map(|(_, strings)| Expr::Str(strings.get(key).unwrap_or(&fallback).clone()))
+                    .collect(),
+            ));
+        }
+
+        let mut items = vec![
+            Item::Use {
+                path: Path::parse("std::sync::atomic::AtomicUsize"),
+                alias: None,
+                allow_unused: false,
+            },
+            Item::Use {
+                path: Path::parse("std::sync::atomic::Ordering"),
+                alias: None,
+                allow_unused: false,
+            },
+            Item::Enum {
+                name: Ident::new("Key"),
+                variants,
+            },
+            Item::Const {
+                name: Ident::new("LOCALES"),
+                ty: Type::Array(Box::new(str_type()), self.locales.len()),
+                value: Expr::Array(self.codes().map(|c| Expr::Str(c.to_string())).collect()),
+            },
+            Item::Const {
+                name: Ident::new("STRINGS"),
+                ty: Type::Array(
+                    Box::new(Type::Array(Box::new(str_type()), self.locales.len())),
+                    self.keys.len(),
+                ),
+                value: Expr::Array(rows),
+            },
+        ];
+        items.extend(rust::selection("LOCALES", "locale", "code", SELECTION_DOCS));
+        let locale = Expr::path("LOCALE").method("load", vec![Expr::path("Ordering::Relaxed")]);
+        items.push(Item::Fn {
+            public: true,
+            docs: Some("The string in the selected locale.".to_string()),
+            name: Ident::new("get"),
+            params: vec![(Ident::new("key"), Type::Path(Path::parse("Key")))],
+            ret: Type::StaticRef(Box::new(Type::Path(Path::parse("str")))),
+            body: Block {
+                stmts: Vec::new(),
+                tail: Some(Box::new(
+                    Expr::path("STRINGS")
+                        .index(Expr::Cast(
+                            Box::new(Expr::path("key")),
+                            Type::Path(Path::parse("usize")),
+                        ))
+                        .index(locale),
+                )),
+            },
+        });
+        rust::print_items(&items)
+    }
+}
+
+/// Checks that every string a UI or style file refers to, found with [`references`] or
+/// [`style_references`], has a usable name and exists in every locale. Returns an error for
+/// every reference that doesn't.
+pub fn check(
+    locales: Option<&Locales>,
+    file: &FilePath,
+    references: &[(String, Position)],
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (key, position) in references {
+        if variant(key).is_none() {
+            errors.push(format!(
+                "error: String '{}' contains no usable characters\n  --> {}:{}\n",
+                key,
+                file.display(),
+                position
+            ));
+            continue;
+        }
+        let missing: Vec<&str> = match locales {
+            None => {
+                errors.push(format!(
+                    "error: String '{}' is used, but there are no locale files in {}\n  --> {}:{}\n",
+                    key,
+                    LANG_PATH,
+                    file.display(),
+                    position
+                ));
+                continue;
+            }
+            Some(locales) => locales
+                .locales
+                .iter()
+                .filter(|(_, strings)| !strings.contains_key(key))
+                .map(|(code, _)| code.as_str())
+                .collect(),
+        };
+        if !missing.is_empty() {
+            errors.push(format!(
+                "error: String '{}' is missing in locale {}\n  --> {}:{}\n",
+                key,
+                missing.join(", "),
+                file.display(),
+                position
+            ));
+        }
+    }
+    errors
+}
+
+/// Flattens a table of strings into dotted keys. Values which aren't strings or tables are
+/// reported as errors.
+fn flatten(
+    file: &FilePath,
+    prefix: &str,
+    table: toml::Table,
+    strings: &mut BTreeMap<String, String>,
+    errors: &mut Vec<String>,
+) {
+    for (name, value) in table {
+        let key = if prefix.is_empty() {
+            name
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        match value {
+            toml::Value::String(s) => {
+                strings.insert(key, s);
+            }
+            toml::Value::Table(table) => flatten(file, &key, table, strings, errors),
+            _ => errors.push(format!(
+                "Value of '{}' in locale file {} is not a string",
+                key,
+                file.display()
+            )),
+        }
+    }
+}
+
+/// Every string a tree refers to, with the position of the element or attribute referring to
+/// it. Text has no position of its own, so the element containing it is used.
+pub fn references(tree: &[Node]) -> Vec<(String, Position)> {
+    let mut res = Vec::new();
+    for node in tree {
+        collect_references(node, &mut res);
+    }
+    res
+}
+
+/// Every string the declarations and variables of a stylesheet refer to, with their positions.
+pub fn style_references(sheet: &StyleSheet) -> Vec<(String, Position)> {
+    let declarations = sheet
+        .rules
+        .iter()
+        .flat_map(|rule| &rule.declarations)
+        .map(|d| (&d.value, d.position));
+    let variables = sheet.variables.iter().map(|v| (&v.value, v.position));
+    declarations
+        .chain(variables)
+        .filter_map(|(value, position)| {
+            reference(unquote(value)).map(|key| (key.to_string(), position))
+        })
+        .collect()
+}
+
+fn collect_references(node: &Node, res: &mut Vec<(String, Position)>) {
+    let (children, position) = match node {
+        Node::Named(named) => {
+            for attribute in &named.attributes {
+                if let Literal::String(value) = &attribute.value {
+                    if let Some(key) = reference(value) {
+                        res.push((key.to_string(), attribute.position));
+                    }
+                }
+            }
+            (&named.children, named.position)
+        }
+        Node::Unnamed(unnamed) => (&unnamed.children, unnamed.position),
+    };
+    for child in children {
+        match child {
+            Child::Node(child) => collect_references(child, res),
+            Child::String(text) => {
+                if let Some(key) = reference(text) {
+                    res.push((key.to_string(), position));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locales(files: &[(&str, &str)], default: Option<&str>) -> Result<Locales, Vec<String>> {
+        let sources = files
+            .iter()
+            .map(|(code, source)| (PathBuf::from(format!("{code}.toml")), source.to_string()))
+            .collect();
+        Locales::parse(sources, default).map(|l| l.expect("There are locale files"))
+    }
+
+    const FILES: [(&str, &str); 3] = [
+        (
+            "de",
+            "title = \"Titel\"\n[menu]\nstart = \"Starten\"\nextra = \"Mehr\"",
+        ),
+        ("en", "title = \"Title\"\n[menu]\nstart = \"Start\""),
+        ("fr", "title = \"Titre\""),
+    ];
+
+    #[test]
+    fn fallback() {
+        let locales = locales(&FILES, Some("en")).unwrap();
+        assert_eq!(locales.codes().collect::<Vec<_>>(), ["en", "de", "fr"]);
+        assert_eq!(locales.missing("en"), ["menu.extra"]);
+        assert_eq!(locales.missing("fr"), ["menu.extra", "menu.start"]);
+
+        // Missing strings fall back to the default locale, or else the first one with the key.
+        let generated = locales.generate();
+        assert!(generated.contains(
+            "pub const STRINGS: [[&str; 3]; 3] = [
+    [\"Mehr\", \"Mehr\", \"Mehr\"],
+    [\"Start\", \"Starten\", \"Start\"],
+    [\"Title\", \"Titel\", \"Titre\"],
+];"
+        ));
+    }
+
+    #[test]
+    fn alphabetical_default() {
+        let locales = locales(&FILES, None).unwrap();
+        assert_eq!(locales.codes().collect::<Vec<_>>(), ["de", "en", "fr"]);
+        assert!(locales
+            .generate()
+            .contains("[\"Starten\", \"Start\", \"Starten\"],"));
+    }
+
+    #[test]
+    fn errors() {
+        let errors = locales(
+            &[
+                ("en", "menu_start = \"a\"\nmenu.start = \"b\"\nnumber = 1"),
+                ("de", "title = "),
+            ],
+            Some("fr"),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(errors.len(), 4);
+        assert_eq!(
+            errors[0],
+            "Value of 'number' in locale file en.toml is not a string"
+        );
+        assert!(errors[1].starts_with("Failed to read locale file de.toml"));
+        assert_eq!(
+            errors[2],
+            format!("The default locale 'fr' has no file in {LANG_PATH}")
+        );
+        assert!(errors[3].starts_with("Keys 'menu.start' and 'menu_start'"));
+    }
+
+    #[test]
+    fn references() {
+        let locales = locales(&FILES, Some("en")).unwrap();
+        let tree = crate::ui::xml::parse(
+            "<column label=\"@str/menu.start\">\n<label>@str/title</label>\n<label>@str/menu.extra</label>\n</column>",
+        )
+        .unwrap();
+        let errors = check(
+            Some(&locales),
+            FilePath::new("main.ui"),
+            &super::references(&tree),
+        );
+        assert_eq!(
+            errors,
+            [
+                "error: String 'menu.start' is missing in locale fr\n  --> main.ui:1:9\n",
+                "error: String 'menu.extra' is missing in locale en, fr\n  --> main.ui:3:1\n",
+            ]
+        );
+        assert_eq!(
+            check(None, FilePath::new("main.ui"), &super::references(&tree)).len(),
+            3
+        );
+    }
+
+    #[test]
+    fn style_references() {
+        let locales = locales(&FILES, Some("en")).unwrap();
+        let sheet = crate::ui::style::parser::parse(
+            "--name: \"@str/menu.extra\";\nlabel {\n  text: @str/title;\n  hint: \"@str/...\";\n}",
+        )
+        .unwrap();
+        let errors = check(
+            Some(&locales),
+            FilePath::new("main.style"),
+            &super::style_references(&sheet),
+        );
+        assert_eq!(
+            errors,
+            [
+                "error: String '...' contains no usable characters\n  --> main.style:4:3\n",
+                "error: String 'menu.extra' is missing in locale en, fr\n  --> main.style:1:1\n",
+            ]
+        );
+    }
+}
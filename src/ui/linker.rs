@@ -1,160 +1,555 @@
-use crate::ui::consts::{LIB_PATH, MAIN_PATH, UI_COMPILED_PATH, UI_MOD_PATH};
-use crate::ui::meta::lexer::Lexer;
-use crate::ui::meta::token::{Keyword, Operator, Token};
-use hashbrown::HashSet;
-use std::fs::{read_dir, File, OpenOptions};
+use crate::ui::consts::{
+    ADDED_MARKER, LIB_PATH, MAIN_PATH, NON_RAW_KEYWORDS, RUST_KEYWORDS, UI_COMPILED_PATH,
+    UI_FILE_PATH, UI_MOD_PATH, UI_PATH,
+};
+use crate::ui::header;
+use crate::ui::meta::parser::{Item, ItemKind, Parser, Visibility};
+use crate::ui::meta::token::{Operator, Token};
+use hashbrown::{HashMap, HashSet};
+use std::fs::{
+    read_dir, read_to_string, remove_dir, remove_dir_all, remove_file, write, File, OpenOptions,
+};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
-pub fn generate_modules(paths: Vec<PathBuf>) -> Result<(), std::io::Error> {
-    let mut modules: HashSet<PathBuf> = ["".into()].into();
+/// Writes a `mod.rs` declaring the contents of every directory of the generated code. Takes
+/// the UI files together with their outputs.
+pub fn generate_modules(files: &[(PathBuf, PathBuf)], dry_run: bool) -> Result<(), std::io::Error> {
+    let mut modules: HashMap<PathBuf, PathBuf> = [("".into(), UI_PATH.into())].into();
 
-    for path in paths {
+    for (ui, path) in files {
         if let Ok(relative_path) = path.strip_prefix(UI_COMPILED_PATH) {
             let parent = relative_path.parent().unwrap();
+            let source: Vec<_> = ui.strip_prefix(UI_PATH).unwrap_or(ui).iter().collect();
 
             for ancestor in parent.ancestors() {
                 if ancestor != Path::new("") {
-                    modules.insert(ancestor.to_path_buf());
+                    let depth = ancestor.iter().count();
+                    let source = source[..depth]
+                        .iter()
+                        .fold(PathBuf::from(UI_PATH), |dir, c| dir.join(c));
+                    modules.insert(ancestor.to_path_buf(), source);
                 }
             }
         }
     }
 
-    for module in modules {
+    for (module, source) in modules {
         let path = Path::new(UI_COMPILED_PATH).join(&module);
-        let mut file = File::create(path.join("mod.rs"))?;
+        let mut names = Vec::new();
 
-        for entry in read_dir(path)? {
+        for entry in read_dir(&path)? {
             let entry = entry?;
             let path = entry.path();
 
             if path.is_dir() {
-                let module_name = path.file_name().unwrap().to_str().unwrap();
-                writeln!(file, "pub mod {};", module_name)?;
+                names.push(path.file_name().unwrap().to_str().unwrap().to_string());
             } else if let Some(ext) = path.extension() {
                 if ext == "rs" {
                     let stem = path.file_stem().unwrap().to_str().unwrap();
                     if stem != "mod" {
-                        writeln!(file, "pub mod {};", stem)?;
+                        names.push(stem.to_string());
                     }
                 }
             }
         }
+
+        names.sort();
+        let body: String = names
+            .iter()
+            .map(|name| format!("pub mod {};\n", module_ident(name)))
+            .collect();
+        write(path.join("mod.rs"), header::stamp(&source, &body))?;
     }
 
-    update_ui_mod_file()?;
-    format();
+    update_ui_mod_file(dry_run)?;
 
     Ok(())
 }
 
-fn update_ui_mod_file() -> Result<(), std::io::Error> {
+/// Maps a file or directory name to a valid Rust identifier to be used as module and file
+/// name. The name is converted to snake_case, prefixed with `_` if it would start with a digit
+/// and suffixed with `_` if it is a keyword which can't be used as raw identifier. Returns
+/// `None` if the name contains nothing usable.
+pub fn module_name(name: &str) -> Option<String> {
+    let chars: Vec<char> = name.chars().collect();
+    let mut res = String::with_capacity(name.len());
+
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() {
+            let prev = i.checked_sub(1).map(|i| chars[i]);
+            let next = chars.get(i + 1);
+            let boundary = prev.is_some_and(|p| p.is_ascii_lowercase() || p.is_ascii_digit())
+                || (prev.is_some_and(|p| p.is_ascii_uppercase())
+                    && next.is_some_and(|n| n.is_ascii_lowercase()));
+            if boundary {
+                res.push('_');
+            }
+            res.push(c.to_ascii_lowercase());
+        } else if c.is_ascii_alphanumeric() {
+            res.push(*c);
+        } else if !res.ends_with('_') {
+            res.push('_');
+        }
+    }
+
+    let res = res.trim_matches('_');
+    if res.is_empty() {
+        return None;
+    }
+
+    let mut res = res.to_string();
+    if res.starts_with(|c: char| c.is_ascii_digit()) {
+        res.insert(0, '_');
+    }
+    if NON_RAW_KEYWORDS.contains(&res.as_str()) {
+        res.push('_');
+    }
+    Some(res)
+}
+
+/// The identifier a module is declared with, which is a raw identifier for keywords.
+pub fn module_ident(name: &str) -> String {
+    if RUST_KEYWORDS.contains(&name) {
+        format!("r#{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// An addition mve has to make to a source file of the user.
+struct Edit {
+    path: PathBuf,
+    text: String,
+    create: bool,
+}
+
+impl Edit {
+    fn apply(&self, dry_run: bool) -> Result<(), std::io::Error> {
+        if dry_run {
+            let action = if self.create { "create" } else { "append to" };
+            println!("Would {} {}:", action, self.path.display());
+            for line in self.text.lines().filter(|l| !l.is_empty()) {
+                println!("+ {}", line);
+            }
+            return Ok(());
+        }
+
+        let mut file = if self.create {
+            File::create(&self.path)?
+        } else {
+            OpenOptions::new().append(true).open(&self.path)?
+        };
+        file.write_all(self.text.as_bytes())
+    }
+}
+
+#[derive(PartialEq)]
+enum Declaration {
+    Missing,
+    Conditional,
+    Present,
+}
+
+/// Makes sure the generated code is reachable: the ui module declares and re-exports the
+/// generated module, and the crate root declares the ui module. Only items at the top level of
+/// those files count, and declarations behind `#[cfg(...)]` are reported instead of duplicated.
+/// In a crate with both a library and a binary, the ui module is added to the library.
+fn update_ui_mod_file(dry_run: bool) -> Result<(), std::io::Error> {
     let generated = "generated";
     let ui = "ui";
 
-    if !update_file_if_exists(UI_MOD_PATH, generated)? {
-        let mut ui_mod_file = File::create(UI_MOD_PATH)?;
-        writeln!(
-            ui_mod_file,
-            "pub mod {};\npub use {}::*;",
-            generated, generated
-        )?;
+    let mut edits = Vec::new();
+
+    match ui_mod_file() {
+        None => edits.push(Edit {
+            path: UI_MOD_PATH.into(),
+            text: format!(
+                "{}\n{}\n",
+                added(&format!("pub mod {};", generated)),
+                added(&format!("pub use {}::*;", generated))
+            ),
+            create: true,
+        }),
+        Some(file) => {
+            let items = parse_file(file)?;
+            let mut lines = Vec::new();
+            match declaration(&items, generated) {
+                Declaration::Missing => lines.push(added(&format!("pub mod {};", generated))),
+                Declaration::Conditional => eprintln!(
+                    "Warning: Module '{}' in {} is only declared conditionally, not adding it",
+                    generated, file
+                ),
+                Declaration::Present => {}
+            }
+            if !exports_all(&items, generated) {
+                lines.push(added(&format!("pub use {}::*;", generated)));
+            }
+            if !lines.is_empty() {
+                edits.push(Edit {
+                    path: file.into(),
+                    text: format!("\n{}\n", lines.join("\n")),
+                    create: false,
+                });
+            }
+        }
+    }
+
+    let mut roots = Vec::new();
+    for root in [LIB_PATH, MAIN_PATH] {
+        if Path::new(root).exists() {
+            roots.push((root, declaration(&parse_file(root)?, ui)));
+        }
+    }
+    if roots.iter().all(|(_, d)| *d == Declaration::Missing) {
+        if let Some((root, _)) = roots.first() {
+            edits.push(Edit {
+                path: root.into(),
+                text: format!("\n{}\n", added(&format!("pub mod {};", ui))),
+                create: false,
+            });
+        }
     } else {
-        check_public_export(UI_MOD_PATH, generated)?;
+        for (root, d) in &roots {
+            if *d == Declaration::Conditional {
+                eprintln!(
+                    "Warning: Module '{}' in {} is only declared conditionally",
+                    ui, root
+                );
+            }
+        }
     }
 
-    if !update_file_if_exists(MAIN_PATH, ui)? {
-        update_file_if_exists(LIB_PATH, ui)?;
+    for edit in edits {
+        edit.apply(dry_run)?;
     }
 
     Ok(())
 }
 
-fn update_file_if_exists(file: &str, module: &str) -> Result<bool, std::io::Error> {
-    fn exists(lexer: Lexer, module: &str) -> bool {
-        let mut lexer = lexer.peekable();
-        while let Some(token) = lexer.next() {
-            match token {
-                Token::Keyword(Keyword::Mod) => {
-                    if let Some(Token::Ident(name)) | Some(Token::RawIdent(name)) = lexer.peek() {
-                        if name == module {
-                            return true;
-                        }
+/// Removes the generated code and the declarations mve added to the project's own source files.
+/// The ui module file is removed as well if nothing else is left in it, and only then is the
+/// `pub mod ui;` line removed from the crate root.
+pub fn clean(dry_run: bool) -> Result<(), std::io::Error> {
+    if Path::new(UI_COMPILED_PATH).exists() {
+        if dry_run {
+            println!("Would remove {}", UI_COMPILED_PATH);
+        } else {
+            remove_dir_all(UI_COMPILED_PATH)?;
+        }
+    }
+
+    let mut ui_removed = true;
+    if let Some(file) = ui_mod_file() {
+        let rest = remove_added_lines(file, dry_run)?;
+        if rest.trim().is_empty() {
+            if dry_run {
+                println!("Would remove {}", file);
+            } else {
+                remove_file(file)?;
+                if let Some(parent) = Path::new(file).parent() {
+                    if read_dir(parent)?.next().is_none() {
+                        remove_dir(parent)?;
                     }
                 }
-                Token::EOF => break,
-                _ => continue,
             }
+        } else {
+            ui_removed = false;
         }
+    }
 
-        false
+    if ui_removed {
+        for root in [LIB_PATH, MAIN_PATH] {
+            if Path::new(root).exists() {
+                remove_added_lines(root, dry_run)?;
+            }
+        }
     }
 
-    let path = Path::new(file);
-    if path.exists() {
-        let mut contents = String::new();
-        File::open(path)?.read_to_string(&mut contents)?;
+    Ok(())
+}
 
-        let lexer = Lexer::new(contents);
+/// Removes the lines carrying the [`ADDED_MARKER`] from the file and returns what is left.
+fn remove_added_lines(file: &str, dry_run: bool) -> Result<String, std::io::Error> {
+    let contents = read_to_string(file)?;
+    let (added, kept): (Vec<_>, Vec<_>) = contents
+        .lines()
+        .partition(|line| line.trim_end().ends_with(ADDED_MARKER));
+    if added.is_empty() {
+        return Ok(contents);
+    }
 
-        if !exists(lexer, module) {
-            let mut file = OpenOptions::new().append(true).open(path)?;
-            writeln!(file, "\npub mod {};", module)?;
+    let mut rest = kept.join("\n").trim_end().to_string();
+    if !rest.is_empty() {
+        rest.push('\n');
+    }
+    if dry_run {
+        println!("Would remove from {}:", file);
+        for line in added {
+            println!("- {}", line);
         }
-        Ok(true)
     } else {
-        Ok(false)
-    }
-}
-
-fn check_public_export(file: &str, module: &str) -> Result<(), std::io::Error> {
-    fn exists(lexer: Lexer, module: &str) -> bool {
-        let mut lexer = lexer.peekable();
-        while let Some(token) = lexer.next() {
-            match token {
-                Token::Keyword(Keyword::Pub) => {
-                    if let Some(Token::Keyword(Keyword::Use)) = lexer.peek() {
-                        lexer.next();
-                        if let Some(Token::Ident(ident)) = lexer.next() {
-                            if ident != module || lexer.next() != Some(Token::DColon) {
-                                continue;
-                            }
-                            if lexer.next() == Some(Token::Operator(Operator::Multiply)) {
-                                return true;
-                            }
-                        }
-                    }
-                }
-                Token::EOF => break,
-                _ => continue,
+        write(file, &rest)?;
+    }
+    Ok(rest)
+}
+
+/// Marks a line as added by mve, so `mve clean` can remove it again.
+fn added(line: &str) -> String {
+    format!("{} {}", line, ADDED_MARKER)
+}
+
+fn ui_mod_file() -> Option<&'static str> {
+    [UI_FILE_PATH, UI_MOD_PATH]
+        .into_iter()
+        .find(|p| Path::new(p).exists())
+}
+
+/// Removes everything from the generated directory that is not one of the given outputs, which
+/// leaves exactly the files whose UI sources still exist. The `mod.rs` files are kept for
+/// directories still in use, they are rewritten by [`generate_modules`] anyway.
+pub fn remove_stale(outputs: &[PathBuf]) -> Result<(), std::io::Error> {
+    let keep: HashSet<&Path> = outputs.iter().map(PathBuf::as_path).collect();
+    if Path::new(UI_COMPILED_PATH).exists() {
+        prune(Path::new(UI_COMPILED_PATH), &keep)?;
+    }
+    Ok(())
+}
+
+/// Returns whether anything in the directory is still used.
+fn prune(dir: &Path, keep: &HashSet<&Path>) -> Result<bool, std::io::Error> {
+    let mut used = false;
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if prune(&path, keep)? {
+                used = true;
+            } else {
+                remove_dir_all(&path)?;
             }
+        } else if keep.contains(path.as_path()) {
+            used = true;
+        } else if path.file_name().is_none_or(|name| name != "mod.rs") {
+            remove_file(&path)?;
         }
-
-        false
     }
+    Ok(used)
+}
 
-    let path = Path::new(file);
+fn parse_file(file: &str) -> Result<Vec<Item>, std::io::Error> {
     let mut contents = String::new();
-    File::open(path)?.read_to_string(&mut contents)?;
-    let mut lexer = Lexer::new(contents);
+    File::open(file)?.read_to_string(&mut contents)?;
+    Ok(Parser::new(contents).parse())
+}
 
-    if !exists(lexer, module) {
-        let mut file = OpenOptions::new().append(true).open(path)?;
-        writeln!(file, "pub use {}::*;", module)?;
+fn declaration(items: &[Item], module: &str) -> Declaration {
+    let mut res = Declaration::Missing;
+    for item in items {
+        if let ItemKind::Module(_) = item.kind {
+            if item.name == module {
+                if !item.attributes.iter().any(|a| a.is_cfg()) {
+                    return Declaration::Present;
+                }
+                res = Declaration::Conditional;
+            }
+        }
     }
+    res
+}
 
-    Ok(())
+/// Whether the items contain an unconditional `pub use` of the given module, such as
+/// `pub use module::*`, `pub use self::module::*` or `pub use module::{A, B}`.
+fn exports_all(items: &[Item], module: &str) -> bool {
+    items.iter().any(|item| {
+        let ItemKind::Use(tree) = &item.kind else {
+            return false;
+        };
+        if item.visibility == Visibility::Private || item.attributes.iter().any(|a| a.is_cfg()) {
+            return false;
+        }
+        let tree = match tree.as_slice() {
+            [Token::Ident(s), Token::DColon, rest @ ..] if s == "self" => rest,
+            tree => tree,
+        };
+        matches!(
+            tree,
+            [Token::Ident(name), Token::DColon, Token::Operator(Operator::Multiply) | Token::LBrace, ..]
+                if name == module
+        )
+    })
 }
 
-fn format() {
-    let status = Command::new("cargo")
-        .arg("fmt")
+/// Runs rustfmt on the given generated files only, leaving the rest of the project untouched.
+/// The generated code is already indented, so a missing rustfmt is not an error.
+pub fn format(paths: &[PathBuf]) {
+    let files: Vec<_> = paths.iter().filter(|p| p.exists()).collect();
+    if files.is_empty() {
+        return;
+    }
+
+    match Command::new("rustfmt")
+        .arg("--edition")
+        .arg("2021")
+        .args(files)
         .status()
-        .expect("Failed to execute cargo fmt");
+    {
+        Ok(status) if !status.success() => eprintln!("rustfmt failed with status: {}", status),
+        Ok(_) => {}
+        Err(e) => eprintln!("Skipping formatting, failed to execute rustfmt: {}", e),
+    }
+
+    for file in paths.iter().filter(|p| p.exists()) {
+        if let Err(e) = header::restamp(file) {
+            eprintln!(
+                "Warning: Failed to update the checksum of {:?}: {}",
+                file, e
+            );
+        }
+    }
+}
 
-    if !status.success() {
-        eprintln!("Cargo fmt failed with status: {}", status);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_names() {
+        let name = |s| module_name(s);
+        assert_eq!(name("MainMenu").as_deref(), Some("main_menu"));
+        assert_eq!(name("HTTPServer").as_deref(), Some("http_server"));
+        assert_eq!(name("level2Boss").as_deref(), Some("level2_boss"));
+        assert_eq!(name("my-file name").as_deref(), Some("my_file_name"));
+        assert_eq!(name("__a--b__").as_deref(), Some("a_b"));
+        assert_eq!(name("2d").as_deref(), Some("_2d"));
+        assert_eq!(name("self").as_deref(), Some("self_"));
+        assert_eq!(name("Super").as_deref(), Some("super_"));
+        assert_eq!(name("type").as_deref(), Some("type"));
+        assert_eq!(name("--"), None);
+        assert_eq!(name("ü"), None);
+    }
+
+    #[test]
+    fn module_idents() {
+        assert_eq!(module_ident("type"), "r#type");
+        assert_eq!(module_ident("async"), "r#async");
+        assert_eq!(module_ident("menu"), "menu");
+    }
+
+    fn items(code: &str) -> Vec<Item> {
+        Parser::new(code.to_string()).parse()
+    }
+
+    #[test]
+    fn declarations() {
+        let code = "mod ui;";
+        assert!(declaration(&items(code), "ui") == Declaration::Present);
+        let code = "#[cfg(feature = \"ui\")]\npub mod ui;";
+        assert!(declaration(&items(code), "ui") == Declaration::Conditional);
+        let code = "#[cfg(test)]\nmod ui {}\npub(crate) mod ui;";
+        assert!(declaration(&items(code), "ui") == Declaration::Present);
+        let code = "mod other { mod ui; }\nfn ui() {}";
+        assert!(declaration(&items(code), "ui") == Declaration::Missing);
+    }
+
+    #[test]
+    fn exports() {
+        let exports = |code: &str| exports_all(&items(code), "generated");
+        assert!(exports("pub use generated::*;"));
+        assert!(exports("pub use self::generated::*;"));
+        assert!(exports("pub use generated::{MainMenu, Settings};"));
+        assert!(!exports("use generated::*;"));
+        assert!(!exports("pub use other::generated::*;"));
+        assert!(!exports("#[cfg(test)]\npub use generated::*;"));
+        assert!(!exports("pub mod generated;"));
+    }
+
+    #[test]
+    fn dry_run_edits() {
+        let path = std::env::temp_dir().join(format!("mve-edit-{}.rs", std::process::id()));
+        let edit = |create| Edit {
+            path: path.clone(),
+            text: "pub mod ui;\n".to_string(),
+            create,
+        };
+
+        edit(true).apply(true).unwrap();
+        assert!(!path.exists());
+        edit(true).apply(false).unwrap();
+        edit(false).apply(true).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "pub mod ui;\n");
+        edit(false).apply(false).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "pub mod ui;\npub mod ui;\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mve-{}-{}", name, std::process::id()));
+        let _ = remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn removes_only_added_lines() {
+        let dir = temp_dir("added");
+        let file = dir.join("lib.rs");
+        let contents = format!(
+            "mod app;\n\n{}\n// pub mod ui; added by hand\n\n{}\n",
+            added("pub mod ui;"),
+            added("pub use ui::*;")
+        );
+        write(&file, &contents).unwrap();
+        let file = file.to_str().unwrap();
+
+        let rest = remove_added_lines(file, true).unwrap();
+        assert_eq!(rest, "mod app;\n\n// pub mod ui; added by hand\n");
+        assert_eq!(read_to_string(file).unwrap(), contents);
+
+        remove_added_lines(file, false).unwrap();
+        assert_eq!(read_to_string(file).unwrap(), rest);
+        assert_eq!(remove_added_lines(file, false).unwrap(), rest);
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn prunes_stale_files() {
+        let dir = temp_dir("prune");
+        for file in [
+            "mod.rs",
+            "menu.rs",
+            "old.rs",
+            "a/mod.rs",
+            "a/b.rs",
+            "gone/mod.rs",
+        ] {
+            let file = dir.join(file);
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            write(file, "").unwrap();
+        }
+        let keep = [dir.join("menu.rs"), dir.join("a/b.rs")];
+        let used = prune(&dir, &keep.iter().map(PathBuf::as_path).collect()).unwrap();
+        assert!(used);
+
+        let mut left: Vec<_> = ["mod.rs", "menu.rs", "a/mod.rs", "a/b.rs"]
+            .into_iter()
+            .map(|f| dir.join(f))
+            .collect();
+        left.sort();
+        let mut files = Vec::new();
+        for entry in read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(read_dir(&path).unwrap().map(|e| e.unwrap().path()));
+            } else {
+                files.push(path);
+            }
+        }
+        files.sort();
+        assert_eq!(files, left);
+        remove_dir_all(dir).unwrap();
     }
 }
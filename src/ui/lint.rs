@@ -0,0 +1,422 @@
+use crate::ui::config::{Level, LintConfig};
+use crate::ui::consts::{ELEMENTS, LINT_SUPPRESSION};
+use crate::ui::style::tree::{unquote, Rule, StyleSheet, Target};
+use crate::ui::xml;
+use crate::ui::xml::lexer::{Literal, Position};
+use crate::ui::xml::markup::MarkupError;
+use crate::ui::xml::tree::{Child, NamedNode, Node};
+use hashbrown::{HashMap, HashSet};
+use mvutils::utils::Recover;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum LintRule {
+    DuplicateId,
+    UnusedClass,
+    EmptyClass,
+    NestedWrappers,
+    TextContainer,
+    RedundantAttribute,
+}
+
+impl LintRule {
+    /// The name used in the config and in suppression comments.
+    fn name(self) -> &'static str {
+        match self {
+            LintRule::DuplicateId => "duplicate_id",
+            LintRule::UnusedClass => "unused_class",
+            LintRule::EmptyClass => "empty_class",
+            LintRule::NestedWrappers => "nested_wrappers",
+            LintRule::TextContainer => "text_container",
+            LintRule::RedundantAttribute => "redundant_attribute",
+        }
+    }
+
+    fn level(self, config: &LintConfig) -> Level {
+        match self {
+            LintRule::DuplicateId => config.duplicate_id,
+            LintRule::UnusedClass => config.unused_class,
+            LintRule::EmptyClass => config.empty_class,
+            LintRule::NestedWrappers => config.nested_wrappers,
+            LintRule::TextContainer => config.text_container,
+            LintRule::RedundantAttribute => config.redundant_attribute,
+        }
+    }
+}
+
+struct Lint {
+    rule: LintRule,
+    message: String,
+    position: Position,
+    /// The line of the element the lint is about, which can be suppressed as well.
+    element_line: usize,
+}
+
+/// A parsed stylesheet with the file it came from.
+pub struct Style {
+    pub file: PathBuf,
+    pub sheet: StyleSheet,
+}
+
+/// Lints the UI files against the stylesheets and prints the findings. Returns false if any
+/// file failed to parse or any rule at the error level was violated.
+pub fn lint(uis: &[PathBuf], styles: &[Style], config: &LintConfig) -> bool {
+    let classes: HashSet<&String> = styles
+        .iter()
+        .flat_map(|s| &s.sheet.rules)
+        .flat_map(|r| &r.selectors)
+        .flat_map(|s| s.classes())
+        .collect();
+
+    let mut success = true;
+    for ui in uis {
+        let contents = match std::fs::read_to_string(ui) {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("Error reading UI file {:?}: {}", ui, e);
+                success = false;
+                continue;
+            }
+        };
+        let lints = match lint_file(&contents, styles, &classes, config) {
+            Ok(lints) => lints,
+            Err(e) => {
+                eprintln!(
+                    "error: {}\n  --> {}:{}\n",
+                    e.message,
+                    ui.display(),
+                    e.position
+                );
+                success = false;
+                continue;
+            }
+        };
+
+        for (level, lint) in lints {
+            let level = if level == Level::Error {
+                success = false;
+                "error"
+            } else {
+                "warning"
+            };
+            eprintln!(
+                "{}[{}]: {}\n  --> {}:{}\n",
+                level,
+                lint.rule.name(),
+                lint.message,
+                ui.display(),
+                lint.position
+            );
+        }
+    }
+    success
+}
+
+/// Lints the contents of one UI file, returning the findings which are neither allowed by the
+/// config nor suppressed, with their level.
+fn lint_file(
+    contents: &str,
+    styles: &[Style],
+    classes: &HashSet<&String>,
+    config: &LintConfig,
+) -> Result<Vec<(Level, Lint)>, MarkupError> {
+    let tree = xml::parse(contents)?;
+    let mut linter = Linter {
+        config,
+        styles,
+        classes,
+        ids: HashMap::new(),
+        lints: Vec::new(),
+    };
+    for node in &tree {
+        linter.check(node, &mut Vec::new(), false);
+    }
+
+    let suppressed = suppressions(contents);
+    let lints = linter.lints.into_iter().filter_map(|lint| {
+        let level = lint.rule.level(config);
+        let allowed = [lint.position.line, lint.element_line].iter().any(|line| {
+            suppressed
+                .get(line)
+                .is_some_and(|rules| rules.contains(lint.rule.name()))
+        });
+        (level != Level::Allow && !allowed).then_some((level, lint))
+    });
+    Ok(lints.collect())
+}
+
+/// Collects the rules allowed per line by comments like `<!-- mve-lint: allow(rule, ...) -->`.
+/// A comment after markup applies to its own line, a comment alone on its line to the next one.
+fn suppressions(contents: &str) -> HashMap<usize, HashSet<String>> {
+    let mut res: HashMap<usize, HashSet<String>> = HashMap::new();
+    for (i, line) in contents.lines().enumerate() {
+        let Some((before, rest)) = line.split_once(LINT_SUPPRESSION) else {
+            continue;
+        };
+        let Some(rules) = rest
+            .trim_start()
+            .strip_prefix("allow(")
+            .and_then(|r| r.split_once(')'))
+        else {
+            continue;
+        };
+        let rules: HashSet<String> = rules.0.split(',').map(|r| r.trim().to_string()).collect();
+        let alone = before.trim() == "<!--";
+        let line = if alone { i + 2 } else { i + 1 };
+        res.entry(line).or_default().extend(rules);
+    }
+    res
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    styles: &'a [Style],
+    classes: &'a HashSet<&'a String>,
+    ids: HashMap<String, Position>,
+    lints: Vec<Lint>,
+}
+
+impl<'a> Linter<'a> {
+    fn push(&mut self, rule: LintRule, message: String, position: Position, element: Position) {
+        self.lints.push(Lint {
+            rule,
+            message,
+            position,
+            element_line: element.line,
+        });
+    }
+
+    fn check(&mut self, node: &'a Node, ancestors: &mut Vec<Target<'a>>, in_chain: bool) {
+        let depth = wrapper_chain(node);
+        let in_chain = depth > 0 && in_chain;
+        if !in_chain && depth > self.config.max_wrapper_depth {
+            let position = node.position();
+            self.push(
+                LintRule::NestedWrappers,
+                format!(
+                    "{} wrappers are nested directly in each other without adding anything, \
+                     at most {} are allowed",
+                    depth, self.config.max_wrapper_depth
+                ),
+                position,
+                position,
+            );
+        }
+
+        let children = match node {
+            Node::Named(named) => {
+                self.check_named(named, ancestors);
+                &named.children
+            }
+            Node::Unnamed(unnamed) => &unnamed.children,
+        };
+
+        let container = match node {
+            Node::Named(named) => {
+                named.tag == "element"
+                    || ELEMENTS
+                        .read()
+                        .recover()
+                        .iter()
+                        .any(|e| e.tag == named.tag && e.container)
+            }
+            Node::Unnamed(_) => true,
+        };
+        if container
+            && !children.is_empty()
+            && children.iter().all(|c| matches!(c, Child::String(_)))
+        {
+            let position = node.position();
+            self.push(
+                LintRule::TextContainer,
+                "Container only holds text, use a <label> instead".to_string(),
+                position,
+                position,
+            );
+        }
+
+        if let Node::Named(named) = node {
+            ancestors.push(Target {
+                tag: &named.tag,
+                id: &named.id,
+                classes: &named.class,
+            });
+        }
+        for child in children {
+            if let Child::Node(child) = child {
+                self.check(child, ancestors, depth > 0);
+            }
+        }
+        if let Node::Named(_) = node {
+            ancestors.pop();
+        }
+    }
+
+    fn check_named(&mut self, node: &'a NamedNode, ancestors: &[Target<'a>]) {
+        let element = node.position;
+
+        if let Some(position) = node.id_position {
+            if let Some(first) = self.ids.get(&node.id) {
+                let message = format!("Id '{}' is already used at {}", node.id, first);
+                self.push(LintRule::DuplicateId, message, position, element);
+            } else {
+                self.ids.insert(node.id.clone(), position);
+            }
+        }
+
+        if let Some(position) = node.class_position {
+            if node.class.is_empty() {
+                let message = "Empty class attribute".to_string();
+                self.push(LintRule::EmptyClass, message, position, element);
+            }
+            for class in &node.class {
+                if !self.classes.contains(class) {
+                    let message = format!("Class '{}' is not used by any stylesheet", class);
+                    self.push(LintRule::UnusedClass, message, position, element);
+                }
+            }
+        }
+
+        let target = Target {
+            tag: &node.tag,
+            id: &node.id,
+            classes: &node.class,
+        };
+        for attribute in &node.attributes {
+            let value = match &attribute.value {
+                Literal::String(s) => s.clone(),
+                literal => literal.to_string(),
+            };
+            for (file, rule) in self.matching_rules(&target, ancestors) {
+                let redundant = rule
+                    .declarations
+                    .iter()
+                    .any(|d| d.name == attribute.name && unquote(&d.value) == value);
+                if redundant {
+                    let message = format!(
+                        "Attribute '{}' repeats the value set by the stylesheet rule at {}:{}",
+                        attribute.name,
+                        file.display(),
+                        rule.position
+                    );
+                    self.push(
+                        LintRule::RedundantAttribute,
+                        message,
+                        attribute.position,
+                        element,
+                    );
+                    break;
+                }
+            }
+        }
+    }
+
+    fn matching_rules(&self, target: &Target, ancestors: &[Target]) -> Vec<(&'a Path, &'a Rule)> {
+        self.styles
+            .iter()
+            .flat_map(|s| s.sheet.rules.iter().map(|r| (s.file.as_path(), r)))
+            .filter(|(_, r)| r.selectors.iter().any(|s| s.matches(target, ancestors)))
+            .collect()
+    }
+}
+
+/// How many wrappers without attributes are nested in each other starting at the node, where
+/// every wrapper holds nothing but the next one.
+fn wrapper_chain(node: &Node) -> usize {
+    let children = match node {
+        Node::Named(node)
+            if node.tag == "element"
+                && node.id_position.is_none()
+                && node.class_position.is_none()
+                && node.attributes.is_empty() =>
+        {
+            &node.children
+        }
+        Node::Unnamed(node) => &node.children,
+        Node::Named(_) => return 0,
+    };
+    match children.as_slice() {
+        [Child::Node(child)] => 1 + wrapper_chain(child),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::style::parser;
+
+    fn lints(ui: &str, style: &str, config: &LintConfig) -> Vec<(&'static str, usize, usize)> {
+        let styles = [Style {
+            file: PathBuf::from("main.style"),
+            sheet: parser::parse(style).unwrap(),
+        }];
+        let classes = styles[0]
+            .sheet
+            .rules
+            .iter()
+            .flat_map(|r| &r.selectors)
+            .flat_map(|s| s.classes())
+            .collect();
+        lint_file(ui, &styles, &classes, config)
+            .unwrap()
+            .into_iter()
+            .map(|(_, l)| (l.rule.name(), l.position.line, l.position.column))
+            .collect()
+    }
+
+    #[test]
+    fn rules() {
+        let ui = "<element id=\"main\">
+    <label id=\"main\" class=\"title missing\" width=5/>
+    <label class=\"\" width=6/>
+    <element>Text</element>
+    <element><element><element><label/></element></element></element>
+</element>";
+        let style = "label.title { width: 5; }";
+        assert_eq!(
+            lints(ui, style, &LintConfig::default()),
+            [
+                ("duplicate_id", 2, 12),
+                ("unused_class", 2, 22),
+                ("redundant_attribute", 2, 44),
+                ("empty_class", 3, 12),
+                ("text_container", 4, 5),
+                ("nested_wrappers", 5, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn levels_and_suppressions() {
+        let ui = "<element>
+    <!-- mve-lint: allow(unused_class) -->
+    <label class=\"a\"/>
+    <label class=\"b\"/> <!-- mve-lint: allow(text_container, unused_class) -->
+    <label class=\"c\"/>
+    <label class=\"d\"/>
+    <label id=\"x\"/><label id=\"x\"/>
+</element>";
+        assert_eq!(
+            lints(ui, "", &LintConfig::default()),
+            [
+                ("unused_class", 5, 12),
+                ("unused_class", 6, 12),
+                ("duplicate_id", 7, 27)
+            ]
+        );
+
+        let config = LintConfig {
+            unused_class: Level::Allow,
+            ..LintConfig::default()
+        };
+        assert_eq!(lints(ui, "", &config), [("duplicate_id", 7, 27)]);
+    }
+
+    #[test]
+    fn invalid_markup_is_an_error() {
+        let classes = HashSet::new();
+        let config = LintConfig::default();
+        assert!(lint_file("<label", &[], &classes, &config).is_err());
+    }
+}
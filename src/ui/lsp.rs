@@ -0,0 +1,582 @@
+use crate::ui::consts::{ELEMENTS, STYLE_EXTENSIONS, UI_EXTENSIONS};
+use crate::ui::meta::file::ElementMeta;
+use crate::ui::style;
+use crate::ui::xml;
+use crate::ui::xml::lexer::Position;
+use crate::ui::xml::tree::{Child, Node};
+use hashbrown::HashMap;
+use mvutils::utils::Recover;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// A language server for UI and style files, speaking LSP over any reader and writer so it can
+/// be driven by an editor over stdio as well as by an in-process client.
+pub struct Server<R, W> {
+    input: R,
+    output: W,
+    documents: HashMap<String, String>,
+    shutdown: bool,
+}
+
+/// What the cursor is on in a UI file.
+enum Context {
+    /// A tag name, after `<` or `</`.
+    Tag(String),
+    /// An attribute name inside the opening tag of the given element.
+    Attribute {
+        tag: String,
+        name: String,
+    },
+    None,
+}
+
+impl<R: BufRead, W: Write> Server<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Server {
+            input,
+            output,
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    /// Handles messages until the client sends `exit` or closes the input. Returns whether the
+    /// client asked for a shutdown before exiting.
+    pub fn run(&mut self) -> Result<bool, std::io::Error> {
+        while let Some(message) = self.read_message()? {
+            let method = message["method"].as_str().unwrap_or_default().to_string();
+            if method == "exit" {
+                break;
+            }
+            let params = &message["params"];
+            let result = match method.as_str() {
+                "initialize" => Some(capabilities()),
+                "shutdown" => {
+                    self.shutdown = true;
+                    Some(Value::Null)
+                }
+                "textDocument/didOpen" => {
+                    let document = &params["textDocument"];
+                    self.update(document["uri"].as_str(), document["text"].as_str())?;
+                    None
+                }
+                "textDocument/didChange" => {
+                    let text = params["contentChanges"]
+                        .as_array()
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change["text"].as_str());
+                    self.update(params["textDocument"]["uri"].as_str(), text)?;
+                    None
+                }
+                "textDocument/didClose" => {
+                    if let Some(uri) = params["textDocument"]["uri"].as_str() {
+                        self.documents.remove(uri);
+                    }
+                    None
+                }
+                "textDocument/completion" => Some(self.completion(params)),
+                "textDocument/hover" => Some(self.hover(params)),
+                "textDocument/definition" => Some(self.definition(params)),
+                _ => None,
+            };
+
+            let Some(id) = message.get("id").cloned() else {
+                continue;
+            };
+            let response = match result {
+                Some(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                None => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": METHOD_NOT_FOUND, "message": format!("Unknown method '{method}'") },
+                }),
+            };
+            self.write_message(&response)?;
+        }
+        Ok(self.shutdown)
+    }
+
+    fn read_message(&mut self) -> Result<Option<Value>, std::io::Error> {
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("Content-Length") {
+                    length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+
+        let Some(length) = length else {
+            return Err(std::io::Error::other("Message without Content-Length"));
+        };
+        let mut body = vec![0; length];
+        self.input.read_exact(&mut body)?;
+        serde_json::from_slice(&body)
+            .map(Some)
+            .map_err(std::io::Error::other)
+    }
+
+    fn write_message(&mut self, message: &Value) -> Result<(), std::io::Error> {
+        let body = message.to_string();
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+        self.output.flush()
+    }
+
+    fn update(&mut self, uri: Option<&str>, text: Option<&str>) -> Result<(), std::io::Error> {
+        let (Some(uri), Some(text)) = (uri, text) else {
+            return Ok(());
+        };
+        self.documents.insert(uri.to_string(), text.to_string());
+        let diagnostics = diagnostics(uri, text);
+        self.write_message(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }))
+    }
+
+    /// The text of the document and the character offset of the requested position.
+    fn document(&self, params: &Value) -> Option<(&str, usize)> {
+        let text = self
+            .documents
+            .get(params["textDocument"]["uri"].as_str()?)?;
+        let position = &params["position"];
+        let offset = offset(
+            text,
+            position["line"].as_u64()? as usize,
+            position["character"].as_u64()? as usize,
+        );
+        Some((text, offset))
+    }
+
+    fn completion(&self, params: &Value) -> Value {
+        let Some((text, offset)) = self.document(params) else {
+            return Value::Null;
+        };
+        let prefix: String = text.chars().take(offset).collect();
+        let elements = ELEMENTS.read().recover();
+
+        let items: Vec<Value> = match context_before(&prefix) {
+            Context::Tag(_) => elements
+                .iter()
+                .filter(|e| !e.tag.is_empty())
+                .map(|e| {
+                    json!({
+                        "label": e.tag,
+                        "kind": 7,
+                        "detail": e.path,
+                        "documentation": e.docs.clone().unwrap_or_default(),
+                    })
+                })
+                .collect(),
+            Context::Attribute { tag, .. } => {
+                let mut items = vec![
+                    attribute_item("id", "", None),
+                    attribute_item("class", "", None),
+                ];
+                if let Some(element) = elements.iter().find(|e| e.tag == tag) {
+                    items.extend(
+                        element
+                            .properties
+                            .iter()
+                            .map(|p| attribute_item(&p.name, &p.ty, p.docs.as_deref())),
+                    );
+                }
+                items
+            }
+            Context::None => Vec::new(),
+        };
+        json!(items)
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let Some((text, offset)) = self.document(params) else {
+            return Value::Null;
+        };
+        let elements = ELEMENTS.read().recover();
+        let contents = match context_at(text, offset) {
+            Context::Tag(tag) => elements.iter().find(|e| e.tag == tag).map(element_docs),
+            Context::Attribute { tag, name } => elements
+                .iter()
+                .find(|e| e.tag == tag)
+                .and_then(|e| e.properties.iter().find(|p| p.name == name))
+                .map(|p| {
+                    format!(
+                        "```rust\n{}: {}\n```\n{}",
+                        p.name,
+                        p.ty,
+                        p.docs.as_deref().unwrap_or_default()
+                    )
+                }),
+            Context::None => None,
+        };
+        match contents {
+            Some(value) => json!({ "contents": { "kind": "markdown", "value": value } }),
+            None => Value::Null,
+        }
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let Some((text, offset)) = self.document(params) else {
+            return Value::Null;
+        };
+        let Context::Tag(tag) = context_at(text, offset) else {
+            return Value::Null;
+        };
+        let elements = ELEMENTS.read().recover();
+        let Some(source) = elements
+            .iter()
+            .find(|e| e.tag == tag)
+            .and_then(|e| e.source.as_ref())
+        else {
+            return Value::Null;
+        };
+        let file = std::env::current_dir()
+            .map(|dir| dir.join(&source.file))
+            .unwrap_or_else(|_| PathBuf::from(&source.file));
+        let position = json!({
+            "line": source.line.saturating_sub(1),
+            "character": source.column.saturating_sub(1),
+        });
+        json!({
+            "uri": path_to_uri(&file),
+            "range": { "start": position, "end": position },
+        })
+    }
+}
+
+/// Runs the language server on stdin and stdout. Returns false if the connection failed or the
+/// client exited without shutting the server down.
+pub fn run() -> bool {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    match Server::new(stdin.lock(), stdout.lock()).run() {
+        Ok(shutdown) => shutdown,
+        Err(e) => {
+            eprintln!("Language server failed: {}", e);
+            false
+        }
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "capabilities": {
+            "textDocumentSync": 1,
+            "completionProvider": { "triggerCharacters": ["<", " "] },
+            "hoverProvider": true,
+            "definitionProvider": true,
+        },
+        "serverInfo": { "name": "mve", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+fn attribute_item(name: &str, ty: &str, docs: Option<&str>) -> Value {
+    json!({
+        "label": name,
+        "kind": 10,
+        "detail": ty,
+        "documentation": docs.unwrap_or_default(),
+        "insertText": format!("{name}=\"$1\""),
+        "insertTextFormat": 2,
+    })
+}
+
+fn element_docs(element: &ElementMeta) -> String {
+    let mut docs = format!("```rust\n{}\n```", element.path);
+    if let Some(text) = &element.docs {
+        docs.push_str("\n\n");
+        docs.push_str(text);
+    }
+    docs
+}
+
+/// Parser errors of UI and style files, and tags which aren't known elements.
+fn diagnostics(uri: &str, text: &str) -> Vec<Value> {
+    let mut res = Vec::new();
+    if UI_EXTENSIONS.iter().any(|e| uri.ends_with(e)) {
+        let tree = match xml::parse(text) {
+            Ok(tree) => tree,
+            Err(e) => {
+                res.push(diagnostic(e.position, 1, 1, &e.message));
+                return res;
+            }
+        };
+        let elements = ELEMENTS.read().recover();
+        let mut stack: Vec<&Node> = tree.iter().collect();
+        while let Some(node) = stack.pop() {
+            let children = match node {
+                Node::Named(named) => {
+                    if !elements.iter().any(|e| e.tag == named.tag) {
+                        res.push(diagnostic(
+                            named.position,
+                            named.tag.chars().count() + 1,
+                            2,
+                            &format!("Unknown element '{}'", named.tag),
+                        ));
+                    }
+                    &named.children
+                }
+                Node::Unnamed(unnamed) => &unnamed.children,
+            };
+            stack.extend(children.iter().filter_map(|c| match c {
+                Child::Node(node) => Some(node),
+                _ => None,
+            }));
+        }
+    } else if STYLE_EXTENSIONS.iter().any(|e| uri.ends_with(e)) {
+        if let Err(e) = style::parser::parse(text) {
+            res.push(diagnostic(e.position, 1, 1, &e.message));
+        }
+    }
+    res
+}
+
+fn diagnostic(position: Position, length: usize, severity: u8, message: &str) -> Value {
+    let line = position.line.saturating_sub(1);
+    let character = position.column.saturating_sub(1);
+    json!({
+        "range": {
+            "start": { "line": line, "character": character },
+            "end": { "line": line, "character": character + length },
+        },
+        "severity": severity,
+        "source": "mve",
+        "message": message,
+    })
+}
+
+/// Finds what the text before the cursor is in the middle of, for completion.
+fn context_before(prefix: &str) -> Context {
+    let Some(open) = prefix.rfind('<') else {
+        return Context::None;
+    };
+    if prefix[open..].contains('>') || prefix[open..].matches('"').count() % 2 == 1 {
+        return Context::None;
+    }
+    let inside = &prefix[open + 1..];
+    let inside = inside.strip_prefix('/').unwrap_or(inside);
+    match inside.split_once(char::is_whitespace) {
+        None => Context::Tag(inside.to_string()),
+        Some((tag, rest)) => Context::Attribute {
+            tag: tag.to_string(),
+            name: rest
+                .rsplit(char::is_whitespace)
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+        },
+    }
+}
+
+/// Finds the tag or attribute name under the cursor.
+fn context_at(text: &str, offset: usize) -> Context {
+    let chars: Vec<char> = text.chars().collect();
+    let is_ident = |c: &char| c.is_alphanumeric() || *c == '_';
+    let mut start = offset.min(chars.len());
+    while start > 0 && is_ident(&chars[start - 1]) {
+        start -= 1;
+    }
+    let mut end = start;
+    while end < chars.len() && is_ident(&chars[end]) {
+        end += 1;
+    }
+    if start == end {
+        return Context::None;
+    }
+    let prefix: String = chars[..end].iter().collect();
+    context_before(&prefix)
+}
+
+/// Converts an LSP position to a character offset. LSP counts columns in UTF-16 code units.
+fn offset(text: &str, line: usize, character: usize) -> usize {
+    let mut offset = 0;
+    for (i, l) in text.split('\n').enumerate() {
+        if i == line {
+            let mut units = 0;
+            for c in l.chars() {
+                if units >= character {
+                    break;
+                }
+                units += c.len_utf16();
+                offset += 1;
+            }
+            return offset;
+        }
+        offset += l.chars().count() + 1;
+    }
+    offset
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let path = path
+        .to_string_lossy()
+        .replace('\\', "/")
+        .replace(' ', "%20");
+    if path.starts_with('/') {
+        format!("file://{path}")
+    } else {
+        format!("file:///{path}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::meta::file::PropertyMeta;
+
+    /// Runs a server on the messages and returns whether it was shut down and what it sent.
+    fn run_server(messages: &[Value]) -> (bool, Vec<Value>) {
+        let mut input = Vec::new();
+        for message in messages {
+            let body = message.to_string();
+            input.extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).bytes());
+        }
+        let mut output = Vec::new();
+        let shutdown = Server::new(input.as_slice(), &mut output).run().unwrap();
+
+        let mut responses = Vec::new();
+        let mut rest = output.as_slice();
+        while let Some(start) = rest.windows(4).position(|w| w == b"\r\n\r\n") {
+            let header = std::str::from_utf8(&rest[..start]).unwrap();
+            let length: usize = header["Content-Length: ".len()..].parse().unwrap();
+            let body = &rest[start + 4..start + 4 + length];
+            responses.push(serde_json::from_slice(body).unwrap());
+            rest = &rest[start + 4 + length..];
+        }
+        (shutdown, responses)
+    }
+
+    fn open(uri: &str, text: &str) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": uri, "text": text } },
+        })
+    }
+
+    fn request(id: i64, method: &str, uri: &str, line: usize, character: usize) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": {
+                "textDocument": { "uri": uri },
+                "position": { "line": line, "character": character },
+            },
+        })
+    }
+
+    fn add_element(tag: &str) {
+        let mut element = ElementMeta::new(tag.to_string(), format!("crate::{tag}"));
+        element.properties.push(PropertyMeta {
+            name: "min".to_string(),
+            ty: "f64".to_string(),
+            docs: Some("The smallest value.".to_string()),
+        });
+        ELEMENTS.write().recover().push(element);
+    }
+
+    #[test]
+    fn shutdown_and_exit() {
+        let (shutdown, responses) = run_server(&[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+        ]);
+        assert!(shutdown);
+        assert_eq!(responses.len(), 2);
+        assert_eq!(
+            responses[0]["result"]["capabilities"]["hoverProvider"],
+            true
+        );
+        assert_eq!(responses[1]["result"], Value::Null);
+
+        let (shutdown, _) = run_server(&[json!({ "jsonrpc": "2.0", "method": "exit" })]);
+        assert!(!shutdown);
+    }
+
+    #[test]
+    fn unknown_methods_are_errors() {
+        let (_, responses) = run_server(&[json!({ "jsonrpc": "2.0", "id": 7, "method": "foo" })]);
+        assert_eq!(responses[0]["id"], 7);
+        assert_eq!(responses[0]["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn invalid_markup_is_a_diagnostic() {
+        let (_, responses) = run_server(&[
+            open("file:///a.ui", "<label width=1.2.3/>"),
+            open("file:///b.ui", "<1abc/>"),
+            open(
+                "file:///c.ui",
+                "<label>\n    <nothing_like_this/>\n</label>",
+            ),
+        ]);
+        let diagnostics: Vec<&Value> = responses
+            .iter()
+            .map(|r| &r["params"]["diagnostics"][0])
+            .collect();
+        assert_eq!(diagnostics[0]["message"], "Invalid number '1.2.3'");
+        assert_eq!(diagnostics[0]["range"]["start"]["character"], 13);
+        assert_eq!(diagnostics[1]["severity"], 1);
+        assert_eq!(
+            diagnostics[2]["message"],
+            "Unknown element 'nothing_like_this'"
+        );
+        assert_eq!(diagnostics[2]["range"]["start"]["line"], 1);
+    }
+
+    #[test]
+    fn completes_tags_and_attributes() {
+        add_element("lsp_completion");
+        let uri = "file:///a.ui";
+        let (_, responses) = run_server(&[
+            open(uri, "<lsp_completion m"),
+            request(1, "textDocument/completion", uri, 0, 17),
+            request(2, "textDocument/completion", uri, 0, 4),
+        ]);
+        let labels = |response: &Value| -> Vec<String> {
+            response["result"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|item| item["label"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(labels(&responses[1]), ["id", "class", "min"]);
+        assert_eq!(responses[1]["result"][2]["detail"], "f64");
+        assert!(labels(&responses[2]).contains(&"lsp_completion".to_string()));
+    }
+
+    #[test]
+    fn hovers_attributes() {
+        add_element("lsp_hover");
+        let uri = "file:///a.ui";
+        let (_, responses) = run_server(&[
+            open(uri, "<lsp_hover min=5/>"),
+            request(1, "textDocument/hover", uri, 0, 12),
+            request(2, "textDocument/hover", uri, 0, 3),
+        ]);
+        let value = &responses[1]["result"]["contents"]["value"];
+        assert_eq!(value, "```rust\nmin: f64\n```\nThe smallest value.");
+        let value = &responses[2]["result"]["contents"]["value"];
+        assert_eq!(value, "```rust\ncrate::lsp_hover\n```");
+    }
+}
@@ -0,0 +1,321 @@
+use crate::ui::consts::{META_FILE, META_VERSION, OVERRIDE_ARGUMENT, UI_ANNOTATION};
+use crate::ui::rust::Path;
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetaFile {
+    pub version: u32,
+    /// The files the elements were read from, including the manifests, to tell when the file
+    /// is outdated.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    #[serde(default, rename = "element")]
+    pub elements: Vec<ElementMeta>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElementMeta {
+    pub tag: String,
+    pub path: String,
+    /// Names of the generic parameters of the element type, lifetimes keep their leading `'`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub generics: Vec<String>,
+    /// Set when the element accepts child elements.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub container: bool,
+    /// Set when the element intentionally replaces a built-in element with the same tag.
+    #[serde(default, skip_serializing_if = "is_false", rename = "override")]
+    pub overrides: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty", rename = "property")]
+    pub properties: Vec<PropertyMeta>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<SourceLocation>,
+    /// The `cfg` predicates of the element and its enclosing modules. The element only exists
+    /// when all of them hold.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cfg: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PropertyMeta {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+#[derive(Debug)]
+pub struct TagConflict {
+    pub tag: String,
+    pub elements: Vec<ElementMeta>,
+    pub builtin: bool,
+}
+
+impl Display for TagConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.builtin {
+            write!(
+                f,
+                "Tag '{}' is already used by a built-in element, use #[{}({}, {})] to replace it",
+                self.tag, UI_ANNOTATION, self.tag, OVERRIDE_ARGUMENT
+            )?;
+        } else {
+            write!(f, "Tag '{}' is defined more than once", self.tag)?;
+        }
+        for element in &self.elements {
+            match &element.source {
+                Some(source) => write!(f, "\n    {} at {}", element.path, source)?,
+                None if self.builtin => write!(f, "\n    {} (built-in)", element.path)?,
+                None => write!(f, "\n    {} ({})", element.path, META_FILE)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum MetaError {
+    Io(std::io::Error),
+    Parse(String),
+    UnsupportedVersion(u32),
+    LegacyEntry(usize, String),
+    InvalidPath(String, String),
+    Conflicts(Vec<TagConflict>),
+}
+
+impl Display for MetaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetaError::Io(e) => write!(f, "{e}"),
+            MetaError::Parse(e) => write!(f, "Invalid {META_FILE} file: {e}"),
+            MetaError::UnsupportedVersion(v) => write!(
+                f,
+                "{META_FILE} version {v} is newer than the supported version {META_VERSION}"
+            ),
+            MetaError::LegacyEntry(i, entry) => write!(
+                f,
+                "Entry {} of legacy {META_FILE} file is not of the form 'tag=path': '{entry}'",
+                i + 1
+            ),
+            MetaError::InvalidPath(tag, path) => write!(
+                f,
+                "Element '{tag}' in {META_FILE} has an invalid path '{path}'"
+            ),
+            MetaError::Conflicts(conflicts) => {
+                write!(f, "Found {} conflicting element tag(s)", conflicts.len())?;
+                for conflict in conflicts {
+                    write!(f, "\n{conflict}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for MetaError {}
+
+impl From<std::io::Error> for MetaError {
+    fn from(e: std::io::Error) -> Self {
+        MetaError::Io(e)
+    }
+}
+
+impl ElementMeta {
+    /// The path used to name the element type in generated code. Generic type and const
+    /// parameters are left to inference. Fails if the path in the meta file isn't one.
+    pub fn type_path(&self) -> Result<Path, String> {
+        let inferred = self
+            .generics
+            .iter()
+            .filter(|g| !g.starts_with('\''))
+            .count();
+        Path::try_parse(&self.path).map(|path| path.inferred(inferred))
+    }
+
+    pub fn new(tag: String, path: String) -> Self {
+        ElementMeta {
+            tag,
+            path,
+            generics: Vec::new(),
+            container: false,
+            overrides: false,
+            docs: None,
+            properties: Vec::new(),
+            events: Vec::new(),
+            source: None,
+            cfg: Vec::new(),
+        }
+    }
+}
+
+impl MetaFile {
+    pub fn new(elements: Vec<ElementMeta>) -> Self {
+        MetaFile {
+            version: META_VERSION,
+            sources: Vec::new(),
+            elements,
+        }
+    }
+
+    /// Parses the contents of a meta file. Files written in the old `tag=path;` format are
+    /// still accepted so they can be migrated by regenerating them.
+    pub fn parse(contents: &str) -> Result<Self, MetaError> {
+        if contents.trim().is_empty() {
+            return Ok(MetaFile::new(Vec::new()));
+        }
+
+        if is_legacy(contents) {
+            return parse_legacy(contents);
+        }
+
+        let file: MetaFile =
+            toml::from_str(contents).map_err(|e| MetaError::Parse(e.to_string()))?;
+        if file.version > META_VERSION {
+            return Err(MetaError::UnsupportedVersion(file.version));
+        }
+        Ok(file)
+    }
+
+    /// Checks that every element has a valid path, that no two elements share a tag and that
+    /// built-in elements are only replaced by elements that explicitly override them. Every
+    /// conflicting location is reported.
+    pub fn validate(&self, builtins: &[ElementMeta]) -> Result<(), MetaError> {
+        if let Some(element) = self.elements.iter().find(|e| e.type_path().is_err()) {
+            return Err(MetaError::InvalidPath(
+                element.tag.clone(),
+                element.path.clone(),
+            ));
+        }
+
+        let mut tags: HashMap<&str, Vec<&ElementMeta>> = HashMap::new();
+        for element in &self.elements {
+            tags.entry(element.tag.as_str()).or_default().push(element);
+        }
+
+        let mut conflicts = Vec::new();
+        for element in &self.elements {
+            let Some(defined) = tags.remove(element.tag.as_str()) else {
+                continue;
+            };
+            if defined.len() > 1 {
+                conflicts.push(TagConflict {
+                    tag: element.tag.clone(),
+                    elements: defined.into_iter().cloned().collect(),
+                    builtin: false,
+                });
+                continue;
+            }
+            if element.overrides {
+                continue;
+            }
+            if let Some(builtin) = builtins.iter().find(|b| b.tag == element.tag) {
+                conflicts.push(TagConflict {
+                    tag: element.tag.clone(),
+                    elements: vec![builtin.clone(), element.clone()],
+                    builtin: true,
+                });
+            }
+        }
+
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(MetaError::Conflicts(conflicts))
+        }
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.version == 0
+    }
+
+    pub fn serialize(&self) -> Result<String, MetaError> {
+        toml::to_string_pretty(self).map_err(|e| MetaError::Parse(e.to_string()))
+    }
+}
+
+/// Files in the current format have a `version = <number>` line, which can't be a legacy entry
+/// as those always name a path.
+fn is_legacy(contents: &str) -> bool {
+    !contents.lines().any(|line| {
+        line.split_once('=').is_some_and(|(key, value)| {
+            key.trim() == "version" && value.trim().parse::<u32>().is_ok()
+        })
+    })
+}
+
+fn parse_legacy(contents: &str) -> Result<MetaFile, MetaError> {
+    let elements = contents
+        .split(';')
+        .filter(|item| !item.trim().is_empty())
+        .enumerate()
+        .map(|(i, pair)| match pair.split_once('=') {
+            Some((tag, path)) if !path.trim().is_empty() => Ok(ElementMeta::new(
+                tag.trim().to_string(),
+                path.trim().to_string(),
+            )),
+            _ => Err(MetaError::LegacyEntry(i, pair.trim().to_string())),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(MetaFile {
+        version: 0,
+        sources: Vec::new(),
+        elements,
+    })
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_entries_starting_with_version() {
+        let meta = MetaFile::parse("versioned=crate::Versioned;\nversion=crate::Version;").unwrap();
+        assert!(meta.is_legacy());
+        assert_eq!(meta.elements[0].tag, "versioned");
+        assert_eq!(meta.elements[1].path, "crate::Version");
+    }
+
+    #[test]
+    fn current_format() {
+        let contents = "version = 1\n\n[[element]]\ntag = \"knob\"\npath = \"crate::Knob\"\n";
+        let meta = MetaFile::parse(contents).unwrap();
+        assert!(!meta.is_legacy());
+        assert_eq!(meta.elements[0].tag, "knob");
+    }
+
+    #[test]
+    fn invalid_paths() {
+        let meta = MetaFile::new(vec![
+            ElementMeta::new("knob".to_string(), "crate::Knob".to_string()),
+            ElementMeta::new("dial".to_string(), "crate::Dial<T>".to_string()),
+        ]);
+        let error = meta.validate(&[]).unwrap_err().to_string();
+        assert!(error.contains("'dial'"), "{error}");
+        assert!(error.contains("'crate::Dial<T>'"), "{error}");
+    }
+}
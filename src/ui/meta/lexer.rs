@@ -1,25 +1,94 @@
-use crate::ui::meta::token::{Keyword, Literal, Operator, Token};
-use std::iter::Peekable;
-use std::vec::IntoIter;
+use crate::ui::meta::token::{Keyword, LexError, Literal, Operator, Token};
 
+struct Input {
+    chars: Vec<char>,
+    index: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Input {
+    fn peek(&self) -> Option<&char> {
+        self.chars.get(self.index)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<&char> {
+        self.chars.get(self.index + n)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.get(self.index).copied();
+        if c == Some('\n') {
+            self.line += 1;
+            self.column = 1;
+        } else if c.is_some() {
+            self.column += 1;
+        }
+        if c.is_some() {
+            self.index += 1;
+        }
+        c
+    }
+}
+
+/// Tokenizes Rust source code. The lexer never fails: anything it can't make sense of is
+/// returned as a [`Token::Error`] carrying the position of the problem, and lexing continues
+/// after it.
 pub struct Lexer {
-    input: Peekable<IntoIter<char>>,
+    input: Input,
     revert: Vec<Token>,
     done: bool,
+    line: usize,
+    column: usize,
 }
 
 impl Lexer {
     pub fn new(code: String) -> Self {
+        let mut input = Input {
+            chars: code.chars().collect(),
+            index: 0,
+            line: 1,
+            column: 1,
+        };
+
+        // A shebang line, as opposed to an inner attribute such as `#![allow(..)]`
+        if input.peek() == Some(&'#') && input.peek_nth(1) == Some(&'!') {
+            let attribute = input.chars[2..]
+                .iter()
+                .find(|c| !c.is_whitespace())
+                .is_some_and(|c| *c == '[');
+            if !attribute {
+                while input.peek().is_some_and(|c| *c != '\n') {
+                    input.next();
+                }
+            }
+        }
+
         Lexer {
-            input: code.chars().collect::<Vec<_>>().into_iter().peekable(),
+            input,
             revert: Vec::with_capacity(3),
             done: false,
+            line: 1,
+            column: 1,
         }
     }
 
     pub fn revert(&mut self, token: Token) {
         self.revert.push(token);
     }
+
+    /// Line and column (both 1-based) at which the last returned token started.
+    pub fn position(&self) -> (usize, usize) {
+        (self.line, self.column)
+    }
+
+    fn error(&self, message: String) -> Token {
+        Token::Error(LexError {
+            message,
+            line: self.line,
+            column: self.column,
+        })
+    }
 }
 
 impl Iterator for Lexer {
@@ -30,370 +99,745 @@ impl Iterator for Lexer {
             return self.revert.pop();
         }
 
-        while self.input.peek().is_some_and(|x| x.is_ascii_whitespace()) {
+        while self.input.peek().is_some_and(|x| x.is_whitespace()) {
             self.input.next();
         }
-        return match self.input.next() {
-            Some(c) => {
-                return match c {
-                    '/' => {
+        self.line = self.input.line;
+        self.column = self.input.column;
+        match self.input.next() {
+            Some(c) => match c {
+                '/' => {
+                    if self.input.peek().is_some_and(|x| *x == '/') {
+                        self.input.next();
+                        let mut doc = None;
                         if self.input.peek().is_some_and(|x| *x == '/') {
-                            while self.input.peek().is_some_and(|x| *x != '\n') {
-                                self.input.next();
-                            }
-                            return self.next();
-                        }
-                        if self.input.peek().is_some_and(|x| *x == '*') {
                             self.input.next();
-                            while self.input.next().is_some_and(|x| x != '*') {
-                                if self.input.peek().is_some_and(|x| *x == '/') {
-                                    break;
-                                }
+                            if self.input.peek().is_none_or(|x| *x != '/') {
+                                doc = Some(String::new());
                             }
-                            self.input.next();
-                            return self.next();
                         }
-
-                        if self.input.peek().is_some_and(|x| *x == '=') {
+                        while let Some(c) = self.input.peek().copied() {
+                            if c == '\n' {
+                                break;
+                            }
                             self.input.next();
-                            return Some(Token::OperatorAssign(Operator::Divide));
+                            if let Some(doc) = &mut doc {
+                                doc.push(c);
+                            }
                         }
+                        return match doc {
+                            Some(doc) => Some(Token::DocComment(doc.trim().to_string())),
+                            None => self.next(),
+                        };
+                    }
+                    if self.input.peek().is_some_and(|x| *x == '*') {
+                        self.input.next();
+                        return self.block_comment();
+                    }
 
-                        Some(Token::Operator(Operator::Divide))
+                    if self.input.peek().is_some_and(|x| *x == '=') {
+                        self.input.next();
+                        return Some(Token::OperatorAssign(Operator::Divide));
                     }
-                    '#' => Some(Token::Hash),
-                    '*' => {
-                        if self.input.peek().is_some_and(|x| *x == '=') {
-                            self.input.next();
-                            return Some(Token::OperatorAssign(Operator::Multiply));
-                        }
 
-                        Some(Token::Operator(Operator::Multiply))
+                    Some(Token::Operator(Operator::Divide))
+                }
+                '#' => Some(Token::Hash),
+                '*' => {
+                    if self.input.peek().is_some_and(|x| *x == '=') {
+                        self.input.next();
+                        return Some(Token::OperatorAssign(Operator::Multiply));
                     }
 
-                    '+' => {
-                        if self.input.peek().is_some_and(|x| *x == '=') {
-                            self.input.next();
-                            return Some(Token::OperatorAssign(Operator::Plus));
-                        }
+                    Some(Token::Operator(Operator::Multiply))
+                }
 
-                        Some(Token::Operator(Operator::Plus))
+                '+' => {
+                    if self.input.peek().is_some_and(|x| *x == '=') {
+                        self.input.next();
+                        return Some(Token::OperatorAssign(Operator::Plus));
                     }
 
-                    '-' => {
-                        if self.input.peek().is_some_and(|x| *x == '=') {
-                            self.input.next();
-                            return Some(Token::OperatorAssign(Operator::Minus));
-                        }
+                    Some(Token::Operator(Operator::Plus))
+                }
 
-                        if self.input.peek().is_some_and(|x| *x == '>') {
-                            self.input.next();
-                            return Some(Token::Arrow);
-                        }
+                '-' => {
+                    if self.input.peek().is_some_and(|x| *x == '=') {
+                        self.input.next();
+                        return Some(Token::OperatorAssign(Operator::Minus));
+                    }
 
-                        Some(Token::Operator(Operator::Minus))
+                    if self.input.peek().is_some_and(|x| *x == '>') {
+                        self.input.next();
+                        return Some(Token::Arrow);
                     }
 
-                    '%' => {
-                        if self.input.peek().is_some_and(|x| *x == '=') {
-                            self.input.next();
-                            return Some(Token::OperatorAssign(Operator::Modulo));
-                        }
+                    Some(Token::Operator(Operator::Minus))
+                }
 
-                        Some(Token::Operator(Operator::Modulo))
+                '%' => {
+                    if self.input.peek().is_some_and(|x| *x == '=') {
+                        self.input.next();
+                        return Some(Token::OperatorAssign(Operator::Modulo));
                     }
 
-                    '=' => {
-                        if self.input.peek().is_some_and(|x| *x == '=') {
-                            self.input.next();
-                            return Some(Token::Operator(Operator::Eq));
-                        }
+                    Some(Token::Operator(Operator::Modulo))
+                }
 
-                        Some(Token::Assign)
+                '=' => {
+                    if self.input.peek().is_some_and(|x| *x == '=') {
+                        self.input.next();
+                        return Some(Token::Operator(Operator::Eq));
                     }
 
-                    '!' => {
-                        if self.input.peek().is_some_and(|x| *x == '=') {
-                            self.input.next();
-                            return Some(Token::Operator(Operator::NotEq));
-                        }
+                    Some(Token::Assign)
+                }
 
-                        Some(Token::Operator(Operator::Not))
+                '!' => {
+                    if self.input.peek().is_some_and(|x| *x == '=') {
+                        self.input.next();
+                        return Some(Token::Operator(Operator::NotEq));
                     }
 
-                    '<' => {
-                        return match self.input.peek() {
-                            Some(c) => {
-                                let c = *c;
-                                return match c {
-                                    '<' => {
-                                        self.input.next();
-                                        if self.input.peek().is_some_and(|x| *x == '=') {
-                                            self.input.next();
-                                            return Some(Token::OperatorAssign(Operator::Lsh));
-                                        }
-                                        Some(Token::Operator(Operator::Lsh))
-                                    }
+                    Some(Token::Operator(Operator::Not))
+                }
 
-                                    '=' => {
-                                        self.input.next();
-                                        Some(Token::Operator(Operator::LessEq))
-                                    }
+                '<' => match self.input.peek() {
+                    Some(c) => {
+                        let c = *c;
+                        match c {
+                            '<' => {
+                                self.input.next();
+                                if self.input.peek().is_some_and(|x| *x == '=') {
+                                    self.input.next();
+                                    return Some(Token::OperatorAssign(Operator::Lsh));
+                                }
+                                Some(Token::Operator(Operator::Lsh))
+                            }
 
-                                    _ => Some(Token::Operator(Operator::Less)),
-                                };
+                            '=' => {
+                                self.input.next();
+                                Some(Token::Operator(Operator::LessEq))
                             }
 
-                            _ => None,
+                            _ => Some(Token::Operator(Operator::Less)),
                         }
                     }
 
-                    '>' => {
-                        return match self.input.peek() {
-                            Some(c) => {
-                                let c = *c;
-                                return match c {
-                                    '>' => {
-                                        self.input.next();
-                                        if self.input.peek().is_some_and(|x| *x == '>') {
-                                            self.input.next();
-                                            if self.input.peek().is_some_and(|x| *x == '=') {
-                                                self.input.next();
-                                                return Some(Token::OperatorAssign(Operator::ARsh));
-                                            }
-                                            return Some(Token::Operator(Operator::ARsh));
-                                        }
-                                        if self.input.peek().is_some_and(|x| *x == '=') {
-                                            self.input.next();
-                                            return Some(Token::OperatorAssign(Operator::Rsh));
-                                        }
-                                        Some(Token::Operator(Operator::Rsh))
-                                    }
+                    _ => Some(Token::Operator(Operator::Less)),
+                },
 
-                                    '=' => {
+                '>' => match self.input.peek() {
+                    Some(c) => {
+                        let c = *c;
+                        match c {
+                            '>' => {
+                                self.input.next();
+                                if self.input.peek().is_some_and(|x| *x == '>') {
+                                    self.input.next();
+                                    if self.input.peek().is_some_and(|x| *x == '=') {
                                         self.input.next();
-                                        Some(Token::Operator(Operator::GreaterEq))
+                                        return Some(Token::OperatorAssign(Operator::ARsh));
                                     }
+                                    return Some(Token::Operator(Operator::ARsh));
+                                }
+                                if self.input.peek().is_some_and(|x| *x == '=') {
+                                    self.input.next();
+                                    return Some(Token::OperatorAssign(Operator::Rsh));
+                                }
+                                Some(Token::Operator(Operator::Rsh))
+                            }
 
-                                    _ => Some(Token::Operator(Operator::Greater)),
-                                };
+                            '=' => {
+                                self.input.next();
+                                Some(Token::Operator(Operator::GreaterEq))
                             }
 
-                            _ => None,
+                            _ => Some(Token::Operator(Operator::Greater)),
                         }
                     }
 
-                    ',' => Some(Token::Comma),
+                    _ => Some(Token::Operator(Operator::Greater)),
+                },
 
-                    ';' => Some(Token::Semicolon),
+                ',' => Some(Token::Comma),
 
-                    ':' => {
-                        if self.input.peek().is_some_and(|x| *x == ':') {
-                            self.input.next();
-                            return Some(Token::DColon);
-                        }
+                ';' => Some(Token::Semicolon),
 
-                        Some(Token::Colon)
+                ':' => {
+                    if self.input.peek().is_some_and(|x| *x == ':') {
+                        self.input.next();
+                        return Some(Token::DColon);
                     }
 
-                    '?' => Some(Token::QMark),
+                    Some(Token::Colon)
+                }
 
-                    '.' => Some(Token::Dot),
+                '?' => Some(Token::QMark),
 
-                    '(' => Some(Token::LParen),
+                '.' => Some(Token::Dot),
 
-                    ')' => Some(Token::RParen),
+                '(' => Some(Token::LParen),
 
-                    '[' => Some(Token::LBracket),
+                ')' => Some(Token::RParen),
 
-                    ']' => Some(Token::RBracket),
+                '[' => Some(Token::LBracket),
 
-                    '{' => Some(Token::LBrace),
+                ']' => Some(Token::RBracket),
 
-                    '}' => Some(Token::RBrace),
+                '{' => Some(Token::LBrace),
 
-                    '$' => Some(Token::Dollar),
+                '}' => Some(Token::RBrace),
 
-                    '&' => {
-                        if self.input.peek().is_some_and(|x| *x == '&') {
-                            self.input.next();
-                            return Some(Token::Operator(Operator::LAnd));
-                        }
+                '$' => Some(Token::Dollar),
 
-                        if self.input.peek().is_some_and(|x| *x == '=') {
-                            self.input.next();
-                            return Some(Token::OperatorAssign(Operator::BAnd));
-                        }
+                '&' => {
+                    if self.input.peek().is_some_and(|x| *x == '&') {
+                        self.input.next();
+                        return Some(Token::Operator(Operator::LAnd));
+                    }
 
-                        Some(Token::Operator(Operator::BAnd))
+                    if self.input.peek().is_some_and(|x| *x == '=') {
+                        self.input.next();
+                        return Some(Token::OperatorAssign(Operator::BAnd));
                     }
 
-                    '|' => {
-                        if self.input.peek().is_some_and(|x| *x == '|') {
-                            self.input.next();
-                            return Some(Token::Operator(Operator::LOr));
-                        }
+                    Some(Token::Operator(Operator::BAnd))
+                }
 
-                        if self.input.peek().is_some_and(|x| *x == '=') {
-                            self.input.next();
-                            return Some(Token::OperatorAssign(Operator::BOr));
-                        }
+                '|' => {
+                    if self.input.peek().is_some_and(|x| *x == '|') {
+                        self.input.next();
+                        return Some(Token::Operator(Operator::LOr));
+                    }
 
-                        Some(Token::Operator(Operator::BOr))
+                    if self.input.peek().is_some_and(|x| *x == '=') {
+                        self.input.next();
+                        return Some(Token::OperatorAssign(Operator::BOr));
                     }
 
-                    '^' => {
-                        if self.input.peek().is_some_and(|x| *x == '=') {
-                            self.input.next();
-                            return Some(Token::OperatorAssign(Operator::BXor));
-                        }
+                    Some(Token::Operator(Operator::BOr))
+                }
 
-                        Some(Token::Operator(Operator::BXor))
+                '^' => {
+                    if self.input.peek().is_some_and(|x| *x == '=') {
+                        self.input.next();
+                        return Some(Token::OperatorAssign(Operator::BXor));
                     }
 
-                    _ => {
-                        let mut s: String = String::new();
-                        if c.is_ascii_alphabetic() || c == '_' {
-                            if c == 'r' && self.input.peek().is_some_and(|c| *c == '#') {
-                                let mut hash_count = 0;
-                                self.input.next();
+                    Some(Token::Operator(Operator::BXor))
+                }
 
-                                while self.input.peek().is_some_and(|x| *x == '#') {
-                                    hash_count += 1;
-                                    self.input.next();
-                                }
+                '@' => Some(Token::At),
 
-                                return if self.input.peek().is_some_and(|x| *x == '"') {
-                                    self.input.next();
+                '~' => Some(Token::Tilde),
 
-                                    loop {
-                                        match self.input.next() {
-                                            Some('"') => {
-                                                let mut close_hash_count = 0;
-                                                while close_hash_count < hash_count
-                                                    && self.input.peek().is_some_and(|x| *x == '#')
-                                                {
-                                                    close_hash_count += 1;
-                                                    self.input.next();
-                                                }
-
-                                                if close_hash_count == hash_count {
-                                                    break;
-                                                } else {
-                                                    s.push('"');
-                                                    s.extend(
-                                                        std::iter::repeat('#')
-                                                            .take(close_hash_count),
-                                                    );
-                                                }
-                                            }
-                                            Some(ch) => s.push(ch),
-                                            None => break,
-                                        }
-                                    }
+                '"' => Some(self.string()),
 
-                                    Some(Token::Literal(Literal::String(s)))
-                                } else {
-                                    while self
-                                        .input
-                                        .peek()
-                                        .is_some_and(|x| (*x).is_ascii_alphanumeric() || *x == '_')
-                                    {
-                                        s.push(self.input.next().unwrap());
-                                    }
-                                    Some(Token::RawIdent(s))
-                                };
-                            }
-                            s.push(c);
-                            while self
-                                .input
-                                .peek()
-                                .is_some_and(|x| (*x).is_ascii_alphanumeric() || *x == '_')
-                            {
-                                s.push(self.input.next().unwrap());
-                            }
+                '\'' => Some(self.quote()),
 
-                            let keyword: Result<Keyword, ()> = s.clone().try_into();
-                            return if let Ok(keyword) = keyword {
-                                Some(Token::Keyword(keyword))
-                            } else {
-                                Some(Token::Ident(s))
-                            };
-                        }
+                _ if c.is_ascii_digit() => Some(self.number(c)),
 
-                        if c.is_ascii_digit() {
-                            s.push(c);
-                            while self.input.peek().is_some_and(|x| (*x).is_ascii_digit()) {
-                                s.push(self.input.next().unwrap());
-                            }
+                _ if is_ident_start(c) => Some(self.ident(c)),
 
-                            if self.input.peek().is_some_and(|x| *x == '.') {
-                                s.push('.');
-                            } else {
-                                return Some(Token::Literal(Literal::Int(
-                                    s.parse::<i64>().unwrap(),
-                                )));
-                            }
-                            self.input.next();
-                            while self.input.peek().is_some_and(|x| (*x).is_ascii_digit()) {
-                                s.push(self.input.next().unwrap());
-                            }
+                _ => Some(self.error(format!("Unexpected character '{}'", c.escape_debug()))),
+            },
 
-                            return Some(Token::Literal(Literal::Float(s.parse::<f64>().unwrap())));
-                        }
+            None => {
+                if self.done {
+                    return None;
+                }
+                self.done = true;
+                Some(Token::EOF)
+            }
+        }
+    }
+}
 
-                        if c == '"' {
-                            while self.input.peek().is_some_and(|x| *x != '"') {
-                                let c = self.input.next().unwrap();
-                                s.push(c);
-                                if c == '\\' {
-                                    s.push(self.input.next().unwrap());
-                                }
-                            }
-                            self.input.next();
-                            return Some(Token::Literal(Literal::String(s)));
-                        }
+impl Lexer {
+    /// Lexes a block comment after the opening `/*`. Block comments nest, and outer doc
+    /// comments (`/** ... */`) are returned as [`Token::DocComment`].
+    fn block_comment(&mut self) -> Option<Token> {
+        let doc = self.input.peek() == Some(&'*')
+            && !matches!(self.input.peek_nth(1), Some('*') | Some('/'));
+        let mut text = String::new();
+        let mut depth = 1;
+
+        loop {
+            match self.input.next() {
+                None => return Some(self.error("Unterminated block comment".to_string())),
+                Some('/') if self.input.peek() == Some(&'*') => {
+                    self.input.next();
+                    depth += 1;
+                    text.push_str("/*");
+                }
+                Some('*') if self.input.peek() == Some(&'/') => {
+                    self.input.next();
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                    text.push_str("*/");
+                }
+                Some(c) => text.push(c),
+            }
+        }
 
-                        if c == '\'' {
-                            let mut buf = String::new();
-                            let mut fmt = false;
-                            let c = self.input.next().unwrap();
-                            buf.push(c);
-                            if c == '\\' {
-                                fmt = true;
-                                buf.push(self.input.next().unwrap());
-                            }
-                            let mut c = self.input.peek().unwrap();
-
-                            if *c == '\'' {
-                                return if fmt {
-                                    Some(Token::Literal(Literal::String(buf)))
-                                } else {
-                                    Some(Token::Literal(Literal::Char(buf.pop().unwrap())))
-                                };
-                            }
+        if !doc {
+            return self.next();
+        }
 
-                            while c.is_alphanumeric() || *c == '_' {
-                                buf.push(*c);
-                                self.input.next().unwrap();
-                                c = self.input.peek().unwrap();
-                            }
+        let doc = text[1..]
+            .lines()
+            .map(|line| {
+                let line = line.trim();
+                line.strip_prefix('*').unwrap_or(line).trim()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some(Token::DocComment(doc.trim().to_string()))
+    }
 
-                            return Some(Token::Lifetime(buf));
-                        }
+    fn ident(&mut self, c: char) -> Token {
+        match (c, self.input.peek().copied()) {
+            ('b', Some('"')) => {
+                self.input.next();
+                return self.byte_string();
+            }
+            ('b', Some('\'')) => {
+                self.input.next();
+                return self.byte();
+            }
+            ('c', Some('"')) => {
+                self.input.next();
+                return self.string();
+            }
+            ('r', Some('"' | '#')) if self.raw_string_ahead(0) => {
+                return match self.raw_string() {
+                    Ok(s) => Token::Literal(Literal::String(s)),
+                    Err(e) => e,
+                };
+            }
+            ('b', Some('r')) if self.raw_string_ahead(1) => {
+                self.input.next();
+                return match self.raw_string() {
+                    Ok(s) => Token::Literal(Literal::ByteString(s.into_bytes())),
+                    Err(e) => e,
+                };
+            }
+            ('c', Some('r')) if self.raw_string_ahead(1) => {
+                self.input.next();
+                return match self.raw_string() {
+                    Ok(s) => Token::Literal(Literal::String(s)),
+                    Err(e) => e,
+                };
+            }
+            ('r', Some('#')) if self.input.peek_nth(1).is_some_and(|c| is_ident_start(*c)) => {
+                self.input.next();
+                let mut s = String::new();
+                while self.input.peek().is_some_and(|x| is_ident_continue(*x)) {
+                    s.push(self.input.next().unwrap());
+                }
+                return Token::RawIdent(s);
+            }
+            _ => {}
+        }
+
+        let mut s = c.to_string();
+        while self.input.peek().is_some_and(|x| is_ident_continue(*x)) {
+            s.push(self.input.next().unwrap());
+        }
+
+        // Keywords are lowercase, `Fn` or `Struct` are plain identifiers.
+        let keyword: Result<Keyword, ()> = s.clone().try_into();
+        if let (Ok(keyword), true) = (keyword, s.starts_with(|c: char| c.is_lowercase())) {
+            Token::Keyword(keyword)
+        } else {
+            Token::Ident(s)
+        }
+    }
+
+    /// Whether the input at `offset` continues a raw string, which is any number of `#`
+    /// followed by a `"`.
+    fn raw_string_ahead(&self, offset: usize) -> bool {
+        let mut n = offset;
+        while self.input.peek_nth(n) == Some(&'#') {
+            n += 1;
+        }
+        self.input.peek_nth(n) == Some(&'"')
+    }
 
-                        panic!("ERROR: Invalid syntax! {}", c);
+    /// Lexes a raw string after its `r` prefix.
+    fn raw_string(&mut self) -> Result<String, Token> {
+        let mut hash_count = 0;
+        while self.input.peek() == Some(&'#') {
+            hash_count += 1;
+            self.input.next();
+        }
+        self.input.next();
+
+        let mut s = String::new();
+        loop {
+            match self.input.next() {
+                Some('"') => {
+                    let mut close_hash_count = 0;
+                    while close_hash_count < hash_count && self.input.peek() == Some(&'#') {
+                        close_hash_count += 1;
+                        self.input.next();
                     }
-                };
+
+                    if close_hash_count == hash_count {
+                        return Ok(s);
+                    }
+                    s.push('"');
+                    s.extend(std::iter::repeat_n('#', close_hash_count));
+                }
+                Some(ch) => s.push(ch),
+                None => return Err(self.error("Unterminated raw string literal".to_string())),
             }
+        }
+    }
 
-            None => {
-                if self.done {
-                    return None;
+    /// Reads the contents of a string literal after the opening quote, resolving escapes.
+    fn string_contents(&mut self, byte: bool) -> Result<String, Token> {
+        let mut s = String::new();
+        let mut error = None;
+        loop {
+            match self.input.next() {
+                Some('"') => break,
+                Some('\\') if self.input.peek() == Some(&'\n') => {
+                    while self.input.peek().is_some_and(|x| x.is_whitespace()) {
+                        self.input.next();
+                    }
                 }
-                self.done = true;
-                Some(Token::EOF)
+                Some('\\') => match self.escape(byte) {
+                    Ok(c) => s.push(c),
+                    Err(e) => error = error.or(Some(e)),
+                },
+                Some(c) if byte && !c.is_ascii() => {
+                    let e = "Non-ASCII character in byte string literal".to_string();
+                    error = error.or(Some(e));
+                }
+                Some(c) => s.push(c),
+                None => return Err(self.error("Unterminated string literal".to_string())),
+            }
+        }
+        match error {
+            Some(e) => Err(self.error(e)),
+            None => Ok(s),
+        }
+    }
+
+    fn string(&mut self) -> Token {
+        match self.string_contents(false) {
+            Ok(s) => Token::Literal(Literal::String(s)),
+            Err(e) => e,
+        }
+    }
+
+    fn byte_string(&mut self) -> Token {
+        match self.string_contents(true) {
+            // Escapes are at most `\xFF`, other characters are ASCII.
+            Ok(s) => Token::Literal(Literal::ByteString(s.chars().map(|c| c as u8).collect())),
+            Err(e) => e,
+        }
+    }
+
+    /// Resolves an escape sequence after the backslash. Byte escapes allow `\x` values up to
+    /// `\xFF` and no unicode escapes.
+    fn escape(&mut self, byte: bool) -> Result<char, String> {
+        let c = match self.input.next() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('\\') => '\\',
+            Some('0') => '\0',
+            Some('\'') => '\'',
+            Some('"') => '"',
+            Some('x') => {
+                let hex: String = (0..2).filter_map(|_| self.input.next()).collect();
+                let value = u8::from_str_radix(&hex, 16)
+                    .map_err(|_| format!("Invalid escape '\\x{hex}'"))?;
+                if !byte && value > 0x7F {
+                    return Err(format!("Escape '\\x{hex}' is out of range"));
+                }
+                char::from(value)
+            }
+            Some('u') if !byte => {
+                if self.input.next() != Some('{') {
+                    return Err("Expected '{' in unicode escape".to_string());
+                }
+                let mut hex = String::new();
+                loop {
+                    match self.input.next() {
+                        Some('}') => break,
+                        Some(c) if c.is_ascii_hexdigit() || c == '_' => hex.push(c),
+                        _ => return Err("Unterminated unicode escape".to_string()),
+                    }
+                }
+                u32::from_str_radix(&hex.replace('_', ""), 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("Invalid unicode escape '\\u{{{hex}}}'"))?
+            }
+            Some(c) => return Err(format!("Unknown escape '\\{}'", c.escape_debug())),
+            None => return Err("Unterminated escape".to_string()),
+        };
+        Ok(c)
+    }
+
+    /// Lexes a char literal or a lifetime after the opening `'`.
+    fn quote(&mut self) -> Token {
+        match self.input.peek().copied() {
+            Some('\\') => {
+                self.input.next();
+                let c = self.escape(false);
+                self.close_quote(c.map(Literal::Char))
             }
+            Some(c) if self.input.peek_nth(1) == Some(&'\'') => {
+                self.input.next();
+                self.close_quote(Ok(Literal::Char(c)))
+            }
+            Some(c) if is_ident_start(c) => {
+                if c == 'r' && self.input.peek_nth(1) == Some(&'#') {
+                    self.input.next();
+                    self.input.next();
+                }
+                let mut s = String::new();
+                while self.input.peek().is_some_and(|x| is_ident_continue(*x)) {
+                    s.push(self.input.next().unwrap());
+                }
+                Token::Lifetime(s)
+            }
+            _ => {
+                self.input.next();
+                self.error("Unterminated character literal".to_string())
+            }
+        }
+    }
+
+    /// Lexes a byte literal after the opening `b'`.
+    fn byte(&mut self) -> Token {
+        let c = match self.input.next() {
+            Some('\\') => self.escape(true),
+            Some(c) if c.is_ascii() => Ok(c),
+            Some(_) => Err("Non-ASCII character in byte literal".to_string()),
+            None => Err("Unterminated byte literal".to_string()),
+        };
+        self.close_quote(c.map(|c| Literal::Byte(c as u8)))
+    }
+
+    fn close_quote(&mut self, literal: Result<Literal, String>) -> Token {
+        if self.input.peek() != Some(&'\'') {
+            return self.error("Unterminated character literal".to_string());
+        }
+        self.input.next();
+        match literal {
+            Ok(literal) => Token::Literal(literal),
+            Err(e) => self.error(e),
+        }
+    }
+
+    /// Lexes an integer or float literal, including radix prefixes, `_` separators,
+    /// exponents and type suffixes such as `1u32` or `2.5f32`.
+    fn number(&mut self, c: char) -> Token {
+        let radix = match (c, self.input.peek()) {
+            ('0', Some('x')) => 16,
+            ('0', Some('o')) => 8,
+            ('0', Some('b')) => 2,
+            _ => 10,
         };
+
+        let mut s = String::new();
+        // The prefix is only kept to report the literal as written.
+        let mut prefix = String::new();
+        if radix == 10 {
+            s.push(c);
+        } else {
+            prefix.push(c);
+            prefix.push(self.input.next().unwrap());
+        }
+
+        let mut float = false;
+        self.digits(&mut s, radix);
+
+        if radix == 10 {
+            if self.input.peek() == Some(&'.')
+                && self
+                    .input
+                    .peek_nth(1)
+                    .is_none_or(|x| *x != '.' && !is_ident_start(*x))
+            {
+                self.input.next();
+                float = true;
+                s.push('.');
+                self.digits(&mut s, 10);
+            }
+
+            let exponent = match (self.input.peek_nth(1), self.input.peek_nth(2)) {
+                (Some('+' | '-'), Some(d)) => d.is_ascii_digit(),
+                (Some(d), _) => d.is_ascii_digit() || *d == '_',
+                _ => false,
+            };
+            if self.input.peek().is_some_and(|x| *x == 'e' || *x == 'E') && exponent {
+                float = true;
+                s.push(self.input.next().unwrap());
+                if self.input.peek().is_some_and(|x| *x == '+' || *x == '-') {
+                    s.push(self.input.next().unwrap());
+                }
+                self.digits(&mut s, 10);
+            }
+        }
+
+        let mut suffix = String::new();
+        while self.input.peek().is_some_and(|x| is_ident_continue(*x)) {
+            suffix.push(self.input.next().unwrap());
+        }
+        if radix == 10 && suffix.starts_with('f') {
+            float = true;
+        }
+
+        let number = s.replace('_', "");
+        let literal = if float {
+            number.parse().map(Literal::Float).ok()
+        } else {
+            u128::from_str_radix(&number, radix).map(Literal::Int).ok()
+        };
+        match literal {
+            Some(literal) => Token::Literal(literal),
+            None => self.error(format!("Invalid number literal '{prefix}{s}{suffix}'")),
+        }
+    }
+
+    fn digits(&mut self, s: &mut String, radix: u32) {
+        while self
+            .input
+            .peek()
+            .is_some_and(|x| x.is_digit(radix) || *x == '_')
+        {
+            s.push(self.input.next().unwrap());
+        }
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(code: &str) -> Vec<Token> {
+        Lexer::new(code.to_string())
+            .take_while(|t| *t != Token::EOF)
+            .collect()
+    }
+
+    fn literal(code: &str) -> Literal {
+        match tokens(code).as_slice() {
+            [Token::Literal(literal)] => literal.clone(),
+            tokens => panic!("{code} gave {tokens:?}"),
+        }
+    }
+
+    fn error(code: &str) -> LexError {
+        tokens(code)
+            .into_iter()
+            .find_map(|t| match t {
+                Token::Error(e) => Some(e),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("{code} gave no error"))
+    }
+
+    #[test]
+    fn byte_and_raw_strings() {
+        let bytes = |b: &[u8]| Literal::ByteString(b.to_vec());
+        assert_eq!(literal(r#"b"a\x80\n""#), bytes(b"a\x80\n"));
+        assert_eq!(literal(r##"br#"a"b"#"##), bytes(b"a\"b"));
+        assert_eq!(
+            literal(r###"r##"x"#y"##"###),
+            Literal::String("x\"#y".to_string())
+        );
+        assert_eq!(literal(r#"r"\n""#), Literal::String("\\n".to_string()));
+        assert_eq!(literal(r#"c"hi""#), Literal::String("hi".to_string()));
+        assert_eq!(
+            literal(r#""\u{48}\x41""#),
+            Literal::String("HA".to_string())
+        );
+        assert_eq!(literal(r"b'\n'"), Literal::Byte(b'\n'));
+        assert_eq!(literal("'x'"), Literal::Char('x'));
+        assert_eq!(tokens("'a"), [Token::Lifetime("a".to_string())]);
+        assert_eq!(tokens("r#type"), [Token::RawIdent("type".to_string())]);
+
+        assert_eq!(error(r#""\x80""#).message, "Escape '\\x80' is out of range");
+        assert_eq!(
+            error(r#"b"é""#).message,
+            "Non-ASCII character in byte string literal"
+        );
+        assert_eq!(
+            error(r##"r#"open"##).message,
+            "Unterminated raw string literal"
+        );
+    }
+
+    #[test]
+    fn literal_suffixes() {
+        assert_eq!(literal("1u32"), Literal::Int(1));
+        assert_eq!(literal("0xFFu8"), Literal::Int(255));
+        assert_eq!(literal("0b1010_1010"), Literal::Int(170));
+        assert_eq!(literal("1_000i64"), Literal::Int(1000));
+        assert_eq!(literal("2.5f32"), Literal::Float(2.5));
+        assert_eq!(literal("1f64"), Literal::Float(1.0));
+        assert_eq!(literal("1e3"), Literal::Float(1000.0));
+        assert_eq!(literal("1.5E-1"), Literal::Float(0.15));
+
+        // A dot followed by a range or a method is not part of the number.
+        assert_eq!(tokens("1..2")[0], Token::Literal(Literal::Int(1)));
+        assert_eq!(
+            tokens("1.max(2)")[..3],
+            [
+                Token::Literal(Literal::Int(1)),
+                Token::Dot,
+                Token::Ident("max".to_string())
+            ]
+        );
+        assert_eq!(error("0x").message, "Invalid number literal '0x'");
+        assert_eq!(error("0o_u8").message, "Invalid number literal '0o_u8'");
+    }
+
+    #[test]
+    fn nested_block_comments() {
+        assert_eq!(
+            tokens("/* a /* b */ c */ x"),
+            [Token::Ident("x".to_string())]
+        );
+        assert_eq!(
+            tokens("/** doc /* inner */\n * more */"),
+            [Token::DocComment("doc /* inner */\nmore".to_string())]
+        );
+        assert_eq!(tokens("/*** not a doc */ /**/"), []);
+
+        let e = error("x\n  /* open /* */");
+        assert_eq!(e.message, "Unterminated block comment");
+        assert_eq!((e.line, e.column), (2, 3));
+    }
+
+    #[test]
+    fn keywords_are_lowercase() {
+        assert_eq!(
+            tokens("struct Struct fn Fn"),
+            [
+                Token::Keyword(Keyword::Struct),
+                Token::Ident("Struct".to_string()),
+                Token::Keyword(Keyword::Fn),
+                Token::Ident("Fn".to_string()),
+            ]
+        );
     }
 }
@@ -1,125 +1,400 @@
-use crate::ui::consts::{META_FILE, META_PATH, UI_ANNOTATION, UI_PATH};
-use crate::ui::meta::lexer::Lexer;
-use crate::ui::meta::token::{Keyword, Token};
-use std::fs::{create_dir_all, read_dir, File, OpenOptions, ReadDir};
+use crate::ui::consts::{
+    builtin_elements, CONTAINER_ARGUMENT, EVENTS_ARGUMENT, GENERATED_MODULE, LIB_PATH, MAIN_PATH,
+    MANIFEST_FILE, META_PATH, META_VERSION, OVERRIDE_ARGUMENT, SOURCE_PATH, UI_ANNOTATION, UI_PATH,
+};
+use crate::ui::meta::file::{ElementMeta, MetaError, MetaFile, PropertyMeta, SourceLocation};
+use crate::ui::meta::parser::{Item, ItemKind, Parser, Visibility};
+use crate::ui::meta::token::{Literal, Token};
+use crate::ui::meta::workspace::{find_crates, find_workspace_root, Crate};
+use hashbrown::HashMap;
+use std::fs::{create_dir_all, metadata, File, OpenOptions};
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+pub mod file;
 pub mod lexer;
+pub mod parser;
 pub mod token;
+pub mod workspace;
 
-pub fn generate_meta() {
-    if let Ok(dir) = read_dir("src") {
-        create_dir_all(UI_PATH).unwrap();
-        let res = search(dir, vec![]).expect("Failed to read assets UI directory");
-        let mut file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(META_PATH)
-            .expect(&format!("Failed to generate {} file", META_FILE));
-        file.write_all(res.as_bytes())
-            .expect(&format!("Failed to generate {} file", META_FILE));
+/// Scans the project sources for annotated elements and writes the resulting meta file.
+pub fn generate_meta() -> Result<MetaFile, MetaError> {
+    let crates = find_crates();
+    let mut visited = HashMap::new();
+    let elements = search(&crates, &mut visited)?;
+    let mut meta = MetaFile::new(elements);
+    meta.validate(&builtin_elements())?;
+
+    let mut manifests = vec![PathBuf::from(MANIFEST_FILE)];
+    manifests.extend(find_workspace_root().map(|root| root.join(MANIFEST_FILE)));
+    manifests.extend(crates.iter().map(|krate| krate.dir.join(MANIFEST_FILE)));
+    for manifest in manifests.into_iter().filter(|m| m.is_file()) {
+        let source = manifest.to_string_lossy().replace('\\', "/");
+        visited.insert(manifest.canonicalize()?, source);
     }
+    meta.sources = visited.into_values().collect();
+    meta.sources.sort();
+
+    create_dir_all(UI_PATH)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(META_PATH)?;
+    file.write_all(meta.serialize()?.as_bytes())?;
+
+    Ok(meta)
 }
 
-fn search(dir: ReadDir, path: Vec<String>) -> Result<String, std::io::Error> {
-    let mut res = String::new();
-    for entry in dir {
-        let entry = entry?;
-        let meta = entry.metadata()?;
-
-        let mut new_path = path.clone();
-        let mut file_name = entry.file_name().into_string().unwrap();
-        let file = file_name.ends_with(".rs");
-        if file {
-            file_name.truncate(file_name.len() - 3);
-            if !((file_name == "lib" || file_name == "main") && new_path.is_empty())
-                && (file_name != "mod" || new_path.is_empty())
-            {
-                new_path.push(file_name);
-            }
-        } else {
-            new_path.push(file_name);
+/// Returns true if the meta file is missing or was written by an older version, or if any
+/// file it was generated from has been modified or removed since.
+pub fn is_outdated() -> bool {
+    let Ok(generated) = metadata(META_PATH).and_then(|m| m.modified()) else {
+        return true;
+    };
+    let meta = std::fs::read_to_string(META_PATH)
+        .ok()
+        .and_then(|contents| MetaFile::parse(&contents).ok());
+    let Some(meta) = meta.filter(|m| m.version == META_VERSION && !m.sources.is_empty()) else {
+        return true;
+    };
+    let changed = |path: &Path| {
+        metadata(path)
+            .and_then(|m| m.modified())
+            .map_or(true, |modified| modified > generated)
+    };
+    // A dependency which started shipping a meta file isn't scanned anymore.
+    let shipped = find_crates()
+        .iter()
+        .filter_map(Crate::meta_file)
+        .any(|file| {
+            let file = file.to_string_lossy().replace('\\', "/");
+            !meta.sources.contains(&file)
+        });
+    shipped || meta.sources.iter().any(|source| changed(Path::new(source)))
+}
+
+/// Walks the module tree of the crate starting at its root files, so elements get the path they
+/// are actually reachable at. Files which are not part of the module tree are not scanned.
+/// Library crates of the workspace and path dependencies are scanned the same way, with their
+/// crate name as the first path segment, unless they ship a precomputed meta file.
+/// The files scanned are added to `visited`, by their canonical path.
+fn search(
+    crates: &[Crate],
+    visited: &mut HashMap<PathBuf, String>,
+) -> Result<Vec<ElementMeta>, MetaError> {
+    let mut res = Vec::new();
+    for root in [LIB_PATH, MAIN_PATH] {
+        let root = Path::new(root);
+        if root.exists() {
+            let dir = root.parent().unwrap_or(Path::new(SOURCE_PATH));
+            check_file(
+                root,
+                dir.to_path_buf(),
+                vec!["crate".to_string()],
+                vec![],
+                true,
+                visited,
+                &mut res,
+            )?;
         }
+    }
 
-        if meta.is_dir() {
-            res.push_str(&search(read_dir(entry.path())?, new_path)?);
-        } else if file {
-            res.push_str(&check_file(entry.path(), new_path)?);
+    for krate in crates {
+        if let Some(meta) = krate.meta_file() {
+            let source = meta.to_string_lossy().replace('\\', "/");
+            visited.insert(meta.canonicalize()?, source);
+            let contents = std::fs::read_to_string(&meta)?;
+            for mut element in MetaFile::parse(&contents)?.elements {
+                let path = element
+                    .path
+                    .strip_prefix("crate::")
+                    .unwrap_or(&element.path);
+                element.path = format!("{}::{}", krate.name, path);
+                if let Some(source) = &mut element.source {
+                    source.file = krate
+                        .dir
+                        .join(&source.file)
+                        .to_string_lossy()
+                        .replace('\\', "/");
+                }
+                res.push(element);
+            }
+        } else {
+            let dir = krate.lib.parent().unwrap_or(&krate.dir).to_path_buf();
+            let path = vec![krate.name.clone()];
+            check_file(&krate.lib, dir, path, vec![], true, visited, &mut res)?;
         }
     }
+
     Ok(res)
 }
 
-fn check_file(file_path: PathBuf, path: Vec<String>) -> Result<String, std::io::Error> {
+struct Scope<'a> {
+    source: &'a str,
+    file_dir: &'a Path,
+    dir: PathBuf,
+    inline: bool,
+    path: Vec<String>,
+    cfg: Vec<String>,
+    /// Whether the module can be named by the generated code.
+    visible: bool,
+}
+
+fn check_file(
+    file_path: &Path,
+    dir: PathBuf,
+    path: Vec<String>,
+    cfg: Vec<String>,
+    visible: bool,
+    visited: &mut HashMap<PathBuf, String>,
+    res: &mut Vec<ElementMeta>,
+) -> Result<(), std::io::Error> {
+    let source = file_path.to_string_lossy().replace('\\', "/");
+    if visited
+        .insert(file_path.canonicalize()?, source.clone())
+        .is_some()
+    {
+        return Ok(());
+    }
+
     let mut file = File::open(file_path)?;
     let mut contents = String::new();
     file.read_to_string(&mut contents)?;
 
-    let path = path.join("::");
+    let scope = Scope {
+        source: &source,
+        file_dir: file_path.parent().unwrap_or(Path::new("")),
+        dir,
+        inline: false,
+        path,
+        cfg,
+        visible,
+    };
 
-    let mut res = String::new();
+    let mut parser = Parser::new(contents);
+    let items = parser.parse();
+    for error in parser.errors() {
+        eprintln!("Warning: {}:{}", source, error);
+    }
 
-    let mut tokens = Lexer::new(contents);
-    let mut tag;
+    check_items(items, &scope, visited, res)
+}
 
-    while let Some(mut token) = tokens.next() {
-        if token == Token::EOF {
-            break;
-        }
+fn check_items(
+    items: Vec<Item>,
+    scope: &Scope,
+    visited: &mut HashMap<PathBuf, String>,
+    res: &mut Vec<ElementMeta>,
+) -> Result<(), std::io::Error> {
+    for item in items {
+        let mut cfg = scope.cfg.clone();
+        cfg.extend(
+            item.attributes
+                .iter()
+                .filter(|a| a.is_cfg())
+                .map(|a| format!("cfg{}", a.arguments_text())),
+        );
 
-        if token != Token::Hash {
-            continue;
-        }
-        token = tokens.next().unwrap();
-        if token != Token::LBracket {
-            continue;
-        }
-        token = tokens.next().unwrap();
-        if token != Token::Ident(UI_ANNOTATION.to_string()) {
-            continue;
-        }
-        token = tokens.next().unwrap();
-        if token != Token::LParen {
-            continue;
-        }
-        token = tokens.next().unwrap();
-        if let Token::Ident(ident) = token {
-            tag = ident;
-        } else {
-            continue;
-        }
-        token = tokens.next().unwrap();
-        if token != Token::RParen {
-            continue;
-        }
-        token = tokens.next().unwrap();
-        if token != Token::RBracket {
-            continue;
-        }
-        token = tokens.next().unwrap();
-        if token == Token::Keyword(Keyword::Pub) {
-            token = tokens.next().unwrap();
-            if token == Token::LParen {
-                while token != Token::RParen {
-                    token = tokens.next().unwrap();
+        match item.kind {
+            ItemKind::Module(Some(ref body)) => {
+                let mut path = scope.path.clone();
+                path.push(item.name.clone());
+                let inner = Scope {
+                    source: scope.source,
+                    file_dir: scope.file_dir,
+                    dir: scope.dir.join(&item.name),
+                    inline: true,
+                    path,
+                    cfg,
+                    visible: scope.visible && is_visible(&scope.path, &item.visibility),
+                };
+                check_items(body.clone(), &inner, visited, res)?;
+            }
+            ItemKind::Module(None) => {
+                let explicit = item.attributes.iter().find_map(|a| match a.name() {
+                    "path" => match a.value() {
+                        Some(Literal::String(path)) => Some(path.clone()),
+                        _ => None,
+                    },
+                    _ => None,
+                });
+
+                let (file, dir) = if let Some(explicit) = explicit {
+                    let base = if scope.inline {
+                        scope.dir.as_path()
+                    } else {
+                        scope.file_dir
+                    };
+                    let file = base.join(explicit);
+                    let dir = file.parent().unwrap_or(Path::new("")).to_path_buf();
+                    (file, dir)
+                } else {
+                    let dir = scope.dir.join(&item.name);
+                    let file = scope.dir.join(format!("{}.rs", item.name));
+                    if file.exists() {
+                        (file, dir)
+                    } else {
+                        (dir.join("mod.rs"), dir)
+                    }
+                };
+
+                if !file.exists() {
+                    eprintln!(
+                        "Warning: File for module '{}' declared at {}:{}:{} not found",
+                        item.name, scope.source, item.line, item.column
+                    );
+                    continue;
+                }
+
+                let visible = scope.visible && is_visible(&scope.path, &item.visibility);
+                let mut path = scope.path.clone();
+                path.push(item.name);
+                check_file(&file, dir, path, cfg, visible, visited, res)?;
+            }
+            ItemKind::Use(_) => {}
+            ItemKind::Struct | ItemKind::Enum => {
+                if let Some(mut element) = check_item(item, scope) {
+                    element.cfg = cfg;
+                    res.push(element);
                 }
-                token = tokens.next().unwrap();
             }
         }
-        if token != Token::Keyword(Keyword::Struct) {
-            continue;
+    }
+
+    Ok(())
+}
+
+fn check_item(item: Item, scope: &Scope) -> Option<ElementMeta> {
+    let attribute = item.attributes.iter().find(|a| a.name() == UI_ANNOTATION)?;
+    let path = scope.path.join("::");
+
+    let location = SourceLocation {
+        file: scope.source.to_string(),
+        line: item.line,
+        column: item.column,
+    };
+
+    let mut arguments = attribute.arguments().into_iter();
+    let tag = match arguments.next().as_deref() {
+        Some([Token::Ident(tag)] | [Token::RawIdent(tag)])
+        | Some([Token::Literal(Literal::String(tag))]) => tag.clone(),
+        _ => {
+            eprintln!(
+                "Warning: #[{}] on '{}' at {} is missing a tag, skipping it",
+                UI_ANNOTATION, item.name, location
+            );
+            return None;
         }
-        token = tokens.next().unwrap();
-        if let Token::Ident(ident) = token {
-            if path.is_empty() {
-                res.push_str(&format!("{}={};", tag, ident));
-            } else {
-                res.push_str(&format!("{}={}::{};", tag, path, ident));
+    };
+
+    if !scope.visible || !is_visible(&scope.path, &item.visibility) {
+        eprintln!(
+            "Warning: Skipping element '{}' at {}, {}::{} is not visible from {}",
+            tag, location, path, item.name, GENERATED_MODULE
+        );
+        return None;
+    }
+
+    let mut element = ElementMeta::new(tag, format!("{}::{}", path, item.name));
+
+    for (i, argument) in arguments.enumerate() {
+        match argument.as_slice() {
+            [Token::Ident(arg)] if arg == OVERRIDE_ARGUMENT => element.overrides = true,
+            [Token::Ident(arg)] if arg == CONTAINER_ARGUMENT => element.container = true,
+            [Token::Ident(arg), Token::LParen, events @ .., Token::RParen]
+                if arg == EVENTS_ARGUMENT =>
+            {
+                element.events.extend(events.iter().filter_map(|t| match t {
+                    Token::Ident(event) => Some(event.clone()),
+                    _ => None,
+                }))
             }
+            _ => eprintln!(
+                "Warning: Ignoring unknown argument {} of #[{}] at {}",
+                i + 2,
+                UI_ANNOTATION,
+                location
+            ),
         }
     }
 
-    Ok(res)
+    element.generics = item.generics;
+    element.properties = item
+        .fields
+        .into_iter()
+        .filter(|field| field.visibility != Visibility::Private)
+        .map(|field| PropertyMeta {
+            name: field.name,
+            ty: field.ty,
+            docs: (!field.docs.is_empty()).then(|| field.docs.join("\n")),
+        })
+        .collect();
+    if !item.docs.is_empty() {
+        element.docs = Some(item.docs.join("\n"));
+    }
+    element.source = Some(location);
+    Some(element)
+}
+
+/// Whether an item with the visibility, declared in the module at `path`, can be named from
+/// the generated code. Items of other crates have to be `pub`.
+fn is_visible(path: &[String], visibility: &Visibility) -> bool {
+    let visible_in = match visibility {
+        Visibility::Public => return true,
+        Visibility::Crate => vec!["crate".to_string()],
+        Visibility::Private => path.to_vec(),
+        Visibility::Restricted(restricted) => {
+            let mut res = path.to_vec();
+            for segment in restricted {
+                match segment.as_str() {
+                    "crate" => res.truncate(1),
+                    "self" => {}
+                    "super" => {
+                        res.pop();
+                    }
+                    segment => res.push(segment.to_string()),
+                }
+            }
+            res
+        }
+    };
+    if path.first().is_some_and(|root| root != "crate") {
+        return false;
+    }
+    let generated = GENERATED_MODULE.split("::");
+    visible_in.len() <= generated.clone().count() && generated.zip(&visible_in).all(|(a, b)| a == b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(path: &str) -> Vec<String> {
+        path.split("::").map(str::to_string).collect()
+    }
+
+    #[test]
+    fn visibility() {
+        let restricted = |path: &str| Visibility::Restricted(self::path(path));
+        assert!(is_visible(&path("crate::a::b"), &Visibility::Crate));
+        assert!(is_visible(&path("widgets"), &Visibility::Public));
+        assert!(!is_visible(&path("widgets"), &Visibility::Crate));
+        assert!(is_visible(&path("crate"), &Visibility::Private));
+        assert!(is_visible(&path("crate::ui"), &Visibility::Private));
+        assert!(!is_visible(&path("crate::widgets"), &Visibility::Private));
+        assert!(is_visible(&path("crate::widgets"), &restricted("super")));
+        assert!(!is_visible(
+            &path("crate::widgets::a"),
+            &restricted("super")
+        ));
+        assert!(is_visible(
+            &path("crate::widgets"),
+            &restricted("crate::ui")
+        ));
+        assert!(!is_visible(
+            &path("crate::widgets"),
+            &restricted("crate::widgets")
+        ));
+    }
 }
@@ -0,0 +1,756 @@
+use crate::ui::meta::lexer::Lexer;
+use crate::ui::meta::token::{Keyword, LexError, Literal, Operator, Token};
+
+#[derive(Clone)]
+pub struct Attribute {
+    pub path: Vec<String>,
+    pub tokens: Vec<Token>,
+}
+
+#[derive(Clone)]
+pub enum ItemKind {
+    Struct,
+    Enum,
+    /// A module declaration, with its items if it is declared inline.
+    Module(Option<Vec<Item>>),
+    /// A use declaration with the tokens of its use tree.
+    Use(Vec<Token>),
+}
+
+/// The visibility of an item, where `pub(self)` counts as private.
+#[derive(Clone, Default, PartialEq)]
+pub enum Visibility {
+    #[default]
+    Private,
+    Public,
+    Crate,
+    /// `pub(super)` or `pub(in path)`, with the segments of the path as written.
+    Restricted(Vec<String>),
+}
+
+/// A named field of a struct, with its type written back out.
+#[derive(Clone)]
+pub struct Field {
+    pub name: String,
+    pub ty: String,
+    pub visibility: Visibility,
+    pub docs: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct Item {
+    pub kind: ItemKind,
+    pub name: String,
+    pub visibility: Visibility,
+    pub generics: Vec<String>,
+    /// The named fields of a struct, empty for other items.
+    pub fields: Vec<Field>,
+    pub attributes: Vec<Attribute>,
+    pub docs: Vec<String>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Attribute {
+    pub fn name(&self) -> &str {
+        self.path.last().map(String::as_str).unwrap_or_default()
+    }
+
+    /// The comma separated arguments of a list attribute such as `#[name(a, b(c), d = 1)]`.
+    pub fn arguments(&self) -> Vec<Vec<Token>> {
+        let mut arguments = Vec::new();
+        let Some(Token::LParen) = self.tokens.first() else {
+            return arguments;
+        };
+
+        let mut current = Vec::new();
+        let mut depth = 0;
+        for token in &self.tokens[1..] {
+            match token {
+                Token::LParen | Token::LBracket | Token::LBrace => depth += 1,
+                Token::RParen | Token::RBracket | Token::RBrace if depth == 0 => break,
+                Token::RParen | Token::RBracket | Token::RBrace => depth -= 1,
+                Token::Comma if depth == 0 => {
+                    arguments.push(std::mem::take(&mut current));
+                    continue;
+                }
+                _ => {}
+            }
+            current.push(token.clone());
+        }
+        if !current.is_empty() {
+            arguments.push(current);
+        }
+        arguments
+    }
+
+    /// The tokens after the attribute path written back out, e.g. `(feature = "x")`.
+    pub fn arguments_text(&self) -> String {
+        let mut text = String::new();
+        for token in &self.tokens {
+            match token {
+                Token::Ident(ident) | Token::RawIdent(ident) => {
+                    if text.ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+                        text.push(' ');
+                    }
+                    text.push_str(ident);
+                }
+                Token::Literal(Literal::String(s)) => text.push_str(&format!("\"{s}\"")),
+                Token::Literal(Literal::Int(i)) => text.push_str(&i.to_string()),
+                Token::Literal(Literal::Char(c)) => text.push_str(&format!("{c:?}")),
+                Token::Literal(Literal::Bool(b)) => text.push_str(&b.to_string()),
+                Token::LParen => text.push('('),
+                Token::RParen => text.push(')'),
+                Token::Comma => text.push_str(", "),
+                Token::Assign => text.push_str(" = "),
+                Token::DColon => text.push_str("::"),
+                Token::Operator(Operator::Not) => text.push('!'),
+                _ => text.push_str(" .. "),
+            }
+        }
+        text
+    }
+
+    /// Whether this is a `#[cfg(...)]` attribute.
+    pub fn is_cfg(&self) -> bool {
+        self.name() == "cfg"
+    }
+
+    /// The value of a name-value attribute such as `#[doc = "..."]`.
+    pub fn value(&self) -> Option<&Literal> {
+        match self.tokens.as_slice() {
+            [Token::Assign, Token::Literal(literal)] => Some(literal),
+            _ => None,
+        }
+    }
+}
+
+/// The attributes, docs and visibility read so far for the next item.
+#[derive(Default)]
+struct Pending {
+    attributes: Vec<Attribute>,
+    docs: Vec<String>,
+    visibility: Visibility,
+}
+
+impl Pending {
+    fn clear(&mut self) {
+        *self = Pending::default();
+    }
+}
+
+/// A minimal parser for the parts of Rust items the meta generation cares about. Anything it
+/// doesn't understand is skipped, so it never fails on valid code.
+pub struct Parser {
+    lexer: Lexer,
+    errors: Vec<LexError>,
+}
+
+impl Parser {
+    pub fn new(code: String) -> Self {
+        Parser {
+            lexer: Lexer::new(code),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn parse(&mut self) -> Vec<Item> {
+        self.parse_items()
+    }
+
+    /// The lexical errors encountered while parsing. Invalid tokens are otherwise skipped.
+    pub fn errors(&self) -> &[LexError] {
+        &self.errors
+    }
+
+    /// Parses items until the end of the input or the `}` closing the current inline module.
+    /// Items nested in other blocks, such as function bodies, can't be named and are skipped.
+    fn parse_items(&mut self) -> Vec<Item> {
+        let mut items = Vec::new();
+        let mut pending = Pending::default();
+        let mut depth = 0;
+
+        loop {
+            match self.next() {
+                Token::EOF => break,
+                Token::RBrace if depth == 0 => break,
+                Token::LBrace => {
+                    depth += 1;
+                    pending.clear();
+                }
+                Token::RBrace => {
+                    depth -= 1;
+                    pending.clear();
+                }
+                Token::DocComment(doc) => pending.docs.push(doc),
+                Token::Hash => match self.next() {
+                    Token::LBracket => {
+                        let attribute = self.parse_attribute();
+                        if attribute.name() == "doc" {
+                            if let Some(Literal::String(doc)) = attribute.value() {
+                                pending.docs.push(doc.trim().to_string());
+                            }
+                        }
+                        pending.attributes.push(attribute);
+                    }
+                    Token::Operator(Operator::Not) => {
+                        self.skip_group();
+                    }
+                    token => self.lexer.revert(token),
+                },
+                Token::Keyword(Keyword::Pub) => pending.visibility = self.parse_visibility(),
+                Token::Keyword(Keyword::Struct) if depth == 0 => {
+                    if let Some(item) = self.parse_item(ItemKind::Struct, &mut pending) {
+                        items.push(item);
+                    }
+                }
+                Token::Keyword(Keyword::Enum) if depth == 0 => {
+                    if let Some(item) = self.parse_item(ItemKind::Enum, &mut pending) {
+                        items.push(item);
+                    }
+                }
+                Token::Keyword(Keyword::Mod) if depth == 0 => {
+                    if let Some(item) = self.parse_module(&mut pending) {
+                        items.push(item);
+                    }
+                }
+                Token::Keyword(Keyword::Use) if depth == 0 => {
+                    items.push(self.parse_use(&mut pending));
+                }
+                _ => pending.clear(),
+            }
+        }
+
+        items
+    }
+
+    fn next(&mut self) -> Token {
+        loop {
+            match self.lexer.next() {
+                Some(Token::Error(e)) => self.errors.push(e),
+                Some(token) => return token,
+                None => return Token::EOF,
+            }
+        }
+    }
+
+    fn parse_item(&mut self, kind: ItemKind, pending: &mut Pending) -> Option<Item> {
+        let Pending {
+            attributes,
+            docs,
+            visibility,
+        } = std::mem::take(pending);
+
+        let name = match self.next() {
+            Token::Ident(name) | Token::RawIdent(name) => name,
+            token => {
+                self.lexer.revert(token);
+                return None;
+            }
+        };
+        let (line, column) = self.lexer.position();
+
+        let generics = match self.next() {
+            Token::Operator(Operator::Less) => self.parse_generics(),
+            token => {
+                self.lexer.revert(token);
+                Vec::new()
+            }
+        };
+        let fields = match kind {
+            ItemKind::Struct => self.parse_fields(),
+            _ => Vec::new(),
+        };
+
+        Some(Item {
+            kind,
+            name,
+            visibility,
+            generics,
+            fields,
+            attributes,
+            docs,
+            line,
+            column,
+        })
+    }
+
+    fn parse_module(&mut self, pending: &mut Pending) -> Option<Item> {
+        let Pending {
+            attributes,
+            docs,
+            visibility,
+        } = std::mem::take(pending);
+
+        let name = match self.next() {
+            Token::Ident(name) | Token::RawIdent(name) => name,
+            token => {
+                self.lexer.revert(token);
+                return None;
+            }
+        };
+        let (line, column) = self.lexer.position();
+
+        let body = match self.next() {
+            Token::Semicolon => None,
+            Token::LBrace => Some(self.parse_items()),
+            token => {
+                self.lexer.revert(token);
+                return None;
+            }
+        };
+
+        Some(Item {
+            kind: ItemKind::Module(body),
+            name,
+            visibility,
+            generics: Vec::new(),
+            fields: Vec::new(),
+            attributes,
+            docs,
+            line,
+            column,
+        })
+    }
+
+    /// Parses a use declaration after the `use` keyword, keeping the tokens of its use tree.
+    fn parse_use(&mut self, pending: &mut Pending) -> Item {
+        let Pending {
+            attributes,
+            docs,
+            visibility,
+        } = std::mem::take(pending);
+        let (line, column) = self.lexer.position();
+
+        let mut tree = Vec::new();
+        loop {
+            match self.next() {
+                Token::EOF | Token::Semicolon => break,
+                token => tree.push(token),
+            }
+        }
+
+        Item {
+            kind: ItemKind::Use(tree),
+            name: String::new(),
+            visibility,
+            generics: Vec::new(),
+            fields: Vec::new(),
+            attributes,
+            docs,
+            line,
+            column,
+        }
+    }
+
+    /// Parses the body of a struct after its generics, skipping a where clause. Tuple and unit
+    /// structs have no named fields and are left to the item loop.
+    fn parse_fields(&mut self) -> Vec<Field> {
+        match self.next() {
+            Token::LBrace => {}
+            token @ (Token::LParen | Token::Semicolon | Token::EOF) => {
+                self.lexer.revert(token);
+                return Vec::new();
+            }
+            // A where clause, which can contain parentheses of its own like `F: Fn()`.
+            _ => {
+                let mut depth = 0;
+                loop {
+                    match self.next() {
+                        Token::LBrace if depth == 0 => break,
+                        Token::LParen | Token::LBracket => depth += 1,
+                        Token::RParen | Token::RBracket => depth -= 1,
+                        token @ Token::Semicolon if depth == 0 => {
+                            self.lexer.revert(token);
+                            return Vec::new();
+                        }
+                        token @ Token::EOF => {
+                            self.lexer.revert(token);
+                            return Vec::new();
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let mut fields = Vec::new();
+        let mut pending = Pending::default();
+        loop {
+            match self.next() {
+                Token::EOF | Token::RBrace => break,
+                Token::DocComment(doc) => pending.docs.push(doc),
+                Token::Hash => match self.next() {
+                    Token::LBracket => {
+                        let attribute = self.parse_attribute();
+                        if attribute.name() == "doc" {
+                            if let Some(Literal::String(doc)) = attribute.value() {
+                                pending.docs.push(doc.trim().to_string());
+                            }
+                        }
+                    }
+                    token => self.lexer.revert(token),
+                },
+                Token::Keyword(Keyword::Pub) => pending.visibility = self.parse_visibility(),
+                Token::Ident(name) | Token::RawIdent(name) => {
+                    if self.next() != Token::Colon {
+                        pending.clear();
+                        continue;
+                    }
+                    let Pending {
+                        docs, visibility, ..
+                    } = std::mem::take(&mut pending);
+                    fields.push(Field {
+                        name,
+                        ty: self.parse_type(),
+                        visibility,
+                        docs,
+                    });
+                }
+                _ => pending.clear(),
+            }
+        }
+        fields
+    }
+
+    /// Parses a type up to the `,` or `}` ending it, and writes it back out.
+    fn parse_type(&mut self) -> String {
+        let mut text = String::new();
+        let mut depth = 0;
+        loop {
+            let token = self.next();
+            let word = |text: &mut String, word: &str| {
+                if text.ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+                    text.push(' ');
+                }
+                text.push_str(word);
+            };
+            match &token {
+                Token::EOF => break,
+                Token::Comma if depth == 0 => break,
+                Token::RBrace if depth == 0 => {
+                    self.lexer.revert(token);
+                    break;
+                }
+                Token::LParen | Token::LBracket | Token::LBrace => depth += 1,
+                Token::Operator(Operator::Less) => depth += 1,
+                Token::RParen | Token::RBracket | Token::RBrace => depth -= 1,
+                Token::Operator(Operator::Greater) => depth -= 1,
+                Token::Operator(Operator::Rsh) => depth -= 2,
+                _ => {}
+            }
+            match token {
+                Token::Ident(ident) | Token::RawIdent(ident) => word(&mut text, &ident),
+                Token::Keyword(keyword) => word(&mut text, &format!("{keyword:?}").to_lowercase()),
+                Token::Lifetime(name) => text.push_str(&format!("'{name}")),
+                Token::Literal(Literal::Int(i)) => word(&mut text, &i.to_string()),
+                Token::LParen => text.push('('),
+                Token::RParen => text.push(')'),
+                Token::LBracket => text.push('['),
+                Token::RBracket => text.push(']'),
+                Token::Comma => text.push_str(", "),
+                Token::Semicolon => text.push_str("; "),
+                Token::DColon => text.push_str("::"),
+                Token::Arrow => text.push_str(" -> "),
+                Token::Operator(Operator::Less) => text.push('<'),
+                Token::Operator(Operator::Greater) => text.push('>'),
+                Token::Operator(Operator::Rsh) => text.push_str(">>"),
+                Token::Operator(Operator::BAnd) => text.push('&'),
+                Token::Operator(Operator::LAnd) => text.push_str("&&"),
+                Token::Operator(Operator::Multiply) => text.push('*'),
+                Token::Operator(Operator::Plus) => text.push_str(" + "),
+                Token::Operator(Operator::Not) => text.push('!'),
+                Token::QMark => text.push('?'),
+                _ => {}
+            }
+        }
+        text.trim_end().to_string()
+    }
+
+    /// Parses the contents of `#[...]` after the opening bracket.
+    fn parse_attribute(&mut self) -> Attribute {
+        let mut path = Vec::new();
+        let mut tokens = Vec::new();
+        let mut depth = 0;
+
+        loop {
+            let token = self.next();
+            match &token {
+                Token::EOF => break,
+                Token::RBracket if depth == 0 => break,
+                Token::LParen | Token::LBracket | Token::LBrace => depth += 1,
+                Token::RParen | Token::RBracket | Token::RBrace => depth -= 1,
+                Token::Ident(ident) | Token::RawIdent(ident) if tokens.is_empty() => {
+                    path.push(ident.clone());
+                    continue;
+                }
+                Token::DColon if tokens.is_empty() => continue,
+                _ => {}
+            }
+            tokens.push(token);
+        }
+
+        Attribute { path, tokens }
+    }
+
+    /// Parses generic parameters after the opening `<`, returning their names. Lifetimes keep
+    /// their leading `'` and const parameters are prefixed with `const `.
+    fn parse_generics(&mut self) -> Vec<String> {
+        let mut generics = Vec::new();
+        let mut depth = 1;
+        let mut start = true;
+
+        while depth > 0 {
+            let token = self.next();
+            match token {
+                Token::EOF => break,
+                Token::Operator(Operator::Less) => depth += 1,
+                Token::Operator(Operator::Greater) => depth -= 1,
+                Token::Operator(Operator::Rsh) => depth -= 2,
+                Token::Operator(Operator::ARsh) => depth -= 3,
+                Token::Comma if depth == 1 => start = true,
+                Token::Lifetime(name) if start && depth == 1 => {
+                    generics.push(format!("'{name}"));
+                    start = false;
+                }
+                Token::Ident(ident) if start && depth == 1 && ident == "const" => {
+                    if let Token::Ident(name) = self.next() {
+                        generics.push(format!("const {name}"));
+                    }
+                    start = false;
+                }
+                Token::Ident(name) | Token::RawIdent(name) if start && depth == 1 => {
+                    generics.push(name);
+                    start = false;
+                }
+                _ => {}
+            }
+        }
+
+        generics
+    }
+
+    /// Parses the visibility after the `pub` keyword.
+    fn parse_visibility(&mut self) -> Visibility {
+        match self.next() {
+            Token::LParen => {}
+            token => {
+                self.lexer.revert(token);
+                return Visibility::Public;
+            }
+        }
+        let mut tokens = Vec::new();
+        let mut depth = 1;
+        loop {
+            match self.next() {
+                Token::EOF => break,
+                Token::LParen => depth += 1,
+                Token::RParen if depth == 1 => break,
+                Token::RParen => depth -= 1,
+                token => tokens.push(token),
+            }
+        }
+        let segments = |tokens: &[Token]| {
+            tokens
+                .iter()
+                .filter_map(|t| match t {
+                    Token::Ident(s) => Some(s.clone()),
+                    Token::Keyword(Keyword::Crate) => Some("crate".to_string()),
+                    _ => None,
+                })
+                .collect()
+        };
+        match tokens.as_slice() {
+            [Token::Keyword(Keyword::Crate)] => Visibility::Crate,
+            [Token::Ident(s)] if s == "self" => Visibility::Private,
+            [Token::Keyword(Keyword::In), path @ ..] => Visibility::Restricted(segments(path)),
+            path => Visibility::Restricted(segments(path)),
+        }
+    }
+
+    fn skip_group(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.next() {
+                Token::EOF => break,
+                Token::LParen | Token::LBracket | Token::LBrace => depth += 1,
+                Token::RParen | Token::RBracket | Token::RBrace => {
+                    depth -= 1;
+                    if depth <= 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(code: &str) -> Vec<Item> {
+        Parser::new(code.to_string()).parse()
+    }
+
+    #[test]
+    fn attributes() {
+        let items = parse(
+            "#![allow(dead_code)]
+            /// A slider.
+            #[doc = \" More docs. \"]
+            #[mve::ui_element(slider, container, events(click, hover))]
+            pub struct Slider<'a, T: Copy, const N: usize>(T);",
+        );
+        let [item] = items.as_slice() else {
+            panic!("Expected one item");
+        };
+        assert!(matches!(item.kind, ItemKind::Struct));
+        assert_eq!(item.name, "Slider");
+        assert_eq!(item.generics, ["'a", "T", "const N"]);
+        assert_eq!(item.docs, ["A slider.", "More docs."]);
+        assert_eq!((item.line, item.column), (5, 24));
+
+        let attribute = &item.attributes[1];
+        assert_eq!(attribute.path, ["mve", "ui_element"]);
+        assert_eq!(attribute.name(), "ui_element");
+        let arguments = attribute.arguments();
+        assert_eq!(arguments.len(), 3);
+        assert!(arguments[0] == [Token::Ident("slider".to_string())]);
+        assert_eq!(arguments[2].len(), 6);
+        assert!(item.attributes[0].value() == Some(&Literal::String(" More docs. ".to_string())));
+    }
+
+    #[test]
+    fn items() {
+        let items = parse(
+            "#[ui_element(toggle)]
+            #[derive(Clone)]
+            pub(in crate::ui) enum Toggle { On, Off }
+
+            #[ui_element(ignored)]
+            fn not_an_item() {}
+
+            struct r#Plain;",
+        );
+        let names: Vec<_> = items.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["Toggle", "Plain"]);
+        assert!(matches!(items[0].kind, ItemKind::Enum));
+        assert_eq!(items[0].attributes.len(), 2);
+        assert!(matches!(items[1].kind, ItemKind::Struct));
+        assert!(items[1].attributes.is_empty());
+    }
+
+    #[test]
+    fn cfg() {
+        let items = parse(
+            "#[cfg(all(feature = \"x\", not(test)))]
+            mod inner {
+                #[cfg_attr(test, derive(Debug))]
+                enum Kind {}
+
+                fn skipped() {
+                    struct Local;
+                }
+            }
+            #[path = \"other.rs\"]
+            mod outer;",
+        );
+        let ItemKind::Module(Some(body)) = &items[0].kind else {
+            panic!("Expected an inline module");
+        };
+        let cfg = &items[0].attributes[0];
+        assert_eq!(cfg.name(), "cfg");
+        assert_eq!(cfg.arguments_text(), "(all(feature = \"x\", not(test)))");
+
+        assert_eq!(body.len(), 1);
+        assert_eq!(body[0].name, "Kind");
+        assert_eq!(body[0].attributes[0].name(), "cfg_attr");
+
+        assert!(matches!(items[1].kind, ItemKind::Module(None)));
+        assert!(items[1].attributes[0].value() == Some(&Literal::String("other.rs".to_string())));
+    }
+
+    #[test]
+    fn visibility() {
+        let items = parse(
+            "struct A;
+            pub struct B;
+            pub(crate) struct C;
+            pub(self) struct D;
+            pub(super) struct E;
+            pub(in crate::ui) struct F;",
+        );
+        let visibilities: Vec<_> = items.iter().map(|i| i.visibility.clone()).collect();
+        assert!(
+            visibilities
+                == [
+                    Visibility::Private,
+                    Visibility::Public,
+                    Visibility::Crate,
+                    Visibility::Private,
+                    Visibility::Restricted(vec!["super".to_string()]),
+                    Visibility::Restricted(vec!["crate".to_string(), "ui".to_string()]),
+                ]
+        );
+    }
+
+    #[test]
+    fn fields() {
+        let items = parse(
+            "pub struct Slider<'a, T> {
+                /// The current value.
+                pub value: Option<Vec<T>>,
+                #[doc = \"Called on change.\"]
+                pub(crate) on_change: Box<dyn Fn(&'a T) -> bool + Send>,
+                #[serde(skip)]
+                label: &'a str,
+                steps: [u8; 4],
+            }
+            struct Tuple(u8, u16);
+            struct Unit;",
+        );
+        let fields: Vec<_> = items[0]
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.ty.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("value", "Option<Vec<T>>"),
+                ("on_change", "Box<dyn Fn(&'a T) -> bool + Send>"),
+                ("label", "&'a str"),
+                ("steps", "[u8; 4]"),
+            ]
+        );
+        assert_eq!(items[0].fields[0].docs, ["The current value."]);
+        assert_eq!(items[0].fields[1].docs, ["Called on change."]);
+        assert!(items[0].fields[1].visibility == Visibility::Crate);
+        assert!(items[0].fields[2].visibility == Visibility::Private);
+
+        let names: Vec<_> = items.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["Slider", "Tuple", "Unit"]);
+        assert!(items[1].fields.is_empty() && items[2].fields.is_empty());
+    }
+
+    #[test]
+    fn fields_after_where_clauses() {
+        let items = parse(
+            "struct Handler<F> where F: Fn() -> [u8; 2] {
+                callback: F,
+            }
+            struct Pair<T>(T, T) where T: Copy;
+            struct Marker<T> where T: Fn(u8);
+            struct Last { value: u8 }",
+        );
+        let names: Vec<_> = items.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["Handler", "Pair", "Marker", "Last"]);
+        let fields: Vec<Vec<_>> = items
+            .iter()
+            .map(|i| i.fields.iter().map(|f| f.name.as_str()).collect())
+            .collect();
+        assert_eq!(fields, [vec!["callback"], vec![], vec![], vec!["value"]]);
+    }
+}
@@ -1,11 +1,13 @@
 use mvutils::try_from_string;
+use std::fmt::{Display, Formatter};
 
-#[derive(PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     Ident(String),
     RawIdent(String),
     Literal(Literal),
     Lifetime(String),
+    DocComment(String),
 
     Dot,
     Comma,
@@ -31,14 +33,31 @@ pub enum Token {
     Assign, //=,
     Arrow,  //->
     Dollar, // $
+    At,     // @
+    Tilde,  // ~
 
     Operator(Operator),
     OperatorAssign(Operator),
     Keyword(Keyword),
 
+    Error(LexError),
+
     EOF,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
 #[derive(Debug, Clone, PartialOrd, PartialEq)]
 pub enum Operator {
     Plus,
@@ -123,9 +142,11 @@ pub enum Keyword {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
-    Int(i64),
+    Int(u128),
     Float(f64),
     String(String),
+    ByteString(Vec<u8>),
     Char(char),
+    Byte(u8),
     Bool(bool),
 }
@@ -0,0 +1,167 @@
+use crate::ui::consts::{MANIFEST_FILE, META_FILE, META_PATH};
+use hashbrown::HashSet;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize, Default)]
+struct Manifest {
+    package: Option<Package>,
+    lib: Option<Lib>,
+    workspace: Option<Workspace>,
+    #[serde(default)]
+    dependencies: BTreeMap<String, Dependency>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Lib {
+    name: Option<String>,
+    path: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct Workspace {
+    #[serde(default)]
+    dependencies: BTreeMap<String, Dependency>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Dependency {
+    Detailed {
+        path: Option<String>,
+        /// The name of the package if the dependency is renamed.
+        package: Option<String>,
+        #[serde(default)]
+        workspace: bool,
+    },
+    Version(IgnoredAny),
+}
+
+/// A path dependency whose elements can be used in UI files.
+pub struct Crate {
+    /// The name the crate is referred to by in code.
+    pub name: String,
+    pub dir: PathBuf,
+    /// The root file of the library target.
+    pub lib: PathBuf,
+}
+
+impl Crate {
+    /// A precomputed meta file shipped with the crate, if there is one.
+    pub fn meta_file(&self) -> Option<PathBuf> {
+        [self.dir.join(META_PATH), self.dir.join(META_FILE)]
+            .into_iter()
+            .find(|path| path.is_file())
+    }
+}
+
+/// Finds the path dependencies of the project, including workspace members it depends on
+/// through `workspace = true`. Renamed dependencies are named by their key in the manifest,
+/// others by their library name, which is the name used in code.
+pub fn find_crates() -> Vec<Crate> {
+    let Some(manifest) = read_manifest(Path::new(MANIFEST_FILE)) else {
+        return Vec::new();
+    };
+    let project = canonical(Path::new("."));
+    let root = find_workspace_root();
+    let root_manifest = root
+        .as_ref()
+        .and_then(|root| read_manifest(&root.join(MANIFEST_FILE)));
+    let workspace = root_manifest.as_ref().and_then(|m| m.workspace.as_ref());
+
+    let mut crates = Vec::new();
+    let mut seen: HashSet<PathBuf> = [project].into();
+
+    for (key, dependency) in &manifest.dependencies {
+        let (path, renamed) = match dependency {
+            Dependency::Detailed {
+                path: Some(path),
+                package,
+                ..
+            } => (Some(PathBuf::from(path)), package.is_some()),
+            Dependency::Detailed {
+                workspace: true,
+                package,
+                ..
+            } => match workspace.and_then(|w| w.dependencies.get(key)) {
+                Some(Dependency::Detailed {
+                    path: Some(path),
+                    package: inherited,
+                    ..
+                }) => (
+                    root.as_ref().map(|root| root.join(path)),
+                    package.is_some() || inherited.is_some(),
+                ),
+                _ => (None, false),
+            },
+            _ => (None, false),
+        };
+        if let Some(path) = path {
+            if seen.insert(canonical(&path)) {
+                if let Some(krate) = load_crate(&path, Some(key.as_str()).filter(|_| renamed)) {
+                    crates.push(krate);
+                }
+            }
+        }
+    }
+
+    crates
+}
+
+/// Loads the library crate in the directory, named `rename` or else by its library name.
+fn load_crate(dir: &Path, rename: Option<&str>) -> Option<Crate> {
+    let manifest = read_manifest(&dir.join(MANIFEST_FILE))?;
+    let lib = match manifest.lib.as_ref().and_then(|lib| lib.path.as_ref()) {
+        Some(path) => dir.join(path),
+        None => dir.join("src/lib.rs"),
+    };
+    if !lib.is_file() {
+        return None;
+    }
+
+    let name = rename
+        .map(ToString::to_string)
+        .or_else(|| manifest.lib.as_ref().and_then(|lib| lib.name.clone()))
+        .or_else(|| manifest.package.as_ref().map(|p| p.name.clone()))?;
+
+    Some(Crate {
+        name: name.replace('-', "_"),
+        dir: dir.to_path_buf(),
+        lib,
+    })
+}
+
+/// The directory of the workspace the project belongs to, relative to the project, like `..`.
+pub fn find_workspace_root() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    let up = cwd.ancestors().position(|dir| {
+        read_manifest(&dir.join(MANIFEST_FILE)).is_some_and(|m| m.workspace.is_some())
+    })?;
+    Some(match up {
+        0 => PathBuf::from("."),
+        up => vec![".."; up].join("/").into(),
+    })
+}
+
+fn read_manifest(path: &Path) -> Option<Manifest> {
+    let contents = read_to_string(path).ok()?;
+    match toml::from_str(&contents) {
+        Ok(manifest) => Some(manifest),
+        Err(e) => {
+            eprintln!("Warning: Failed to parse '{}': {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
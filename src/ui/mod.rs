@@ -1,54 +1,702 @@
+use crate::ui::config::{Config, SameStem};
 use crate::ui::consts::{
-    ELEMENTS, META_FILE, META_PATH, STYLE_EXTENSIONS, UI_ANNOTATION, UI_COMPILED_PATH,
+    builtin_elements, CLASSES_MODULE, CONFIG_PATH, ELEMENTS, IDS_MODULE, LANG_PATH, META_FILE,
+    META_PATH, STRINGS_MODULE, STYLE_EXTENSIONS, THEMES_MODULE, UI_ANNOTATION, UI_COMPILED_PATH,
     UI_EXTENSIONS, UI_PATH,
 };
+use crate::ui::lang::Locales;
+use crate::ui::meta::file::{ElementMeta, MetaError, MetaFile};
+use crate::ui::registry::Registry;
+use crate::ui::style::cascade::Styles;
+use crate::ui::style::tree::StyleSheet;
+use hashbrown::HashMap;
 use mvutils::utils::Recover;
-use std::fs::{create_dir_all, read_dir, remove_dir_all, File, OpenOptions, ReadDir};
+use std::fs::{create_dir_all, read_dir, File, OpenOptions, ReadDir};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 
+pub mod check;
+pub mod config;
 pub mod consts;
+pub mod header;
+pub mod inline;
+pub mod lang;
 pub mod linker;
+pub mod lint;
+pub mod lsp;
 pub mod meta;
+pub mod registry;
+pub mod rust;
 pub mod style;
 pub mod xml;
 
-pub fn compile() {
+pub struct CompileOptions {
+    /// Regenerate the meta file from the project sources when they have changed since the
+    /// last generation. Disabled with `--no-meta`, e.g. on CI where the committed file is used.
+    pub generate_meta: bool,
+    /// Additionally run rustfmt on the generated files, enabled with `--rustfmt`.
+    pub rustfmt: bool,
+    /// Print the module declarations that would be added to the project's own source files
+    /// instead of adding them, enabled with `--dry-run`.
+    pub dry_run: bool,
+    /// Overwrite generated files even if they were edited by hand, enabled with `--force`.
+    pub force: bool,
+    /// Only report UI files which aren't formatted instead of formatting them, enabled with
+    /// `mve fmt --check`.
+    pub check: bool,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        CompileOptions {
+            generate_meta: true,
+            rustfmt: false,
+            dry_run: false,
+            force: false,
+            check: false,
+        }
+    }
+}
+
+/// Compiles the UI files into Rust modules. Returns false if anything failed, e.g. a UI file
+/// used an id twice.
+pub fn compile(options: CompileOptions) -> bool {
     if let Ok(dir) = read_dir(UI_PATH) {
-        if let Ok(file) = OpenOptions::new().read(true).open(META_PATH) {
-            load_meta(file).expect(&format!("Failed to load {} file", META_FILE));
+        let config = match Config::load() {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{}", e);
+                return false;
+            }
+        };
+        if options.generate_meta && meta::is_outdated() {
+            match meta::generate_meta() {
+                Ok(meta) => register_elements(meta.elements),
+                Err(e) => {
+                    eprintln!("Failed to generate {} file: {}", META_FILE, e);
+                    return false;
+                }
+            }
+        } else if let Ok(file) = OpenOptions::new().read(true).open(META_PATH) {
+            if let Err(e) = load_meta(file) {
+                eprintln!("Failed to load {} file: {}", META_FILE, e);
+                return false;
+            }
         } else {
             println!("No {} file found, if you have custom ui elements, please ensure they are annotated with #[{}(tag)]", META_FILE, UI_ANNOTATION);
         }
         let mut styles = Vec::new();
         let mut uis = Vec::new();
-        process(dir, "".into(), &mut styles, &mut uis).expect("Failed to read assets UI directory");
+        if !find_files(dir, &mut styles, &mut uis) {
+            return false;
+        }
 
-        if uis.is_empty() {
-            return;
+        if uis.is_empty() && !Path::new(UI_COMPILED_PATH).exists() {
+            return true;
         }
 
-        if Path::new(UI_COMPILED_PATH).exists() {
-            if let Err(e) = remove_dir_all(UI_COMPILED_PATH) {
-                eprintln!("Error removing directory '{}': {}", UI_COMPILED_PATH, e);
-                return;
+        let mut outputs = match output_paths(&uis, config.modules.same_stem) {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                eprintln!("{}", e);
+                return false;
+            }
+        };
+
+        let Some(locales) = load_locales(&config) else {
+            return false;
+        };
+        let Some((trees, registry, styles)) = parse_all(&uis, &styles, locales.as_ref(), &config)
+        else {
+            return false;
+        };
+
+        let root = Path::new(UI_COMPILED_PATH);
+        let mut registry_outputs = if uis.is_empty() {
+            Vec::new()
+        } else {
+            vec![
+                (
+                    root.join(IDS_MODULE).with_extension("rs"),
+                    UI_PATH,
+                    registry.ids(),
+                ),
+                (
+                    root.join(CLASSES_MODULE).with_extension("rs"),
+                    UI_PATH,
+                    registry.classes(),
+                ),
+            ]
+        };
+        if let Some(locales) = &locales {
+            for code in locales.codes() {
+                let missing = locales.missing(code).len();
+                if missing > 0 {
+                    eprintln!(
+                        "Warning: Locale '{}' is missing {} strings, run `mve lang` for a report",
+                        code, missing
+                    );
+                }
+            }
+            registry_outputs.push((
+                root.join(STRINGS_MODULE).with_extension("rs"),
+                LANG_PATH,
+                locales.generate(),
+            ));
+        }
+        if let Some(themes) = styles.generate() {
+            registry_outputs.push((
+                root.join(THEMES_MODULE).with_extension("rs"),
+                UI_PATH,
+                themes,
+            ));
+        }
+        for (path, _, _) in &registry_outputs {
+            if let Some(i) = outputs.iter().position(|o| o == path) {
+                eprintln!(
+                    "UI file {} would replace the generated module {}, rename it",
+                    uis[i].0.display(),
+                    path.display()
+                );
+                return false;
             }
         }
 
-        //TODO: styles
+        let mut compiled = Vec::new();
+        for ((ui, _), (tree, output)) in uis.iter().zip(trees.into_iter().zip(&outputs)) {
+            let module = output.file_stem().unwrap_or_default().to_string_lossy();
+            match xml::codegen::generate(tree, &config.runtime.path, &module, &styles) {
+                Ok(code) => compiled.push(code),
+                Err(e) => eprintln!(
+                    "error: {}\n  --> {}:{}\n",
+                    e.message,
+                    ui.display(),
+                    e.position
+                ),
+            }
+        }
+        if compiled.len() < uis.len() {
+            return false;
+        }
+        outputs.extend(registry_outputs.iter().map(|(path, _, _)| path.clone()));
+
+        if !check_modified(options.force) {
+            return false;
+        }
+
+        if let Err(e) = linker::remove_stale(&outputs) {
+            eprintln!(
+                "Error removing stale files from '{}': {}",
+                UI_COMPILED_PATH, e
+            );
+            return false;
+        }
 
         let mut modules = Vec::new();
 
-        for (ui, new) in uis {
-            let mut output = Path::new(UI_COMPILED_PATH).join(new);
-            output.set_extension("rs");
-            modules.push(output.clone());
-            let err = format!("Error processing UI file '{:?}'", ui);
-            process_file(ui, output).expect(&err);
+        for (((ui, _), compiled), output) in uis.into_iter().zip(compiled).zip(outputs) {
+            modules.push((ui.clone(), output.clone()));
+            if let Err(e) = process_file(ui.clone(), &compiled, output) {
+                eprintln!("Error writing the module of UI file {:?}: {}", ui, e);
+                return false;
+            }
+        }
+        for (path, source, contents) in &registry_outputs {
+            if let Err(e) = std::fs::write(path, header::stamp(Path::new(source), contents)) {
+                eprintln!("Error writing {:?}: {}", path, e);
+                return false;
+            }
+        }
+
+        if let Err(e) = linker::generate_modules(&modules, options.dry_run) {
+            eprintln!("Failed to generate mod.rs files: {}", e);
+            return false;
+        }
+        let mut modules: Vec<_> = modules.into_iter().map(|(_, output)| output).collect();
+        modules.extend(registry_outputs.into_iter().map(|(path, _, _)| path));
+
+        if options.rustfmt {
+            linker::format(&modules);
+        }
+    }
+    true
+}
+
+/// Loads the locale files in `assets/lang`. Prints the errors and returns `None` if they
+/// couldn't be loaded.
+fn load_locales(config: &Config) -> Option<Option<Locales>> {
+    match Locales::load(config.lang.default.as_deref()) {
+        Ok(locales) => Some(locales),
+        Err(errors) => {
+            errors.iter().for_each(|e| eprintln!("{}", e));
+            None
+        }
+    }
+}
+
+/// Parses the UI files and the stylesheets, collecting their ids and classes and resolving the
+/// variables of the stylesheets. Prints the errors and returns `None` if a stylesheet couldn't
+/// be parsed or used an undefined variable, or a UI file used an id twice or a string missing in
+/// a locale.
+fn parse_all(
+    uis: &[(PathBuf, PathBuf)],
+    styles: &[PathBuf],
+    locales: Option<&Locales>,
+    config: &Config,
+) -> Option<(Vec<Vec<xml::tree::Node>>, Registry, Styles)> {
+    let mut registry = Registry::default();
+    let mut success = true;
+
+    let mut trees = Vec::new();
+    for (ui, _) in uis {
+        let contents = match std::fs::read_to_string(ui) {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("Error reading UI file {:?}: {}", ui, e);
+                success = false;
+                continue;
+            }
+        };
+        let tree = match xml::parse(&contents) {
+            Ok(tree) => tree,
+            Err(e) => {
+                eprintln!(
+                    "error: {}\n  --> {}:{}\n",
+                    e.message,
+                    ui.display(),
+                    e.position
+                );
+                success = false;
+                continue;
+            }
+        };
+        if let Err(errors) = registry.add_ui(ui, &tree) {
+            errors.iter().for_each(|e| eprintln!("{}", e));
+            success = false;
+        }
+        let errors = lang::check(locales, ui, &lang::references(&tree));
+        errors.iter().for_each(|e| eprintln!("{}", e));
+        success &= errors.is_empty();
+        trees.push(tree);
+    }
+
+    let sheets = match parse_styles(styles) {
+        Ok(sheets) => sheets,
+        Err(errors) => {
+            errors.iter().for_each(|e| eprintln!("{}", e));
+            return None;
+        }
+    };
+    for (file, sheet) in &sheets {
+        registry.add_style(sheet);
+        let errors = lang::check(locales, file, &lang::style_references(sheet));
+        errors.iter().for_each(|e| eprintln!("{}", e));
+        success &= errors.is_empty();
+    }
+    let styles = match Styles::load(&sheets, config.style.default_theme.as_deref()) {
+        Ok(styles) => styles,
+        Err(errors) => {
+            errors.iter().for_each(|e| eprintln!("{}", e));
+            return None;
+        }
+    };
+
+    success.then_some((trees, registry, styles))
+}
+
+/// Parses the stylesheets, failing with an error for every one which couldn't be parsed.
+fn parse_styles(files: &[PathBuf]) -> Result<Vec<(PathBuf, StyleSheet)>, Vec<String>> {
+    let mut sheets = Vec::new();
+    let mut errors = Vec::new();
+    for file in files {
+        let contents = match std::fs::read_to_string(file) {
+            Ok(contents) => contents,
+            Err(e) => {
+                errors.push(format!("Error reading style file {:?}: {}", file, e));
+                continue;
+            }
+        };
+        match style::parser::parse(&contents) {
+            Ok(sheet) => sheets.push((file.clone(), sheet)),
+            Err(e) => errors.push(format!(
+                "error: {}\n  --> {}:{}\n",
+                e.message,
+                file.display(),
+                e.position
+            )),
         }
+    }
+    if errors.is_empty() {
+        Ok(sheets)
+    } else {
+        Err(errors)
+    }
+}
 
-        linker::generate_modules(modules).expect("Failed to generate mod.rs files");
+/// Loads the stylesheets of the project in `dir`, for markup compiled outside of `mve build`.
+fn load_styles(dir: &Path, config: &Config) -> Result<Styles, Vec<String>> {
+    let mut files = Vec::new();
+    if let Ok(ui_dir) = read_dir(dir.join(UI_PATH)) {
+        process(ui_dir, "".into(), &mut files, &mut Vec::new())
+            .map_err(|e| vec![format!("Failed to read {}: {}", UI_PATH, e)])?;
     }
+    files.sort();
+    Styles::load(
+        &parse_styles(&files)?,
+        config.style.default_theme.as_deref(),
+    )
+}
+
+/// Compiles the UI files and runs `cargo check`, reporting errors in generated code at the UI
+/// markup they come from. Returns whether the check passed.
+pub fn check(options: CompileOptions) -> bool {
+    compile(options) && check::run()
+}
+
+/// Formats the UI files in place, or with `check` only reports the ones which aren't formatted.
+/// Returns false if a file couldn't be parsed or, when checking, isn't formatted.
+pub fn format(options: CompileOptions) -> bool {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+    let Ok(dir) = read_dir(UI_PATH) else {
+        return true;
+    };
+    let mut styles = Vec::new();
+    let mut uis = Vec::new();
+    if !find_files(dir, &mut styles, &mut uis) {
+        return false;
+    }
+
+    let mut success = true;
+    for (ui, _) in uis {
+        let contents = match std::fs::read_to_string(&ui) {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("Error reading UI file {:?}: {}", ui, e);
+                success = false;
+                continue;
+            }
+        };
+        let formatted = match xml::markup::parse(&contents) {
+            Ok(markup) => xml::format::format(&markup, &config.format),
+            Err(e) => {
+                eprintln!("{}:{}", ui.display(), e);
+                success = false;
+                continue;
+            }
+        };
+        if formatted == contents {
+            continue;
+        }
+        if options.check {
+            println!("{} is not formatted", ui.display());
+            success = false;
+        } else if let Err(e) = std::fs::write(&ui, formatted) {
+            eprintln!("Error writing UI file {:?}: {}", ui, e);
+            success = false;
+        }
+    }
+    success
+}
+
+/// Reports the strings missing in every locale, out of those of every locale and those the UI
+/// and style files refer to. Returns false if any are missing.
+pub fn lang() -> bool {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+    let Some(locales) = load_locales(&config) else {
+        return false;
+    };
+
+    let mut success = true;
+    if let Ok(dir) = read_dir(UI_PATH) {
+        let mut styles = Vec::new();
+        let mut uis = Vec::new();
+        if !find_files(dir, &mut styles, &mut uis) {
+            return false;
+        }
+        for (ui, _) in uis {
+            match std::fs::read_to_string(&ui) {
+                Ok(contents) => {
+                    let tree = match xml::parse(&contents) {
+                        Ok(tree) => tree,
+                        Err(e) => {
+                            eprintln!(
+                                "error: {}\n  --> {}:{}\n",
+                                e.message,
+                                ui.display(),
+                                e.position
+                            );
+                            success = false;
+                            continue;
+                        }
+                    };
+                    let errors = lang::check(locales.as_ref(), &ui, &lang::references(&tree));
+                    errors.iter().for_each(|e| eprintln!("{}", e));
+                    success &= errors.is_empty();
+                }
+                Err(e) => {
+                    eprintln!("Error reading UI file {:?}: {}", ui, e);
+                    success = false;
+                }
+            }
+        }
+        match parse_styles(&styles) {
+            Ok(sheets) => {
+                for (file, sheet) in &sheets {
+                    let errors =
+                        lang::check(locales.as_ref(), file, &lang::style_references(sheet));
+                    errors.iter().for_each(|e| eprintln!("{}", e));
+                    success &= errors.is_empty();
+                }
+            }
+            Err(errors) => {
+                errors.iter().for_each(|e| eprintln!("{}", e));
+                success = false;
+            }
+        }
+    }
+
+    let Some(locales) = locales else {
+        return success;
+    };
+    for code in locales.codes() {
+        let missing = locales.missing(code);
+        if missing.is_empty() {
+            println!("{}: complete", code);
+        } else {
+            println!("{}: {} missing", code, missing.len());
+            missing.iter().for_each(|key| println!("  {}", key));
+            success = false;
+        }
+    }
+    success
+}
+
+/// Runs a language server for UI and style files on stdin and stdout, using the elements
+/// from the meta file for completion, hover and go to definition.
+pub fn lsp() -> bool {
+    if let Ok(file) = OpenOptions::new().read(true).open(META_PATH) {
+        if let Err(e) = load_meta(file) {
+            eprintln!("Failed to load {} file: {}", META_FILE, e);
+        }
+    }
+    lsp::run()
+}
+
+/// Lints the UI files against the stylesheets with the rules configured in the project config.
+/// Returns false if a file couldn't be parsed or a rule at the error level was violated.
+pub fn lint() -> bool {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+    let Ok(dir) = read_dir(UI_PATH) else {
+        return true;
+    };
+    if let Ok(file) = OpenOptions::new().read(true).open(META_PATH) {
+        if let Err(e) = load_meta(file) {
+            eprintln!("Failed to load {} file: {}", META_FILE, e);
+            return false;
+        }
+    }
+    let mut styles = Vec::new();
+    let mut uis = Vec::new();
+    if !find_files(dir, &mut styles, &mut uis) {
+        return false;
+    }
+
+    let mut success = true;
+    let mut sheets = Vec::new();
+    for file in styles {
+        match std::fs::read_to_string(&file) {
+            Ok(contents) => match style::parser::parse(&contents) {
+                Ok(sheet) => sheets.push(lint::Style { file, sheet }),
+                Err(e) => {
+                    eprintln!(
+                        "error: {}\n  --> {}:{}\n",
+                        e.message,
+                        file.display(),
+                        e.position
+                    );
+                    success = false;
+                }
+            },
+            Err(e) => {
+                eprintln!("Error reading style file {:?}: {}", file, e);
+                success = false;
+            }
+        }
+    }
+
+    let uis: Vec<_> = uis.into_iter().map(|(ui, _)| ui).collect();
+    lint::lint(&uis, &sheets, &config.lint) && success
+}
+
+/// Removes the generated code and undoes the module declarations added by [`compile`].
+/// Returns false if generated files were edited by hand or anything couldn't be removed.
+pub fn clean(options: CompileOptions) -> bool {
+    if !check_modified(options.force) {
+        return false;
+    }
+    if let Err(e) = linker::clean(options.dry_run) {
+        eprintln!("Failed to clean generated code: {}", e);
+        return false;
+    }
+    true
+}
+
+/// Checks whether generated files were edited by hand since mve wrote them. Returns false if
+/// there are edited files which would be lost, unless `force` is set.
+fn check_modified(force: bool) -> bool {
+    let modified = match header::find_modified() {
+        Ok(modified) => modified,
+        Err(e) => {
+            eprintln!("Error reading generated files: {}", e);
+            return false;
+        }
+    };
+    if modified.is_empty() {
+        return true;
+    }
+
+    let files: Vec<_> = modified.iter().map(|f| format!("{:?}", f)).collect();
+    if force {
+        eprintln!(
+            "Warning: Overwriting generated files which were edited by hand: {}",
+            files.join(", ")
+        );
+        true
+    } else {
+        eprintln!(
+            "Generated files were edited by hand: {}\nMove the changes elsewhere, generated files \
+             are overwritten on every run, or pass --force to discard them",
+            files.join(", ")
+        );
+        false
+    }
+}
+
+/// Computes the output file of every UI file, mapping each path component to a valid module
+/// name. Fails with a description of every collision if multiple files or directories map to
+/// the same module. Files which only differ in their extension are handled according to
+/// `same_stem`.
+fn output_paths(uis: &[(PathBuf, PathBuf)], same_stem: SameStem) -> Result<Vec<PathBuf>, String> {
+    let mut stems: HashMap<PathBuf, Vec<&PathBuf>> = HashMap::new();
+    for (ui, new) in uis {
+        stems.entry(new.with_extension("")).or_default().push(ui);
+    }
+
+    if same_stem == SameStem::Error {
+        let mut errors = Vec::new();
+        let mut shared: Vec<_> = stems.values().filter(|files| files.len() > 1).collect();
+        shared.sort();
+        for files in shared {
+            let files: Vec<_> = files.iter().map(|f| format!("{:?}", f)).collect();
+            errors.push(format!(
+                "UI files {} only differ in their extension, rename one of them or set \
+                 `same_stem = \"extension\"` in the [modules] section of {}",
+                files.join(" and "),
+                CONFIG_PATH
+            ));
+        }
+        if !errors.is_empty() {
+            return Err(errors.join("\n"));
+        }
+    }
+
+    let mut outputs = Vec::with_capacity(uis.len());
+    let mut sources: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+    let mut order = Vec::new();
+
+    for (ui, new) in uis {
+        let mut stem = new.file_stem().unwrap_or_default().to_string_lossy();
+        if same_stem == SameStem::Extension && stems[&new.with_extension("")].len() > 1 {
+            let extension = new.extension().unwrap_or_default().to_string_lossy();
+            stem = format!("{}_{}", stem, extension).into();
+        }
+        let mut output = PathBuf::new();
+        let mut source = PathBuf::from(UI_PATH);
+
+        let components: Vec<_> = new.parent().into_iter().flat_map(Path::iter).collect();
+        for (i, component) in components
+            .iter()
+            .map(|c| c.to_string_lossy())
+            .chain([stem])
+            .enumerate()
+        {
+            let Some(name) = linker::module_name(&component) else {
+                return Err(format!(
+                    "Name '{}' of UI file {:?} can't be turned into a module name",
+                    component, ui
+                ));
+            };
+            output.push(name);
+            let source = if i < components.len() {
+                source.push(components[i]);
+                source.clone()
+            } else {
+                ui.clone()
+            };
+            let entry = sources.entry(output.clone()).or_default();
+            if entry.is_empty() {
+                order.push(output.clone());
+            }
+            if !entry.contains(&source) {
+                entry.push(source);
+            }
+        }
+
+        output.set_extension("rs");
+        outputs.push(Path::new(UI_COMPILED_PATH).join(output));
+    }
+
+    let collisions: Vec<_> = order
+        .into_iter()
+        .filter_map(|module| {
+            let sources = &sources[&module];
+            (sources.len() > 1).then(|| {
+                let module: Vec<_> = module.iter().map(|c| c.to_string_lossy()).collect();
+                let sources: Vec<_> = sources.iter().map(|s| format!("{:?}", s)).collect();
+                format!(
+                    "Module '{}' would be generated from multiple sources: {}",
+                    module.join("::"),
+                    sources.join(", ")
+                )
+            })
+        })
+        .collect();
+
+    if collisions.is_empty() {
+        Ok(outputs)
+    } else {
+        Err(collisions.join("\n"))
+    }
+}
+
+/// Finds the stylesheets and UI files in the UI directory, sorted. Prints the error and returns
+/// false if the directory couldn't be read.
+fn find_files(dir: ReadDir, styles: &mut Vec<PathBuf>, uis: &mut Vec<(PathBuf, PathBuf)>) -> bool {
+    if let Err(e) = process(dir, "".into(), styles, uis) {
+        eprintln!("Failed to read {} directory: {}", UI_PATH, e);
+        return false;
+    }
+    styles.sort();
+    uis.sort();
+    true
 }
 
 fn process(
@@ -62,7 +710,7 @@ fn process(
         let meta = entry.metadata()?;
 
         let new_path = path.join(entry.file_name());
-        let mut file_name = entry.file_name().into_string().unwrap();
+        let file_name = entry.file_name().into_string().unwrap();
         let file = UI_EXTENSIONS.iter().any(|e| file_name.ends_with(e));
         let style = STYLE_EXTENSIONS.iter().any(|e| file_name.ends_with(e));
 
@@ -77,12 +725,8 @@ fn process(
     Ok(())
 }
 
-fn process_file(file_path: PathBuf, output: PathBuf) -> Result<(), std::io::Error> {
-    let mut file = File::open(file_path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-
-    let compiled_contents = xml::compile(contents);
+fn process_file(file_path: PathBuf, compiled: &str, output: PathBuf) -> Result<(), std::io::Error> {
+    let compiled_contents = header::stamp(&file_path, compiled);
 
     if let Some(parent) = output.parent() {
         create_dir_all(parent)?;
@@ -98,23 +742,107 @@ fn process_file(file_path: PathBuf, output: PathBuf) -> Result<(), std::io::Erro
     Ok(())
 }
 
-fn load_meta(mut file: File) -> Result<(), std::io::Error> {
+fn load_meta(mut file: File) -> Result<(), MetaError> {
     let mut contents = String::new();
     file.read_to_string(&mut contents)?;
 
-    contents
-        .split(';')
-        .filter(|item| !item.is_empty())
-        .map(|pair| {
-            pair.split_once('=')
-                .expect(&format!("Failed to parse {} file", META_FILE))
-        })
-        .for_each(|(tag, path)| {
-            ELEMENTS
-                .write()
-                .recover()
-                .push((tag.trim().to_string(), path.trim().to_string()))
-        });
+    let meta = MetaFile::parse(&contents)?;
+    if meta.is_legacy() {
+        eprintln!(
+            "{} uses the legacy 'tag=path;' format, regenerate it to migrate to version {}",
+            META_FILE,
+            crate::ui::consts::META_VERSION
+        );
+    } else if meta.version < crate::ui::consts::META_VERSION {
+        eprintln!(
+            "{} was generated by an older version of mve, regenerate it so the element paths \
+             start at the crate root",
+            META_FILE
+        );
+    }
+
+    meta.validate(&builtin_elements())?;
+    register_elements(meta.elements);
 
     Ok(())
-}
\ No newline at end of file
+}
+
+fn register_elements(meta: Vec<ElementMeta>) {
+    let mut elements = ELEMENTS.write().recover();
+    for element in meta {
+        if element.overrides {
+            elements.retain(|e| e.tag != element.tag);
+        }
+        elements.push(element);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ui(path: &str) -> (PathBuf, PathBuf) {
+        (Path::new(UI_PATH).join(path), PathBuf::from(path))
+    }
+
+    #[test]
+    fn output_paths_are_module_names() {
+        let outputs = output_paths(
+            &[ui("Menus/MainMenu.ui"), ui("2d/type.xml")],
+            SameStem::Error,
+        )
+        .unwrap();
+        let compiled = Path::new(UI_COMPILED_PATH);
+        assert_eq!(
+            outputs,
+            [
+                compiled.join("menus/main_menu.rs"),
+                compiled.join("_2d/type.rs")
+            ]
+        );
+    }
+
+    #[test]
+    fn module_collisions() {
+        let error = output_paths(
+            &[ui("main-menu.ui"), ui("MainMenu.ui"), ui("a.ui")],
+            SameStem::Error,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            format!(
+                "Module 'main_menu' would be generated from multiple sources: {:?}, {:?}",
+                Path::new(UI_PATH).join("main-menu.ui"),
+                Path::new(UI_PATH).join("MainMenu.ui")
+            )
+        );
+        assert!(output_paths(&[ui("a/b.ui"), ui("A/c.ui")], SameStem::Error)
+            .unwrap_err()
+            .starts_with("Module 'a' would be generated from multiple sources"));
+        assert!(output_paths(&[ui("--.ui")], SameStem::Error).is_err());
+    }
+
+    #[test]
+    fn same_stem() {
+        let uis = [ui("menu.ui"), ui("menu.xml"), ui("other/menu.ui")];
+        let error = output_paths(&uis, SameStem::Error).unwrap_err();
+        assert!(error.starts_with(&format!(
+            "UI files {:?} and {:?} only differ in their extension",
+            Path::new(UI_PATH).join("menu.ui"),
+            Path::new(UI_PATH).join("menu.xml")
+        )));
+        assert_eq!(error.lines().count(), 1);
+
+        let outputs = output_paths(&uis, SameStem::Extension).unwrap();
+        let compiled = Path::new(UI_COMPILED_PATH);
+        assert_eq!(
+            outputs,
+            [
+                compiled.join("menu_ui.rs"),
+                compiled.join("menu_xml.rs"),
+                compiled.join("other/menu.rs")
+            ]
+        );
+    }
+}
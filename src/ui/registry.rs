@@ -0,0 +1,186 @@
+use crate::ui::linker::module_name;
+use crate::ui::rust::{self, Expr, Ident, Item, Path, Type};
+use crate::ui::style::tree::StyleSheet;
+use crate::ui::xml::lexer::Position;
+use crate::ui::xml::tree::{Child, Node};
+use hashbrown::HashMap;
+use std::collections::BTreeSet;
+use std::path::Path as FilePath;
+
+/// Every id and class used by the UI and style files, to generate constants for them so typos
+/// in lookups are caught by the compiler.
+#[derive(Default)]
+pub struct Registry {
+    ids: BTreeSet<String>,
+    classes: BTreeSet<String>,
+}
+
+impl Registry {
+    /// Adds the ids and classes of a UI file. Returns an error for every id used more than
+    /// once in the file.
+    pub fn add_ui(&mut self, file: &FilePath, tree: &[Node]) -> Result<(), Vec<String>> {
+        let mut ids = HashMap::new();
+        let mut errors = Vec::new();
+        for node in tree {
+            self.add_node(node, &mut ids, &mut |id, first, second| {
+                errors.push(format!(
+                    "error: Id '{}' is used more than once\n  --> {}:{}\n  --> {}:{}\n",
+                    id,
+                    file.display(),
+                    first,
+                    file.display(),
+                    second
+                ))
+            });
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn add_node<'a>(
+        &mut self,
+        node: &'a Node,
+        ids: &mut HashMap<&'a str, Position>,
+        duplicate: &mut impl FnMut(&str, Position, Position),
+    ) {
+        let children = match node {
+            Node::Named(named) => {
+                if let Some(position) = named.id_position {
+                    match ids.get(named.id.as_str()) {
+                        Some(first) => duplicate(&named.id, *first, position),
+                        None => {
+                            ids.insert(&named.id, position);
+                        }
+                    }
+                    self.ids.insert(named.id.clone());
+                }
+                self.classes.extend(named.class.iter().cloned());
+                &named.children
+            }
+            Node::Unnamed(unnamed) => &unnamed.children,
+        };
+        for child in children {
+            if let Child::Node(child) = child {
+                self.add_node(child, ids, duplicate);
+            }
+        }
+    }
+
+    /// Adds the ids and classes the selectors of a stylesheet refer to.
+    pub fn add_style(&mut self, sheet: &StyleSheet) {
+        for compound in sheet
+            .rules
+            .iter()
+            .flat_map(|r| &r.selectors)
+            .flat_map(|s| &s.compounds)
+        {
+            self.ids.extend(compound.id.iter().cloned());
+            self.classes.extend(compound.classes.iter().cloned());
+        }
+    }
+
+    /// The module with a constant for every id, like `pub const START_BUTTON: &str = "startButton";`.
+    pub fn ids(&self) -> String {
+        constants(&self.ids, "id")
+    }
+
+    /// The module with a constant for every class.
+    pub fn classes(&self) -> String {
+        constants(&self.classes, "class")
+    }
+}
+
+/// Names every value in SCREAMING_SNAKE_CASE. Values which give no usable name, or the same
+/// name as an earlier value, are skipped with a warning.
+fn constants(values: &BTreeSet<String>, kind: &str) -> String {
+    let mut names: HashMap<String, &String> = HashMap::new();
+    let mut items = Vec::new();
+    for value in values {
+        let Some(name) = module_name(value).map(|n| n.to_uppercase()) else {
+            eprintln!(
+                "No constant is generated for {kind} '{value}': it contains no usable characters"
+            );
+            continue;
+        };
+        if let Some(other) = names.get(&name) {
+            eprintln!(
+                "No constant is generated for {kind} '{value}': the name {name} is taken by '{other}'"
+            );
+            continue;
+        }
+        names.insert(name.clone(), value);
+        items.push(Item::Const {
+            name: Ident::new(&name),
+            ty: Type::Ref(Box::new(Type::Path(Path::parse("str")))),
+            value: Expr::Str(value.clone()),
+        });
+    }
+    rust::print_items(&items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::style::parser;
+    use crate::ui::xml;
+
+    fn tree(ui: &str) -> Vec<Node> {
+        xml::parse(ui).unwrap()
+    }
+
+    #[test]
+    fn constants() {
+        let mut registry = Registry::default();
+        let ui = "<element id=\"startButton\" class=\"big wide\"><label class=\"big\" id=\"1\"/></element>";
+        assert!(registry.add_ui(FilePath::new("menu.ui"), &tree(ui)).is_ok());
+        registry.add_style(&parser::parse("#title .wide, label.big-text { width: 1; }").unwrap());
+
+        assert_eq!(
+            registry.ids(),
+            "pub const _1: &str = \"1\";\n\
+             pub const START_BUTTON: &str = \"startButton\";\n\
+             pub const TITLE: &str = \"title\";\n"
+        );
+        assert_eq!(
+            registry.classes(),
+            "pub const BIG: &str = \"big\";\n\
+             pub const BIG_TEXT: &str = \"big-text\";\n\
+             pub const WIDE: &str = \"wide\";\n"
+        );
+    }
+
+    #[test]
+    fn colliding_names_are_skipped() {
+        let mut registry = Registry::default();
+        let ui = "<element class=\"big-text big_text -\"/>";
+        assert!(registry.add_ui(FilePath::new("menu.ui"), &tree(ui)).is_ok());
+        assert_eq!(
+            registry.classes(),
+            "pub const BIG_TEXT: &str = \"big-text\";\n"
+        );
+    }
+
+    #[test]
+    fn duplicate_ids() {
+        let mut registry = Registry::default();
+        let ui = "<element id=\"a\">\n    <label id=\"a\"/>\n    <label id=\"b\"/><label id=\"b\"/>\n</element>";
+        let errors = registry
+            .add_ui(FilePath::new("menu.ui"), &tree(ui))
+            .unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                "error: Id 'a' is used more than once\n  --> menu.ui:1:10\n  --> menu.ui:2:12\n",
+                "error: Id 'b' is used more than once\n  --> menu.ui:3:12\n  --> menu.ui:3:27\n",
+            ]
+        );
+
+        // Ids may repeat across files.
+        assert!(registry
+            .add_ui(FilePath::new("other.ui"), &tree("<label id=\"a\"/>"))
+            .is_ok());
+    }
+}
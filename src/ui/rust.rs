@@ -0,0 +1,1009 @@
+use crate::ui::consts::{NON_RAW_KEYWORDS, RUST_KEYWORDS, SOURCE_MARKER};
+use crate::ui::xml::lexer::Position;
+use std::fmt::Display;
+
+/// An identifier, made raw if it is a keyword. Names which can't be identifiers at all are
+/// rejected when constructing it, so generated code is always valid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ident(String);
+
+impl Ident {
+    /// An identifier for a name known to be valid, panicking if it isn't one. Names from UI files
+    /// or stylesheets go through [`Ident::parse`] instead.
+    pub fn new(name: &str) -> Self {
+        Ident::parse(name).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        let plain = name.strip_prefix("r#").unwrap_or(name);
+        let mut chars = plain.chars();
+        let valid = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && chars.all(|c| c.is_alphanumeric() || c == '_')
+            && plain != "_";
+        if !valid {
+            return Err(format!("'{name}' is not a valid identifier"));
+        }
+        if NON_RAW_KEYWORDS.contains(&plain) {
+            return Err(format!("'{name}' can't be used as an identifier"));
+        }
+        if RUST_KEYWORDS.contains(&plain) {
+            Ok(Ident(format!("r#{plain}")))
+        } else {
+            Ok(Ident(plain.to_string()))
+        }
+    }
+
+    /// `_`, for ignored bindings and anonymous imports.
+    pub fn placeholder() -> Self {
+        Ident("_".to_string())
+    }
+}
+
+impl Display for Ident {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A path like `crate::ui::Label`, `::mvcore` or `Slider::<_, _>::new`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    global: bool,
+    /// The leading `crate`, `self` or `super` segments, which aren't identifiers.
+    prefix: Vec<&'static str>,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Segment {
+    ident: Ident,
+    /// How many generic arguments are left to inference.
+    inferred: usize,
+}
+
+impl Path {
+    /// Parses a path written as `a::b::c`, panicking if it isn't one. Paths from `ui.meta` files
+    /// go through [`Path::try_parse`] instead.
+    pub fn parse(path: &str) -> Self {
+        Path::try_parse(path).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_parse(path: &str) -> Result<Self, String> {
+        let invalid = || format!("'{path}' is not a valid path");
+        let mut res = Path {
+            global: false,
+            prefix: Vec::new(),
+            segments: Vec::new(),
+        };
+        let mut parts = path.split("::").peekable();
+        if parts.peek() == Some(&"") && path.len() > 2 {
+            res.global = true;
+            parts.next();
+        }
+        for part in parts {
+            let part = part.trim();
+            match NON_RAW_KEYWORDS.iter().find(|k| **k == part) {
+                Some(keyword) if res.segments.is_empty() && !res.global => res.prefix.push(keyword),
+                _ => res.segments.push(Segment {
+                    ident: Ident::parse(part).map_err(|_| invalid())?,
+                    inferred: 0,
+                }),
+            }
+        }
+        if res.segments.is_empty() && res.prefix.is_empty() {
+            return Err(invalid());
+        }
+        Ok(res)
+    }
+
+    /// The path with another segment appended.
+    pub fn join(mut self, name: &str) -> Self {
+        self.segments.push(Segment {
+            ident: Ident::new(name),
+            inferred: 0,
+        });
+        self
+    }
+
+    /// The path with `_` for the given number of generic arguments of its last segment, like
+    /// `Slider::<_, _>`.
+    pub fn inferred(mut self, count: usize) -> Self {
+        if let Some(last) = self.segments.last_mut() {
+            last.inferred = count;
+        }
+        self
+    }
+
+    /// A path consisting of just the identifier.
+    pub fn of(ident: Ident) -> Self {
+        Path {
+            global: false,
+            prefix: Vec::new(),
+            segments: vec![Segment { ident, inferred: 0 }],
+        }
+    }
+
+    fn is_ident(&self, ident: &Ident) -> bool {
+        !self.global
+            && self.prefix.is_empty()
+            && self.segments
+                == [Segment {
+                    ident: ident.clone(),
+                    inferred: 0,
+                }]
+    }
+
+    /// Makes the path absolute by prefixing `::`, unless it already starts at the crate root,
+    /// is relative to the current module, or starts with one of the given local names.
+    pub fn absolute(mut self, local: &[&str]) -> Self {
+        let local = self
+            .segments
+            .first()
+            .is_some_and(|s| local.contains(&s.ident.0.as_str()));
+        if self.prefix.is_empty() && !local {
+            self.global = true;
+        }
+        self
+    }
+}
+
+pub enum Item {
+    Use {
+        path: Path,
+        alias: Option<Ident>,
+        /// Whether to allow the import being unused.
+        allow_unused: bool,
+    },
+    Fn {
+        public: bool,
+        docs: Option<String>,
+        name: Ident,
+        params: Vec<(Ident, Type)>,
+        ret: Type,
+        body: Block,
+    },
+    /// A struct with public named fields.
+    Struct {
+        name: Ident,
+        fields: Vec<(Ident, Type)>,
+    },
+    Impl {
+        ty: Ident,
+        items: Vec<Item>,
+    },
+    /// A public constant.
+    Const {
+        name: Ident,
+        ty: Type,
+        value: Expr,
+    },
+    /// A private static.
+    Static {
+        name: Ident,
+        ty: Type,
+        value: Expr,
+    },
+    /// A public enum without fields which can be copied and compared, with an optional doc
+    /// comment for every variant.
+    Enum {
+        name: Ident,
+        variants: Vec<(Ident, Option<String>)>,
+    },
+}
+
+pub enum Type {
+    Path(Path),
+    /// A path with generic arguments, like `Handle<Button>`.
+    Generic(Path, Vec<Type>),
+    /// `impl Trait`
+    Impl(Path),
+    /// `dyn Trait`
+    Dyn(Path),
+    Tuple(Vec<Type>),
+    Ref(Box<Type>),
+    /// `&'static T`
+    StaticRef(Box<Type>),
+    Array(Box<Type>, usize),
+}
+
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    pub tail: Option<Box<Expr>>,
+}
+
+pub struct Stmt {
+    pub kind: StmtKind,
+    /// The position in the UI file the statement was generated from, printed as a marker.
+    pub position: Option<Position>,
+    /// Conditions like `cfg(feature = "x")`, printed as attributes the statement is compiled
+    /// under.
+    pub cfg: Vec<String>,
+}
+
+pub enum StmtKind {
+    /// A `let` binding, which is initialized later if it has no value.
+    Let {
+        pattern: Pattern,
+        value: Option<Expr>,
+    },
+    Assign(Expr, Expr),
+    Expr(Expr),
+    /// An item scoped to the block, like an import.
+    Item(Item),
+}
+
+pub enum Pattern {
+    Ident {
+        name: Ident,
+        mutable: bool,
+    },
+    Tuple(Vec<Ident>),
+    /// A unit or tuple variant, like `None` or `Some(i)`.
+    Variant(Path, Vec<Ident>),
+}
+
+pub enum Expr {
+    Path(Path),
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Call(Box<Expr>, Vec<Expr>),
+    Method(Box<Expr>, Ident, Vec<Expr>),
+    Field(Box<Expr>, Ident),
+    Block(Block),
+    Tuple(Vec<Expr>),
+    /// A struct expression, where fields initialized from a variable of the same name are
+    /// printed in shorthand.
+    Struct(Path, Vec<(Ident, Expr)>),
+    Ref(Box<Expr>),
+    /// An array, printed with one element per line if the elements are arrays themselves.
+    Array(Vec<Expr>),
+    Index(Box<Expr>, Box<Expr>),
+    /// A closure with the parameters, like `|a| a.len()`.
+    Closure(Vec<Ident>, Box<Expr>),
+    /// `a == b`
+    Eq(Box<Expr>, Box<Expr>),
+    Deref(Box<Expr>),
+    /// `value as T`
+    Cast(Box<Expr>, Type),
+    Match(Box<Expr>, Vec<(Pattern, Expr)>),
+}
+
+impl Expr {
+    pub fn path(path: &str) -> Self {
+        Expr::Path(Path::parse(path))
+    }
+
+    /// Calls the `new` function of the type at the path.
+    pub fn new(path: Path, args: Vec<Expr>) -> Self {
+        Expr::Path(path.join("new")).call(args)
+    }
+
+    pub fn call(self, args: Vec<Expr>) -> Self {
+        Expr::Call(Box::new(self), args)
+    }
+
+    pub fn method(self, name: &str, args: Vec<Expr>) -> Self {
+        Expr::Method(Box::new(self), Ident::new(name), args)
+    }
+
+    pub fn field(self, name: &str) -> Self {
+        Expr::Field(Box::new(self), Ident::new(name))
+    }
+
+    pub fn index(self, index: Expr) -> Self {
+        Expr::Index(Box::new(self), Box::new(index))
+    }
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind) -> Self {
+        Stmt {
+            kind,
+            position: None,
+            cfg: Vec::new(),
+        }
+    }
+
+    pub fn at(mut self, position: Position) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    pub fn cfg(mut self, cfg: Vec<String>) -> Self {
+        self.cfg = cfg;
+        self
+    }
+
+    pub fn let_(name: &str, value: Expr) -> Self {
+        Stmt::new(StmtKind::Let {
+            pattern: Pattern::Ident {
+                name: Ident::new(name),
+                mutable: false,
+            },
+            value: Some(value),
+        })
+    }
+
+    pub fn let_mut(name: &str, value: Expr) -> Self {
+        Stmt::new(StmtKind::Let {
+            pattern: Pattern::Ident {
+                name: Ident::new(name),
+                mutable: true,
+            },
+            value: Some(value),
+        })
+    }
+
+    /// Declares a variable which is assigned later.
+    pub fn declare(name: Ident) -> Self {
+        Stmt::new(StmtKind::Let {
+            pattern: Pattern::Ident {
+                name,
+                mutable: false,
+            },
+            value: None,
+        })
+    }
+
+    pub fn let_tuple(names: Vec<Ident>, value: Expr) -> Self {
+        Stmt::new(StmtKind::Let {
+            pattern: Pattern::Tuple(names),
+            value: Some(value),
+        })
+    }
+
+    pub fn assign(target: Expr, value: Expr) -> Self {
+        Stmt::new(StmtKind::Assign(target, value))
+    }
+
+    pub fn expr(expr: Expr) -> Self {
+        Stmt::new(StmtKind::Expr(expr))
+    }
+
+    pub fn item(item: Item) -> Self {
+        Stmt::new(StmtKind::Item(item))
+    }
+}
+
+/// The items selecting one of the names in the constant `list` at runtime: a static holding the
+/// index of the selected name, `set_<noun>(<param>)` selecting a name and returning whether it
+/// exists, and `<noun>()` returning the selected name. The first name is selected at first.
+/// `docs` are the doc comments of the two functions. `AtomicUsize` and `Ordering` of
+/// `std::sync::atomic` have to be imported.
+pub fn selection(list: &str, noun: &str, param: &str, docs: [&str; 2]) -> Vec<Item> {
+    let selected = noun.to_uppercase();
+    let str_type = || Type::Path(Path::parse("str"));
+    let relaxed = || Expr::path("Ordering::Relaxed");
+    let found = Expr::path(list).method("iter", vec![]).method(
+        "position",
+        vec![Expr::Closure(
+            vec![Ident::new(noun)],
+            Box::new(Expr::Eq(
+                Box::new(Expr::Deref(Box::new(Expr::path(noun)))),
+                Box::new(Expr::path(param)),
+            )),
+        )],
+    );
+    let store = Expr::path(&selected).method("store", vec![Expr::path("i"), relaxed()]);
+    let select = Expr::Match(
+        Box::new(found),
+        vec![
+            (
+                Pattern::Variant(Path::parse("Some"), vec![Ident::new("i")]),
+                Expr::Block(Block {
+                    stmts: vec![Stmt::expr(store)],
+                    tail: Some(Box::new(Expr::Bool(true))),
+                }),
+            ),
+            (
+                Pattern::Variant(Path::parse("None"), Vec::new()),
+                Expr::Bool(false),
+            ),
+        ],
+    );
+    vec![
+        Item::Static {
+            name: Ident::new(&selected),
+            ty: Type::Path(Path::parse("AtomicUsize")),
+            value: Expr::new(Path::parse("AtomicUsize"), vec![Expr::Int(0)]),
+        },
+        Item::Fn {
+            public: true,
+            docs: Some(docs[0].to_string()),
+            name: Ident::new(&format!("set_{noun}")),
+            params: vec![(Ident::new(param), Type::Ref(Box::new(str_type())))],
+            ret: Type::Path(Path::parse("bool")),
+            body: Block {
+                stmts: Vec::new(),
+                tail: Some(Box::new(select)),
+            },
+        },
+        Item::Fn {
+            public: true,
+            docs: Some(docs[1].to_string()),
+            name: Ident::new(noun),
+            params: Vec::new(),
+            ret: Type::StaticRef(Box::new(str_type())),
+            body: Block {
+                stmts: Vec::new(),
+                tail: Some(Box::new(
+                    Expr::path(list).index(Expr::path(&selected).method("load", vec![relaxed()])),
+                )),
+            },
+        },
+    ]
+}
+
+/// Prints items one after another, with blank lines around everything but consecutive imports
+/// or constants.
+pub fn print_items(items: &[Item]) -> String {
+    let mut printer = Printer::default();
+    printer.items(items);
+    printer.out.push('\n');
+    printer.out
+}
+
+/// Prints an expression, as the expansion of a macro for example.
+pub fn print_expr(expr: &Expr) -> String {
+    let mut printer = Printer::default();
+    printer.expr(expr);
+    printer.out.push('\n');
+    printer.out
+}
+
+/// Prints the tree with four spaces of indentation per block. Every statement gets its own
+/// line, and nested blocks are opened on the line they appear in.
+#[derive(Default)]
+struct Printer {
+    out: String,
+    depth: usize,
+}
+
+impl Printer {
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.out.push_str(&"    ".repeat(self.depth));
+    }
+
+    fn items(&mut self, items: &[Item]) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                let compact = |item: &Item| matches!(item, Item::Use { .. } | Item::Const { .. });
+                if !compact(item)
+                    || std::mem::discriminant(item) != std::mem::discriminant(&items[i - 1])
+                {
+                    self.out.push('\n');
+                }
+                self.newline();
+            }
+            self.item(item);
+        }
+    }
+
+    fn item(&mut self, item: &Item) {
+        match item {
+            Item::Use {
+                path,
+                alias,
+                allow_unused,
+            } => {
+                if *allow_unused {
+                    self.out.push_str("#[allow(unused_imports)]");
+                    self.newline();
+                }
+                self.out.push_str("use ");
+                self.path(path);
+                if let Some(alias) = alias {
+                    self.out.push_str(" as ");
+                    self.out.push_str(&alias.0);
+                }
+                self.out.push(';');
+            }
+            Item::Fn {
+                public,
+                docs,
+                name,
+                params,
+                ret,
+                body,
+            } => {
+                for line in docs.iter().flat_map(|docs| docs.lines()) {
+                    self.out.push_str(format!("/// {line}").trim_end());
+                    self.newline();
+                }
+                if *public {
+                    self.out.push_str("pub ");
+                }
+                self.out.push_str(&format!("fn {}(", name.0));
+                for (i, (name, ty)) in params.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.out.push_str(&format!("{}: ", name.0));
+                    self.ty(ty);
+                }
+                self.out.push_str(") -> ");
+                self.ty(ret);
+                self.out.push(' ');
+                self.block(body);
+            }
+            Item::Struct { name, fields } => {
+                self.out.push_str(&format!("pub struct {} {{", name.0));
+                self.depth += 1;
+                for (name, ty) in fields {
+                    self.newline();
+                    self.out.push_str(&format!("pub {}: ", name.0));
+                    self.ty(ty);
+                    self.out.push(',');
+                }
+                self.depth -= 1;
+                self.newline();
+                self.out.push('}');
+            }
+            Item::Const { name, ty, value } => {
+                self.out.push_str(&format!("pub const {}: ", name.0));
+                self.ty(ty);
+                self.out.push_str(" = ");
+                self.expr(value);
+                self.out.push(';');
+            }
+            Item::Static { name, ty, value } => {
+                self.out.push_str(&format!("static {}: ", name.0));
+                self.ty(ty);
+                self.out.push_str(" = ");
+                self.expr(value);
+                self.out.push(';');
+            }
+            Item::Enum { name, variants } => {
+                self.out
+                    .push_str("#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]");
+                self.newline();
+                self.out.push_str(&format!("pub enum {} {{", name.0));
+                self.depth += 1;
+                for (name, doc) in variants {
+                    if let Some(doc) = doc {
+                        for line in doc.lines() {
+                            self.newline();
+                            self.out.push_str(format!("/// {line}").trim_end());
+                        }
+                    }
+                    self.newline();
+                    self.out.push_str(&format!("{},", name.0));
+                }
+                self.depth -= 1;
+                self.newline();
+                self.out.push('}');
+            }
+            Item::Impl { ty, items } => {
+                self.out.push_str(&format!("impl {} {{", ty.0));
+                self.depth += 1;
+                self.newline();
+                self.items(items);
+                self.depth -= 1;
+                self.newline();
+                self.out.push('}');
+            }
+        }
+    }
+
+    fn ty(&mut self, ty: &Type) {
+        match ty {
+            Type::Path(path) => self.path(path),
+            Type::Generic(path, args) => {
+                self.path(path);
+                self.out.push('<');
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.ty(arg);
+                }
+                self.out.push('>');
+            }
+            Type::Impl(path) => {
+                self.out.push_str("impl ");
+                self.path(path);
+            }
+            Type::Dyn(path) => {
+                self.out.push_str("dyn ");
+                self.path(path);
+            }
+            Type::Ref(ty) => {
+                self.out.push('&');
+                self.ty(ty);
+            }
+            Type::StaticRef(ty) => {
+                self.out.push_str("&'static ");
+                self.ty(ty);
+            }
+            Type::Tuple(types) => {
+                self.out.push('(');
+                for (i, ty) in types.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.ty(ty);
+                }
+                if types.len() == 1 {
+                    self.out.push(',');
+                }
+                self.out.push(')');
+            }
+            Type::Array(ty, len) => {
+                self.out.push('[');
+                self.ty(ty);
+                self.out.push_str(&format!("; {len}]"));
+            }
+        }
+    }
+
+    fn path(&mut self, path: &Path) {
+        if path.global {
+            self.out.push_str("::");
+        }
+        let prefix = path.prefix.iter().map(|p| p.to_string());
+        let segments = path.segments.iter().map(|s| match s.inferred {
+            0 => s.ident.0.clone(),
+            n => format!("{}::<{}>", s.ident.0, vec!["_"; n].join(", ")),
+        });
+        self.out
+            .push_str(&prefix.chain(segments).collect::<Vec<_>>().join("::"));
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.out.push('{');
+        self.depth += 1;
+        for stmt in &block.stmts {
+            self.newline();
+            self.stmt(stmt);
+        }
+        if let Some(tail) = &block.tail {
+            self.newline();
+            self.expr(tail);
+        }
+        self.depth -= 1;
+        self.newline();
+        self.out.push('}');
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        for cfg in &stmt.cfg {
+            self.out.push_str(&format!("#[{cfg}]"));
+            self.newline();
+        }
+        match &stmt.kind {
+            StmtKind::Let { pattern, value } => {
+                self.out.push_str("let ");
+                self.pattern(pattern);
+                if let Some(value) = value {
+                    self.out.push_str(" = ");
+                    self.expr(value);
+                }
+            }
+            StmtKind::Assign(target, value) => {
+                self.expr(target);
+                self.out.push_str(" = ");
+                self.expr(value);
+            }
+            StmtKind::Expr(expr) => self.expr(expr),
+            StmtKind::Item(item) => return self.item(item),
+        }
+        self.out.push(';');
+        if let Some(position) = stmt.position {
+            self.out.push_str(&format!(" {SOURCE_MARKER}{position}"));
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        let names = |names: &[Ident]| {
+            let names: Vec<&str> = names.iter().map(|n| n.0.as_str()).collect();
+            format!("({})", names.join(", "))
+        };
+        match pattern {
+            Pattern::Ident { name, mutable } => {
+                if *mutable {
+                    self.out.push_str("mut ");
+                }
+                self.out.push_str(&name.0);
+            }
+            Pattern::Tuple(fields) => self.out.push_str(&names(fields)),
+            Pattern::Variant(path, fields) => {
+                self.path(path);
+                if !fields.is_empty() {
+                    self.out.push_str(&names(fields));
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Path(path) => self.path(path),
+            Expr::Str(s) => self.out.push_str(&format!("{s:?}")),
+            Expr::Int(i) => self.out.push_str(&i.to_string()),
+            Expr::Float(f) => self.out.push_str(&format!("{f:?}")),
+            Expr::Bool(b) => self.out.push_str(&b.to_string()),
+            Expr::Call(function, args) => {
+                self.receiver(function);
+                self.args(args);
+            }
+            Expr::Method(receiver, name, args) => {
+                self.receiver(receiver);
+                self.out.push('.');
+                self.out.push_str(&name.0);
+                self.args(args);
+            }
+            Expr::Field(receiver, name) => {
+                self.receiver(receiver);
+                self.out.push('.');
+                self.out.push_str(&name.0);
+            }
+            Expr::Block(block) => self.block(block),
+            Expr::Tuple(exprs) => {
+                self.out.push('(');
+                for (i, expr) in exprs.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr(expr);
+                }
+                if exprs.len() == 1 {
+                    self.out.push(',');
+                }
+                self.out.push(')');
+            }
+            Expr::Struct(path, fields) => {
+                self.path(path);
+                self.out.push_str(" {");
+                self.depth += 1;
+                for (name, value) in fields {
+                    self.newline();
+                    self.out.push_str(&name.0);
+                    let shorthand = matches!(value, Expr::Path(path) if path.is_ident(name));
+                    if !shorthand {
+                        self.out.push_str(": ");
+                        self.expr(value);
+                    }
+                    self.out.push(',');
+                }
+                self.depth -= 1;
+                self.newline();
+                self.out.push('}');
+            }
+            Expr::Ref(expr) => {
+                self.out.push('&');
+                self.expr(expr);
+            }
+            Expr::Array(exprs) => {
+                self.out.push('[');
+                if exprs.iter().any(|e| matches!(e, Expr::Array(_))) {
+                    self.depth += 1;
+                    for expr in exprs {
+                        self.newline();
+                        self.expr(expr);
+                        self.out.push(',');
+                    }
+                    self.depth -= 1;
+                    self.newline();
+                } else {
+                    for (i, expr) in exprs.iter().enumerate() {
+                        if i > 0 {
+                            self.out.push_str(", ");
+                        }
+                        self.expr(expr);
+                    }
+                }
+                self.out.push(']');
+            }
+            Expr::Index(expr, index) => {
+                self.receiver(expr);
+                self.out.push('[');
+                self.expr(index);
+                self.out.push(']');
+            }
+            Expr::Closure(params, body) => {
+                let params: Vec<&str> = params.iter().map(|p| p.0.as_str()).collect();
+                self.out.push_str(&format!("|{}| ", params.join(", ")));
+                self.expr(body);
+            }
+            Expr::Eq(left, right) => {
+                self.expr(left);
+                self.out.push_str(" == ");
+                self.expr(right);
+            }
+            Expr::Deref(expr) => {
+                self.out.push('*');
+                self.receiver(expr);
+            }
+            Expr::Cast(expr, ty) => {
+                self.receiver(expr);
+                self.out.push_str(" as ");
+                self.ty(ty);
+            }
+            Expr::Match(scrutinee, arms) => {
+                self.out.push_str("match ");
+                self.expr(scrutinee);
+                self.out.push_str(" {");
+                self.depth += 1;
+                for (pattern, value) in arms {
+                    self.newline();
+                    self.pattern(pattern);
+                    self.out.push_str(" => ");
+                    self.expr(value);
+                    if !matches!(value, Expr::Block(_)) {
+                        self.out.push(',');
+                    }
+                }
+                self.depth -= 1;
+                self.newline();
+                self.out.push('}');
+            }
+        }
+    }
+
+    /// Prints an expression something is called or accessed on, which needs parentheses if it
+    /// is a negative number or an operation.
+    fn receiver(&mut self, expr: &Expr) {
+        let parenthesized = match expr {
+            Expr::Int(i) => *i < 0,
+            Expr::Float(f) => f.is_sign_negative(),
+            Expr::Closure(..)
+            | Expr::Eq(..)
+            | Expr::Deref(_)
+            | Expr::Cast(..)
+            | Expr::Match(..) => true,
+            _ => false,
+        };
+        if parenthesized {
+            self.out.push('(');
+            self.expr(expr);
+            self.out.push(')');
+        } else {
+            self.expr(expr);
+        }
+    }
+
+    fn args(&mut self, args: &[Expr]) {
+        self.out.push('(');
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(arg);
+        }
+        self.out.push(')');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(path: &str, local: &[&str]) -> String {
+        print_expr(&Expr::Path(Path::parse(path).absolute(local)))
+            .trim_end()
+            .to_string()
+    }
+
+    #[test]
+    fn absolute_paths() {
+        assert_eq!(path("engine::core", &[]), "::engine::core");
+        assert_eq!(path("mvcore", &[]), "::mvcore");
+        assert_eq!(path("::mvcore", &[]), "::mvcore");
+        assert_eq!(
+            path("crate::widgets::Slider", &[]),
+            "crate::widgets::Slider"
+        );
+        assert_eq!(path("super::Slider", &[]), "super::Slider");
+        assert_eq!(
+            path("runtime::ui::Label", &["runtime"]),
+            "runtime::ui::Label"
+        );
+        assert_eq!(path("type::r#fn", &[]), "::r#type::r#fn");
+    }
+
+    #[test]
+    fn invalid_paths() {
+        for path in [
+            "",
+            "::",
+            "ui::",
+            "ui::Label<T>",
+            "ui::crate::Label",
+            "ui-kit::Label",
+        ] {
+            assert_eq!(
+                Path::try_parse(path),
+                Err(format!("'{path}' is not a valid path")),
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn prints_blocks() {
+        let block = Block {
+            stmts: vec![
+                Stmt::let_mut("a", Expr::new(Path::parse("S").inferred(2), vec![])),
+                Stmt::expr(
+                    Expr::path("a").method("set", vec![Expr::Int(-1).method("abs", vec![])]),
+                )
+                .at(Position { line: 3, column: 7 }),
+                Stmt::assign(Expr::path("a").field("text"), Expr::Str("{ \"".to_string())),
+            ],
+            tail: Some(Box::new(Expr::path("a"))),
+        };
+        let items = [
+            Item::Use {
+                path: Path::parse("x::Y"),
+                alias: None,
+                allow_unused: true,
+            },
+            Item::Fn {
+                public: true,
+                docs: Some("Builds the element.\n".to_string()),
+                name: Ident::new("generate"),
+                params: vec![
+                    (Ident::new("a"), Type::Path(Path::parse("u8"))),
+                    (Ident::new("type"), Type::Path(Path::parse("bool"))),
+                ],
+                ret: Type::Impl(Path::parse("Y")),
+                body: block,
+            },
+        ];
+        assert_eq!(
+            print_items(&items),
+            format!(
+                "#[allow(unused_imports)]\nuse x::Y;\n\n/// Builds the element.\n\
+                 pub fn generate(a: u8, r#type: bool) -> impl Y {{\n    \
+                 let mut a = S::<_, _>::new();\n    \
+                 a.set((-1).abs()); {SOURCE_MARKER}3:7\n    \
+                 a.text = \"{{ \\\"\";\n    \
+                 a\n}}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn idents() {
+        assert_eq!(Ident::parse("width").unwrap().to_string(), "width");
+        assert_eq!(Ident::parse("type").unwrap().to_string(), "r#type");
+        assert_eq!(Ident::parse("r#match").unwrap().to_string(), "r#match");
+        for name in ["self", "super", "crate", "Self", "_", "", "1a", "a-b"] {
+            assert!(Ident::parse(name).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn selection_items() {
+        let items = selection(
+            "COLORS",
+            "color",
+            "name",
+            ["Selects it.", "The selected one."],
+        );
+        assert_eq!(
+            print_items(&items),
+            "static COLOR: AtomicUsize = AtomicUsize::new(0);
+
+/// Selects it.
+pub fn set_color(name: &str) -> bool {
+    match COLORS.iter().position(|color| *color == name) {
+        Some(i) => {
+            COLOR.store(i, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// The selected one.
+pub fn color() -> &'static str {
+    COLORS[COLOR.load(Ordering::Relaxed)]
+}
+"
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use crate::ui::rust::{self, Block, Expr, Ident, Item, Path, Type};
+use crate::ui::style::tree::{Selector, StyleSheet, Target, Variable};
+use std::path::PathBuf;
+
+/// How deeply variables may refer to other variables, to stop at cycles.
+const MAX_DEPTH: usize = 32;
+/// The name of the theme of the styles outside of `@theme` blocks, if none is configured.
+const DEFAULT_THEME: &str = "default";
+/// The docs of the generated functions selecting the theme and returning the selected one.
+const SELECTION_DOCS: [&str; 2] = [
+    "Selects the theme by its name, like `\"dark\"`. Elements built afterwards are styled for it, so\n\
+     the UI has to be built again to switch. Returns false if there is no such theme.",
+    "The name of the selected theme.",
+];
+
+/// The rules of every stylesheet with their variables resolved in every theme, to find the
+/// properties of an element.
+#[derive(Default)]
+pub struct Styles {
+    /// The themes of the `@theme` blocks, after the default one. The default theme is made of
+    /// the rules and variables outside of `@theme` blocks if no block declares it.
+    themes: Vec<String>,
+    /// The rules in the order they were written.
+    rules: Vec<StyleRule>,
+}
+
+struct StyleRule {
+    selectors: Vec<Selector>,
+    /// The value of every declaration in every theme, `None` in the themes the rule isn't for.
+    declarations: Vec<(String, Vec<Option<String>>)>,
+}
+
+impl Styles {
+    /// Resolves the variables of the stylesheets, given in the order their rules apply in.
+    /// Fails with every value using an undefined variable. If there are themes, the default one
+    /// is named `default` unless another name is given.
+    pub fn load(
+        sheets: &[(PathBuf, StyleSheet)],
+        default: Option<&str>,
+    ) -> Result<Self, Vec<String>> {
+        let mut errors = Vec::new();
+        let mut themes: Vec<String> = sheets
+            .iter()
+            .flat_map(|(_, sheet)| {
+                let rules = sheet.rules.iter().map(|r| &r.theme);
+                rules.chain(sheet.variables.iter().map(|v| &v.theme))
+            })
+            .flatten()
+            .cloned()
+            .collect();
+        themes.sort();
+        themes.dedup();
+        if !themes.is_empty() {
+            let default = default.unwrap_or(DEFAULT_THEME);
+            match themes.iter().position(|theme| theme == default) {
+                Some(i) => {
+                    let theme = themes.remove(i);
+                    themes.insert(0, theme);
+                }
+                None => themes.insert(0, default.to_string()),
+            }
+        }
+
+        let variables = Variables {
+            themes: &themes,
+            variables: sheets.iter().flat_map(|(_, s)| &s.variables).collect(),
+        };
+        let slots = themes.len().max(1);
+        let mut rules = Vec::new();
+        for (file, sheet) in sheets {
+            for rule in &sheet.rules {
+                let theme = rule
+                    .theme
+                    .as_ref()
+                    .and_then(|name| themes.iter().position(|t| t == name));
+                let mut declarations = Vec::new();
+                for declaration in &rule.declarations {
+                    let mut values = vec![None; slots];
+                    for (slot, value) in values.iter_mut().enumerate() {
+                        if theme.is_some_and(|theme| theme != slot) {
+                            continue;
+                        }
+                        match variables.resolve(&declaration.value, slot, 0) {
+                            Ok(resolved) => *value = Some(resolved),
+                            Err(e) => {
+                                errors.push(format!(
+                                    "error: {}\n  --> {}:{}\n",
+                                    e,
+                                    file.display(),
+                                    declaration.position
+                                ));
+                                break;
+                            }
+                        }
+                    }
+                    declarations.push((declaration.name.clone(), values));
+                }
+                rules.push(StyleRule {
+                    selectors: rule.selectors.clone(),
+                    declarations,
+                });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(Styles { themes, rules })
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The properties the rules matching the element set, with their value in every theme or
+    /// just one value if there are no themes. Rules with a more specific selector win, then
+    /// later ones.
+    pub fn properties(
+        &self,
+        target: &Target,
+        ancestors: &[Target],
+    ) -> Vec<(&str, Vec<Option<&str>>)> {
+        let mut matching: Vec<_> = self
+            .rules
+            .iter()
+            .filter_map(|rule| {
+                rule.selectors
+                    .iter()
+                    .filter(|s| s.matches(target, ancestors))
+                    .map(Selector::specificity)
+                    .max()
+                    .map(|specificity| (specificity, rule))
+            })
+            .collect();
+        matching.sort_by_key(|(specificity, _)| *specificity);
+
+        let mut res: Vec<(&str, Vec<Option<&str>>)> = Vec::new();
+        for (_, rule) in matching {
+            for (name, values) in &rule.declarations {
+                let i = match res.iter().position(|(n, _)| n == name) {
+                    Some(i) => i,
+                    None => {
+                        res.push((name, vec![None; values.len()]));
+                        res.len() - 1
+                    }
+                };
+                for (slot, value) in values.iter().enumerate() {
+                    if let Some(value) = value {
+                        res[i].1[slot] = Some(value);
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    /// The `themes` module, with the name of every theme and functions to select one, or `None`
+    /// if no theme is declared.
+    pub fn generate(&self) -> Option<String> {
+        if self.themes.is_empty() {
+            return None;
+        }
+        let mut items = vec![
+            Item::Use {
+                path: Path::parse("std::sync::atomic::AtomicUsize"),
+                alias: None,
+                allow_unused: false,
+            },
+            Item::Use {
+                path: Path::parse("std::sync::atomic::Ordering"),
+                alias: None,
+                allow_unused: false,
+            },
+            Item::Const {
+                name: Ident::new("THEMES"),
+                ty: Type::Array(
+                    Box::new(Type::Ref(Box::new(Type::Path(Path::parse("str"))))),
+                    self.themes.len(),
+                ),
+                value: Expr::Array(self.themes.iter().map(|t| Expr::Str(t.clone())).collect()),
+            },
+        ];
+        items.extend(rust::selection("THEMES", "theme", "name", SELECTION_DOCS));
+        items.push(Item::Fn {
+            public: true,
+            docs: Some(
+                "The index of the selected theme in `THEMES`, which generated styles are looked \
+                 up by."
+                    .to_string(),
+            ),
+            name: Ident::new("index"),
+            params: Vec::new(),
+            ret: Type::Path(Path::parse("usize")),
+            body: Block {
+                stmts: Vec::new(),
+                tail: Some(Box::new(
+                    Expr::path("THEME").method("load", vec![Expr::path("Ordering::Relaxed")]),
+                )),
+            },
+        });
+        Some(rust::print_items(&items))
+    }
+}
+
+struct Variables<'a> {
+    themes: &'a [String],
+    variables: Vec<&'a Variable>,
+}
+
+impl Variables<'_> {
+    /// The variable in the theme, or outside of any theme. Later definitions replace earlier
+    /// ones.
+    fn get(&self, name: &str, slot: usize) -> Option<&Variable> {
+        let theme = self.themes.get(slot);
+        let find = |theme: Option<&String>| {
+            self.variables
+                .iter()
+                .rev()
+                .find(|v| v.name == name && v.theme.as_ref() == theme)
+        };
+        find(theme).or_else(|| find(None)).copied()
+    }
+
+    /// Replaces every `var(--name)` in the value, including those in the values of variables.
+    fn resolve(&self, value: &str, slot: usize, depth: usize) -> Result<String, String> {
+        let mut res = String::new();
+        let mut rest = value;
+        while let Some(start) = rest.find("var(") {
+            res.push_str(&rest[..start]);
+            let arguments = &rest[start + 4..];
+            let Some(end) = arguments.find(')') else {
+                return Err("Expected ')' after 'var('".to_string());
+            };
+            let name = arguments[..end].trim();
+            let Some(variable) = self.get(name, slot) else {
+                return Err(match self.themes.get(slot) {
+                    Some(theme) => format!("Variable '{name}' is not defined in theme '{theme}'"),
+                    None => format!("Variable '{name}' is not defined"),
+                });
+            };
+            if depth == MAX_DEPTH {
+                return Err(format!("Variable '{name}' refers to itself"));
+            }
+            res.push_str(&self.resolve(&variable.value, slot, depth + 1)?);
+            rest = &arguments[end + 1..];
+        }
+        res.push_str(rest);
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::style::parser;
+
+    const STYLE: &str = "
+--accent: #f80;
+label { color: var(--accent); }
+
+@theme dark {
+    --accent: #08f;
+}
+";
+
+    fn colors(default: Option<&str>) -> (Vec<String>, Vec<Option<String>>) {
+        let sheets = [("a.style".into(), parser::parse(STYLE).unwrap())];
+        let styles = Styles::load(&sheets, default).unwrap();
+        let target = Target {
+            tag: "label",
+            id: "",
+            classes: &[],
+        };
+        let properties = styles.properties(&target, &[]);
+        let values = properties[0]
+            .1
+            .iter()
+            .map(|v| v.map(String::from))
+            .collect();
+        (styles.themes, values)
+    }
+
+    #[test]
+    fn unthemed_styles_are_the_default_theme() {
+        let (themes, values) = colors(None);
+        assert_eq!(themes, ["default", "dark"]);
+        assert_eq!(values, [Some("#f80".to_string()), Some("#08f".to_string())]);
+
+        let (themes, values) = colors(Some("light"));
+        assert_eq!(themes, ["light", "dark"]);
+        assert_eq!(values[0].as_deref(), Some("#f80"));
+
+        let (themes, values) = colors(Some("dark"));
+        assert_eq!(themes, ["dark"]);
+        assert_eq!(values, [Some("#08f".to_string())]);
+    }
+
+    #[test]
+    fn no_themes() {
+        let sheets = [(
+            "a.style".into(),
+            parser::parse("label { width: 5; }").unwrap(),
+        )];
+        let styles = Styles::load(&sheets, Some("light")).unwrap();
+        assert!(styles.themes.is_empty());
+        assert!(styles.generate().is_none());
+    }
+}
@@ -0,0 +1,746 @@
+use crate::ui::style::parser::{is_ident_char, StyleError};
+use crate::ui::style::tree::{Compound, Declaration, Rule, Selector, StyleSheet, Variable};
+use crate::ui::xml::lexer::Position;
+use hashbrown::HashMap;
+
+/// How deeply mixins may include other mixins, to stop at cycles.
+const MAX_INCLUDE_DEPTH: usize = 32;
+
+/// A stylesheet item as written, before nested rules, mixins and `$variables` are flattened.
+pub enum Item {
+    /// `$gap: 4px;`, usable after it in its block and the blocks nested in it.
+    Variable(Property),
+    /// `--accent: #f80;`, which is looked up by theme and kept as is.
+    ThemeVariable(Property),
+    Declaration(Property),
+    Rule {
+        selectors: Vec<NestedSelector>,
+        items: Vec<Item>,
+        position: Position,
+    },
+    Theme {
+        name: String,
+        items: Vec<Item>,
+        position: Position,
+    },
+    Mixin(Mixin),
+    /// `@include name(argument, ...);`, with the position of every argument.
+    Include {
+        name: String,
+        args: Vec<(String, Position)>,
+        position: Position,
+    },
+}
+
+/// `@mixin name($param, ...) { ... }`, whose items are inserted wherever it is included.
+pub struct Mixin {
+    pub name: String,
+    /// The names of the parameters, including the `$`.
+    pub params: Vec<String>,
+    pub items: Vec<Item>,
+    pub position: Position,
+}
+
+pub struct Property {
+    pub name: String,
+    pub value: String,
+    pub position: Position,
+    pub value_position: Position,
+}
+
+/// A selector of a rule which may be nested in another one. Nested selectors match descendants
+/// of the outer ones, unless they start with `&`: then their first compound is added to the last
+/// one of the outer selector if it is attached to the `&`, as in `&.active`.
+pub struct NestedSelector {
+    pub parent: bool,
+    pub attached: bool,
+    pub selector: Selector,
+}
+
+/// Flattens the items into plain rules. Declarations of nested rules and included mixins get
+/// rules of their own, with the positions they were written at, and `$variables` and `calc()`
+/// in values are replaced by their values.
+pub fn flatten(items: Vec<Item>) -> Result<StyleSheet, StyleError> {
+    let mut mixins = HashMap::new();
+    let mut rest = Vec::new();
+    for item in items {
+        match item {
+            Item::Mixin(mixin) => {
+                if mixins.contains_key(&mixin.name) {
+                    return Err(StyleError {
+                        message: format!("Mixin '{}' is declared twice", mixin.name),
+                        position: mixin.position,
+                    });
+                }
+                mixins.insert(mixin.name.clone(), mixin);
+            }
+            item => rest.push(item),
+        }
+    }
+
+    let mut flattener = Flattener {
+        mixins: &mixins,
+        sheet: StyleSheet {
+            rules: Vec::new(),
+            variables: Vec::new(),
+        },
+        scopes: vec![HashMap::new()],
+        includes: 0,
+    };
+    flattener.items(
+        &rest,
+        &Context {
+            theme: None,
+            rule: None,
+        },
+    )?;
+    Ok(flattener.sheet)
+}
+
+struct Flattener<'a> {
+    mixins: &'a HashMap<String, Mixin>,
+    sheet: StyleSheet,
+    /// The `$variables` of every block from the stylesheet down to the current one.
+    scopes: Vec<HashMap<String, String>>,
+    /// How many mixins are being included in each other.
+    includes: usize,
+}
+
+/// Where items are flattened into.
+struct Context {
+    theme: Option<String>,
+    /// The index of the rule declarations are added to, and its selectors.
+    rule: Option<(usize, Vec<Selector>)>,
+}
+
+impl Flattener<'_> {
+    fn items(&mut self, items: &[Item], context: &Context) -> Result<(), StyleError> {
+        for item in items {
+            match item {
+                Item::Variable(property) => {
+                    let value = self.value(&property.value, property.value_position)?;
+                    let scope = self.scopes.last_mut().expect("There is always a scope");
+                    scope.insert(property.name.clone(), value);
+                }
+                Item::ThemeVariable(property) => {
+                    if context.rule.is_some() {
+                        return Err(StyleError {
+                            message: format!(
+                                "Variable '{}' has to be declared outside of rules",
+                                property.name
+                            ),
+                            position: property.position,
+                        });
+                    }
+                    let value = self.value(&property.value, property.value_position)?;
+                    self.sheet.variables.push(Variable {
+                        name: property.name.clone(),
+                        value,
+                        theme: context.theme.clone(),
+                        position: property.position,
+                    });
+                }
+                Item::Declaration(property) => {
+                    let Some((rule, _)) = &context.rule else {
+                        return Err(StyleError {
+                            message: format!("Declaration '{}' has to be in a rule", property.name),
+                            position: property.position,
+                        });
+                    };
+                    let value = self.value(&property.value, property.value_position)?;
+                    self.sheet.rules[*rule].declarations.push(Declaration {
+                        name: property.name.clone(),
+                        value,
+                        position: property.position,
+                    });
+                }
+                Item::Rule {
+                    selectors,
+                    items,
+                    position,
+                } => {
+                    let parents = context.rule.as_ref().map(|(_, s)| s.as_slice());
+                    let selectors = combine(parents, selectors, *position)?;
+                    self.sheet.rules.push(Rule {
+                        selectors: selectors.clone(),
+                        declarations: Vec::new(),
+                        position: *position,
+                        theme: context.theme.clone(),
+                    });
+                    let context = Context {
+                        theme: context.theme.clone(),
+                        rule: Some((self.sheet.rules.len() - 1, selectors)),
+                    };
+                    self.scoped(|f| f.items(items, &context))?;
+                }
+                Item::Theme {
+                    name,
+                    items,
+                    position,
+                } => {
+                    if context.theme.is_some() || context.rule.is_some() {
+                        return Err(StyleError {
+                            message: "@theme blocks have to be at the top level".to_string(),
+                            position: *position,
+                        });
+                    }
+                    let context = Context {
+                        theme: Some(name.clone()),
+                        rule: None,
+                    };
+                    self.scoped(|f| f.items(items, &context))?;
+                }
+                Item::Mixin(mixin) => {
+                    return Err(StyleError {
+                        message: "Mixins have to be declared at the top level".to_string(),
+                        position: mixin.position,
+                    })
+                }
+                Item::Include {
+                    name,
+                    args,
+                    position,
+                } => self.include(name, args, *position, context)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `f` with a new scope for variables.
+    fn scoped(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<(), StyleError>,
+    ) -> Result<(), StyleError> {
+        self.scopes.push(HashMap::new());
+        let res = f(self);
+        self.scopes.pop();
+        res
+    }
+
+    /// Flattens the items of a mixin into the rule. The mixin sees the top level variables and
+    /// its parameters, errors in it are reported where it is written and where it is included.
+    fn include(
+        &mut self,
+        name: &str,
+        args: &[(String, Position)],
+        position: Position,
+        context: &Context,
+    ) -> Result<(), StyleError> {
+        let error = |message: String| Err(StyleError { message, position });
+        if context.rule.is_none() {
+            return error(format!("@include {} has to be in a rule", name));
+        }
+        let Some(mixin) = self.mixins.get(name) else {
+            return error(format!("Mixin '{}' is not declared", name));
+        };
+        if args.len() != mixin.params.len() {
+            return error(format!(
+                "Mixin '{}' takes {} arguments but {} were given",
+                name,
+                mixin.params.len(),
+                args.len()
+            ));
+        }
+        if self.includes == MAX_INCLUDE_DEPTH {
+            return error(format!("Mixin '{}' includes itself", name));
+        }
+
+        let mut params = HashMap::new();
+        for (param, (arg, position)) in mixin.params.iter().zip(args) {
+            params.insert(param.clone(), self.value(arg, *position)?);
+        }
+        let global = self.scopes[0].clone();
+        let scopes = std::mem::replace(&mut self.scopes, vec![global, params]);
+        self.includes += 1;
+        let res = self.items(&mixin.items, context);
+        self.includes -= 1;
+        self.scopes = scopes;
+        // Only the outermost include is noted, which is in a rule of the stylesheet.
+        res.map_err(|mut e| {
+            if self.includes == 0 {
+                e.message = format!("{} (included at {})", e.message, position);
+            }
+            e
+        })
+    }
+
+    fn variable(&self, name: &str) -> Option<&String> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// Replaces the `$variables` in the value and evaluates `calc()` expressions.
+    fn value(&self, value: &str, position: Position) -> Result<String, StyleError> {
+        let chars: Vec<char> = value.chars().collect();
+        let mut res = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            if chars[i..].starts_with(&['c', 'a', 'l', 'c', '(']) {
+                let start = i;
+                let mut depth = 0;
+                let end = (i + 4..chars.len()).find(|j| {
+                    match chars[*j] {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                });
+                let Some(end) = end else {
+                    return Err(StyleError {
+                        message: "Unclosed 'calc('".to_string(),
+                        position: advance(position, &chars[..start]),
+                    });
+                };
+                res.push_str(&self.calc(&chars, start + 5, end, position)?);
+                i = end + 1;
+            } else if chars[i] == '$' {
+                let name: String = chars[i + 1..]
+                    .iter()
+                    .take_while(|c| is_ident_char(**c))
+                    .collect();
+                let name = format!("${name}");
+                let Some(value) = self.variable(&name) else {
+                    return Err(StyleError {
+                        message: format!("Variable '{}' is not defined", name),
+                        position: advance(position, &chars[..i]),
+                    });
+                };
+                res.push_str(value);
+                i += name.chars().count();
+            } else {
+                res.push(chars[i]);
+                i += 1;
+            }
+        }
+        Ok(res)
+    }
+
+    /// Evaluates the expression of a `calc()` from `start` to `end` in the value, made of
+    /// numbers with an optional unit, `$variables` holding such numbers, `+`, `-`, `*`, `/` and
+    /// parentheses.
+    fn calc(
+        &self,
+        chars: &[char],
+        start: usize,
+        end: usize,
+        position: Position,
+    ) -> Result<String, StyleError> {
+        let error = |message: String, i: usize| StyleError {
+            message,
+            position: advance(position, &chars[..i]),
+        };
+        let mut tokens = Vec::new();
+        let mut i = start;
+        while i < end {
+            let c = chars[i];
+            if c.is_whitespace() {
+                i += 1;
+            } else if "+-*/()".contains(c) {
+                tokens.push((Token::Symbol(c), i));
+                i += 1;
+            } else if c == '$' || c.is_ascii_digit() || c == '.' {
+                let len = chars[i + 1..end]
+                    .iter()
+                    .take_while(|c| is_ident_char(**c) || **c == '.' || **c == '%')
+                    .count()
+                    + 1;
+                let text: String = chars[i..i + len].iter().collect();
+                let number = if c == '$' {
+                    let value = self
+                        .variable(&text)
+                        .ok_or_else(|| error(format!("Variable '{}' is not defined", text), i))?;
+                    Number::parse(value).ok_or_else(|| {
+                        error(
+                            format!("Variable '{}' is '{}', not a number", text, value),
+                            i,
+                        )
+                    })?
+                } else {
+                    Number::parse(&text)
+                        .ok_or_else(|| error(format!("'{}' is not a number", text), i))?
+                };
+                tokens.push((Token::Number(number), i));
+                i += len;
+            } else {
+                return Err(error(format!("Unexpected '{}' in calc()", c), i));
+            }
+        }
+
+        let mut calc = Calc {
+            tokens,
+            index: 0,
+            end,
+        };
+        let result = calc.sum().map_err(|(message, i)| error(message, i))?;
+        if let Some((_, i)) = calc.tokens.get(calc.index) {
+            return Err(error("Expected an operator".to_string(), *i));
+        }
+        Ok(result.to_string())
+    }
+}
+
+/// Combines the selectors of a rule with those of the rule it is nested in.
+fn combine(
+    parents: Option<&[Selector]>,
+    selectors: &[NestedSelector],
+    position: Position,
+) -> Result<Vec<Selector>, StyleError> {
+    let Some(parents) = parents else {
+        if selectors.iter().any(|s| s.parent) {
+            return Err(StyleError {
+                message: "'&' can only be used in nested rules".to_string(),
+                position,
+            });
+        }
+        return Ok(selectors.iter().map(|s| s.selector.clone()).collect());
+    };
+
+    let mut res = Vec::new();
+    for parent in parents {
+        for nested in selectors {
+            let mut compounds = parent.compounds.clone();
+            let mut rest = nested.selector.compounds.iter();
+            if nested.attached {
+                if let (Some(last), Some(first)) = (compounds.last_mut(), rest.next()) {
+                    merge(last, first);
+                }
+            }
+            compounds.extend(rest.cloned());
+            res.push(Selector { compounds });
+        }
+    }
+    Ok(res)
+}
+
+fn merge(compound: &mut Compound, other: &Compound) {
+    if other.tag.is_some() {
+        compound.tag.clone_from(&other.tag);
+    }
+    if other.id.is_some() {
+        compound.id.clone_from(&other.id);
+    }
+    compound.classes.extend(other.classes.iter().cloned());
+}
+
+/// The position of the character following the given ones, which start at `position`.
+fn advance(mut position: Position, chars: &[char]) -> Position {
+    for c in chars {
+        if *c == '\n' {
+            position.line += 1;
+            position.column = 1;
+        } else {
+            position.column += 1;
+        }
+    }
+    position
+}
+
+/// A number with an optional unit like `px` or `%`.
+#[derive(Clone)]
+struct Number {
+    value: f64,
+    unit: String,
+}
+
+impl Number {
+    fn parse(text: &str) -> Option<Number> {
+        let text = text.trim();
+        let split = text
+            .find(|c: char| !c.is_ascii_digit() && c != '.' && c != '-')
+            .unwrap_or(text.len());
+        let (value, unit) = text.split_at(split);
+        let valid_unit = unit == "%" || unit.chars().all(|c| c.is_ascii_alphabetic());
+        Some(Number {
+            value: value.parse().ok()?,
+            unit: unit.to_string(),
+        })
+        .filter(|_| valid_unit)
+    }
+}
+
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Rounded to hide floating point noise like 0.30000000000000004.
+        let value = format!("{:.6}", self.value);
+        let value = value.trim_end_matches('0').trim_end_matches('.');
+        let value = if value == "-0" { "0" } else { value };
+        write!(f, "{}{}", value, self.unit)
+    }
+}
+
+#[derive(Clone)]
+enum Token {
+    Number(Number),
+    Symbol(char),
+}
+
+/// Evaluates the tokens of a `calc()` expression by precedence. Errors carry the index in the
+/// value they are at.
+struct Calc {
+    tokens: Vec<(Token, usize)>,
+    index: usize,
+    /// The index of the closing parenthesis, where errors at the end are reported.
+    end: usize,
+}
+
+type CalcResult = Result<Number, (String, usize)>;
+
+impl Calc {
+    fn symbol(&self) -> Option<(char, usize)> {
+        match self.tokens.get(self.index) {
+            Some((Token::Symbol(c), i)) => Some((*c, *i)),
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> CalcResult {
+        let mut left = self.product()?;
+        while let Some((op @ ('+' | '-'), i)) = self.symbol() {
+            self.index += 1;
+            let right = self.product()?;
+            if left.unit != right.unit {
+                return Err((
+                    format!("Can't combine '{}' and '{}' with '{}'", left, right, op),
+                    i,
+                ));
+            }
+            left.value += if op == '+' { right.value } else { -right.value };
+        }
+        Ok(left)
+    }
+
+    fn product(&mut self) -> CalcResult {
+        let mut left = self.factor()?;
+        while let Some((op @ ('*' | '/'), i)) = self.symbol() {
+            self.index += 1;
+            let right = self.factor()?;
+            left = if op == '*' {
+                if !left.unit.is_empty() && !right.unit.is_empty() {
+                    return Err((format!("Can't multiply '{}' by '{}'", left, right), i));
+                }
+                Number {
+                    value: left.value * right.value,
+                    unit: if left.unit.is_empty() {
+                        right.unit
+                    } else {
+                        left.unit
+                    },
+                }
+            } else {
+                if right.value == 0.0 {
+                    return Err(("Division by zero".to_string(), i));
+                }
+                let unit = if right.unit.is_empty() {
+                    left.unit
+                } else if right.unit == left.unit {
+                    String::new()
+                } else {
+                    return Err((format!("Can't divide '{}' by '{}'", left, right), i));
+                };
+                Number {
+                    value: left.value / right.value,
+                    unit,
+                }
+            };
+        }
+        Ok(left)
+    }
+
+    fn factor(&mut self) -> CalcResult {
+        let Some((token, i)) = self.tokens.get(self.index).cloned() else {
+            return Err(("Expected a number".to_string(), self.end));
+        };
+        self.index += 1;
+        match token {
+            Token::Number(number) => Ok(number),
+            Token::Symbol('-') => {
+                let mut number = self.factor()?;
+                number.value = -number.value;
+                Ok(number)
+            }
+            Token::Symbol('(') => {
+                let number = self.sum()?;
+                match self.symbol() {
+                    Some((')', _)) => {
+                        self.index += 1;
+                        Ok(number)
+                    }
+                    _ => Err(("Unclosed '('".to_string(), i)),
+                }
+            }
+            Token::Symbol(c) => Err((format!("Unexpected '{}'", c), i)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ui::style::parser::parse;
+    use crate::ui::style::tree::Selector;
+
+    fn selector(selector: &Selector) -> String {
+        let compounds = selector.compounds.iter().map(|c| {
+            let mut res = c.tag.clone().unwrap_or_default();
+            if let Some(id) = &c.id {
+                res += &format!("#{id}");
+            }
+            for class in &c.classes {
+                res += &format!(".{class}");
+            }
+            res
+        });
+        compounds.collect::<Vec<_>>().join(" ")
+    }
+
+    /// The selectors, declarations and theme of every rule.
+    fn rules(style: &str) -> Vec<(String, String, Option<String>)> {
+        let sheet = parse(style).unwrap();
+        sheet
+            .rules
+            .iter()
+            .map(|rule| {
+                let selectors = rule.selectors.iter().map(selector).collect::<Vec<_>>();
+                let declarations = rule
+                    .declarations
+                    .iter()
+                    .map(|d| format!("{}: {}", d.name, d.value))
+                    .collect::<Vec<_>>();
+                (
+                    selectors.join(", "),
+                    declarations.join("; "),
+                    rule.theme.clone(),
+                )
+            })
+            .collect()
+    }
+
+    fn error(style: &str) -> String {
+        let error = parse(style).unwrap_err();
+        format!("{}: {}", error.position, error.message)
+    }
+
+    #[test]
+    fn flattens() {
+        let style = "
+$base: 2px;
+@mixin sized($w) {
+    height: $w;
+    & label { height: calc($w / 2); }
+}
+row, column {
+    $gap: calc($base * 2 + 1px);
+    gap: $gap;
+    &.wide { width: calc(10 - -2 * (1 + 2)); }
+    & .item { @include sized(4px); }
+    label { padding: $gap; }
+}
+@theme dark {
+    --accent: #08f;
+    label { color: red; }
+}";
+        let rule = |selectors: &str, declarations: &str| {
+            (selectors.to_string(), declarations.to_string(), None)
+        };
+        assert_eq!(
+            rules(style),
+            [
+                rule("row, column", "gap: 5px"),
+                rule("row.wide, column.wide", "width: 16"),
+                rule("row .item, column .item", "height: 4px"),
+                rule("row .item label, column .item label", "height: 2px"),
+                rule("row label, column label", "padding: 5px"),
+                (
+                    "label".to_string(),
+                    "color: red".to_string(),
+                    Some("dark".to_string())
+                ),
+            ]
+        );
+        let variables = parse(style).unwrap().variables;
+        assert_eq!(variables[0].theme.as_deref(), Some("dark"));
+    }
+
+    #[test]
+    fn nesting_errors() {
+        assert_eq!(
+            error("label {\n    @theme dark {}\n}"),
+            "2:5: @theme blocks have to be at the top level"
+        );
+        assert_eq!(
+            error("label {\n    @mixin m() {}\n}"),
+            "2:5: Mixins have to be declared at the top level"
+        );
+        assert_eq!(
+            error("width: 5;"),
+            "1:1: Declaration 'width' has to be in a rule"
+        );
+        assert_eq!(
+            error("label { --accent: red; }"),
+            "1:9: Variable '--accent' has to be declared outside of rules"
+        );
+        assert_eq!(
+            error("label {}\n&.a { width: 1; }"),
+            "2:1: '&' can only be used in nested rules"
+        );
+        assert_eq!(
+            error("label { width: 1px $gap; }"),
+            "1:20: Variable '$gap' is not defined"
+        );
+    }
+
+    #[test]
+    fn mixin_errors() {
+        assert_eq!(
+            error("@mixin m($a) { width: $a; }\nlabel { @include m(1, 2); }"),
+            "2:9: Mixin 'm' takes 1 arguments but 2 were given"
+        );
+        assert_eq!(
+            error("label { @include m(); }"),
+            "1:9: Mixin 'm' is not declared"
+        );
+        assert_eq!(
+            error("@mixin m() {}\n@mixin m() {}"),
+            "2:1: Mixin 'm' is declared twice"
+        );
+        assert_eq!(
+            error("@mixin m() {\n    label { @include m(); }\n}\nrow { @include m(); }"),
+            "2:13: Mixin 'm' includes itself (included at 4:7)"
+        );
+        assert_eq!(
+            error("@mixin m($a) {\n    width: calc($a + 1);\n}\nrow { @include m(1px); }"),
+            "2:20: Can't combine '1px' and '1' with '+' (included at 4:7)"
+        );
+    }
+
+    #[test]
+    fn calc_errors() {
+        assert_eq!(
+            error("label { width: calc(1px + 2%); }"),
+            "1:25: Can't combine '1px' and '2%' with '+'"
+        );
+        assert_eq!(
+            error("label { width: calc(2px * 3px); }"),
+            "1:25: Can't multiply '2px' by '3px'"
+        );
+        assert_eq!(
+            error("label { width: calc(4 / (2 - 2)); }"),
+            "1:23: Division by zero"
+        );
+        assert_eq!(
+            error("label { width: calc(4px / 2%); }"),
+            "1:25: Can't divide '4px' by '2%'"
+        );
+        assert_eq!(
+            error("label { width: calc((1 + 2); }"),
+            "1:16: Unclosed 'calc('"
+        );
+        assert_eq!(
+            error("label { width: calc(1 2); }"),
+            "1:23: Expected an operator"
+        );
+        assert_eq!(
+            error("label { width: calc(1 +); }"),
+            "1:24: Expected a number"
+        );
+    }
+}
@@ -1 +1,4 @@
-
+pub mod cascade;
+pub mod flatten;
+pub mod parser;
+pub mod tree;
@@ -0,0 +1,396 @@
+use crate::ui::style::flatten::{self, Item, Mixin, NestedSelector, Property};
+use crate::ui::style::tree::{Compound, Selector, StyleSheet};
+use crate::ui::xml::lexer::Position;
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub struct StyleError {
+    pub message: String,
+    pub position: Position,
+}
+
+impl Display for StyleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.position, self.message)
+    }
+}
+
+/// Parses a stylesheet made of rules like `label.title, #main { width: 5; }` and variables like
+/// `--accent: #f80;`, which can be put in `@theme dark { ... }` blocks. Rules can be nested and
+/// use `$variables`, `calc()` and `@mixin`s, which are flattened into plain rules. Comments are
+/// written as `/* ... */`.
+pub fn parse(style: &str) -> Result<StyleSheet, StyleError> {
+    let mut parser = Parser {
+        chars: style.chars().collect(),
+        index: 0,
+        position: Position { line: 1, column: 1 },
+    };
+    let items = parser.parse_items(false)?;
+    flatten::flatten(items)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+    position: Position,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        let mut rest = self.chars[self.index..].iter();
+        s.chars().all(|c| rest.next() == Some(&c))
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        if c == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, StyleError> {
+        Err(StyleError {
+            message: message.into(),
+            position: self.position,
+        })
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_trivia(&mut self) -> Result<(), StyleError> {
+        loop {
+            if self.peek().is_some_and(char::is_whitespace) {
+                self.next();
+            } else if self.starts_with("/*") {
+                let position = self.position;
+                while !self.starts_with("*/") {
+                    if self.next().is_none() {
+                        return Err(StyleError {
+                            message: "Unterminated comment".to_string(),
+                            position,
+                        });
+                    }
+                }
+                self.next();
+                self.next();
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Parses the items of the stylesheet, or of a block up to and including its closing `}`.
+    fn parse_items(&mut self, block: bool) -> Result<Vec<Item>, StyleError> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_trivia()?;
+            match self.peek() {
+                None if block => return self.error("Expected '}' but found EOF"),
+                None => return Ok(items),
+                Some('}') if block => {
+                    self.next();
+                    return Ok(items);
+                }
+                Some('}') => return self.error("Unexpected '}'"),
+                Some(';') => {
+                    self.next();
+                }
+                Some('@') => items.push(self.parse_at_rule()?),
+                Some('$') => items.push(Item::Variable(self.parse_property()?)),
+                Some(_) if self.starts_with("--") => {
+                    items.push(Item::ThemeVariable(self.parse_property()?))
+                }
+                Some(_) if self.at_declaration() => {
+                    items.push(Item::Declaration(self.parse_property()?))
+                }
+                Some(_) => items.push(self.parse_rule()?),
+            }
+        }
+    }
+
+    /// Whether the next item is a declaration, which is a name followed by a colon.
+    fn at_declaration(&self) -> bool {
+        let rest = &self.chars[self.index..];
+        let name = rest.iter().take_while(|c| is_ident_char(**c)).count();
+        name > 0
+            && rest[name..]
+                .iter()
+                .find(|c| !c.is_whitespace())
+                .is_some_and(|c| *c == ':')
+    }
+
+    /// Parses `@theme name { ... }`, `@mixin name($param, ...) { ... }` or
+    /// `@include name(argument, ...);`.
+    fn parse_at_rule(&mut self) -> Result<Item, StyleError> {
+        let position = self.position;
+        self.next();
+        let keyword = self.parse_ident();
+        self.skip_trivia()?;
+        let name = self.expect_ident()?;
+        self.skip_trivia()?;
+
+        match keyword.as_str() {
+            "theme" => {
+                self.expect_block(&format!("@theme {name}"))?;
+                Ok(Item::Theme {
+                    name,
+                    items: self.parse_items(true)?,
+                    position,
+                })
+            }
+            "mixin" => {
+                let mut params = Vec::new();
+                if self.peek() == Some('(') {
+                    for (param, position) in self.parse_arguments()? {
+                        match param.strip_prefix('$') {
+                            Some(name) if !name.is_empty() && name.chars().all(is_ident_char) => {
+                                params.push(param)
+                            }
+                            _ => {
+                                return Err(StyleError {
+                                    message: format!(
+                                        "Expected a parameter like '$name' but found '{param}'"
+                                    ),
+                                    position,
+                                })
+                            }
+                        }
+                    }
+                    self.skip_trivia()?;
+                }
+                self.expect_block(&format!("@mixin {name}"))?;
+                Ok(Item::Mixin(Mixin {
+                    name,
+                    params,
+                    items: self.parse_items(true)?,
+                    position,
+                }))
+            }
+            "include" => {
+                let args = if self.peek() == Some('(') {
+                    self.parse_arguments()?
+                } else {
+                    Vec::new()
+                };
+                Ok(Item::Include {
+                    name,
+                    args,
+                    position,
+                })
+            }
+            _ => Err(StyleError {
+                message: format!("Unknown at-rule '@{keyword}'"),
+                position,
+            }),
+        }
+    }
+
+    fn expect_block(&mut self, after: &str) -> Result<(), StyleError> {
+        if self.next() != Some('{') {
+            return self.error(format!("Expected '{{' after '{after}'"));
+        }
+        Ok(())
+    }
+
+    /// Parses comma separated arguments in parentheses, with the position each one starts at.
+    /// Commas in nested parentheses or strings don't separate arguments.
+    fn parse_arguments(&mut self) -> Result<Vec<(String, Position)>, StyleError> {
+        let open = self.position;
+        self.next();
+        let mut args = Vec::new();
+        let mut arg = String::new();
+        let mut start = None;
+        let mut depth = 0;
+        let mut quote = None;
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(StyleError {
+                    message: "Unclosed '('".to_string(),
+                    position: open,
+                });
+            };
+            match c {
+                '"' | '\'' if quote == Some(c) => quote = None,
+                '"' | '\'' if quote.is_none() => quote = Some(c),
+                '(' if quote.is_none() => depth += 1,
+                ')' if quote.is_none() && depth > 0 => depth -= 1,
+                ')' | ',' if quote.is_none() => {
+                    if let Some(start) = start.take() {
+                        args.push((arg.trim_end().to_string(), start));
+                    } else if c == ',' || !args.is_empty() {
+                        return self.error("Expected an argument");
+                    }
+                    arg.clear();
+                    self.next();
+                    if c == ')' {
+                        return Ok(args);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            if start.is_none() && !c.is_whitespace() {
+                start = Some(self.position);
+            }
+            if start.is_some() {
+                arg.push(c);
+            }
+            self.next();
+        }
+    }
+
+    fn parse_rule(&mut self) -> Result<Item, StyleError> {
+        let position = self.position;
+        let new_selector = || NestedSelector {
+            parent: false,
+            attached: false,
+            selector: Selector {
+                compounds: Vec::new(),
+            },
+        };
+        let mut selectors = vec![new_selector()];
+
+        loop {
+            self.skip_trivia()?;
+            let current = selectors.last_mut().unwrap();
+            match self.peek() {
+                Some('{') => {
+                    self.next();
+                    break;
+                }
+                Some(',') => {
+                    self.next();
+                    selectors.push(new_selector());
+                }
+                Some('&') if !current.parent && current.selector.compounds.is_empty() => {
+                    self.next();
+                    current.parent = true;
+                    current.attached = matches!(self.peek(), Some('.' | '#'));
+                    if current.attached {
+                        let compound = self.parse_compound()?;
+                        current.selector.compounds.push(compound);
+                    }
+                }
+                Some(_) => {
+                    let compound = self.parse_compound()?;
+                    current.selector.compounds.push(compound);
+                }
+                None => return self.error("Expected '{' but found EOF"),
+            }
+        }
+
+        if selectors
+            .iter()
+            .any(|s| !s.parent && s.selector.compounds.is_empty())
+        {
+            return Err(StyleError {
+                message: "Expected a selector".to_string(),
+                position,
+            });
+        }
+
+        Ok(Item::Rule {
+            selectors,
+            items: self.parse_items(true)?,
+            position,
+        })
+    }
+
+    fn parse_compound(&mut self) -> Result<Compound, StyleError> {
+        let mut compound = Compound::default();
+        if self.peek() == Some('*') {
+            self.next();
+        } else if self.peek().is_some_and(is_ident_char) {
+            compound.tag = Some(self.parse_ident());
+        }
+
+        loop {
+            match self.peek() {
+                Some('.') => {
+                    self.next();
+                    let class = self.expect_ident()?;
+                    compound.classes.push(class);
+                }
+                Some('#') => {
+                    self.next();
+                    compound.id = Some(self.expect_ident()?);
+                }
+                Some(c) if c.is_whitespace() || c == ',' || c == '{' => return Ok(compound),
+                Some(c) => return self.error(format!("Unexpected '{c}' in selector")),
+                None => return Ok(compound),
+            }
+        }
+    }
+
+    /// Parses a declaration or a variable, like `width: 5` or `$gap: 4px`.
+    fn parse_property(&mut self) -> Result<Property, StyleError> {
+        let position = self.position;
+        let mut name = String::new();
+        if self.peek() == Some('$') {
+            self.next();
+            name.push('$');
+        }
+        name.push_str(&self.expect_ident()?);
+        self.skip_trivia()?;
+        if self.next() != Some(':') {
+            return self.error(format!("Expected ':' after '{name}'"));
+        }
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.next();
+        }
+
+        let value_position = self.position;
+        let mut value = String::new();
+        let mut quote = None;
+        while let Some(c) = self.peek() {
+            match c {
+                '"' | '\'' if quote == Some(c) => quote = None,
+                '"' | '\'' if quote.is_none() => quote = Some(c),
+                ';' | '}' if quote.is_none() => break,
+                _ => {}
+            }
+            value.push(c);
+            self.next();
+        }
+
+        Ok(Property {
+            name,
+            value: value.trim().to_string(),
+            position,
+            value_position,
+        })
+    }
+
+    fn parse_ident(&mut self) -> String {
+        let mut ident = String::new();
+        while let Some(c) = self.peek().filter(|c| is_ident_char(*c)) {
+            ident.push(c);
+            self.next();
+        }
+        ident
+    }
+
+    fn expect_ident(&mut self) -> Result<String, StyleError> {
+        let ident = self.parse_ident();
+        if ident.is_empty() {
+            match self.peek() {
+                Some(c) => self.error(format!("Expected identifier but found '{c}'")),
+                None => self.error("Expected identifier but found EOF"),
+            }
+        } else {
+            Ok(ident)
+        }
+    }
+}
+
+pub fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
@@ -0,0 +1,108 @@
+use crate::ui::xml::lexer::Position;
+
+#[derive(Debug, Clone)]
+pub struct StyleSheet {
+    pub rules: Vec<Rule>,
+    pub variables: Vec<Variable>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    /// The comma separated selectors of the rule.
+    pub selectors: Vec<Selector>,
+    pub declarations: Vec<Declaration>,
+    pub position: Position,
+    /// The theme the rule only applies in, if it is in a `@theme name { ... }` block.
+    pub theme: Option<String>,
+}
+
+/// A variable like `--accent: #f80;`, used in values as `var(--accent)`. Variables in a
+/// `@theme` block replace the ones outside of it in that theme.
+#[derive(Debug, Clone)]
+pub struct Variable {
+    /// The name including the leading `--`.
+    pub name: String,
+    pub value: String,
+    pub theme: Option<String>,
+    pub position: Position,
+}
+
+/// A chain of compound selectors separated by whitespace, matching descendants left to right.
+#[derive(Debug, Clone)]
+pub struct Selector {
+    pub compounds: Vec<Compound>,
+}
+
+/// A selector such as `label#title.big.red`. Without a tag, or with `*`, it matches any tag.
+#[derive(Debug, Clone, Default)]
+pub struct Compound {
+    pub tag: Option<String>,
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Declaration {
+    pub name: String,
+    /// The value as written, without surrounding whitespace.
+    pub value: String,
+    pub position: Position,
+}
+
+/// The parts of an element selectors are matched against.
+pub struct Target<'a> {
+    pub tag: &'a str,
+    pub id: &'a str,
+    pub classes: &'a [String],
+}
+
+impl Compound {
+    pub fn matches(&self, target: &Target) -> bool {
+        self.tag.as_ref().is_none_or(|tag| tag == target.tag)
+            && self.id.as_ref().is_none_or(|id| id == target.id)
+            && self.classes.iter().all(|c| target.classes.contains(c))
+    }
+}
+
+impl Selector {
+    /// Whether the selector matches the element, given its ancestors from the root down.
+    pub fn matches(&self, target: &Target, ancestors: &[Target]) -> bool {
+        let Some((last, rest)) = self.compounds.split_last() else {
+            return false;
+        };
+        if !last.matches(target) {
+            return false;
+        }
+        let mut ancestors = ancestors.iter().rev();
+        rest.iter()
+            .rev()
+            .all(|compound| ancestors.any(|ancestor| compound.matches(ancestor)))
+    }
+
+    /// The number of ids, classes and tags in the selector, in that order. When rules set the
+    /// same property, the one with the higher specificity wins.
+    pub fn specificity(&self) -> (usize, usize, usize) {
+        self.compounds
+            .iter()
+            .fold((0, 0, 0), |(ids, classes, tags), c| {
+                (
+                    ids + c.id.is_some() as usize,
+                    classes + c.classes.len(),
+                    tags + c.tag.is_some() as usize,
+                )
+            })
+    }
+
+    pub fn classes(&self) -> impl Iterator<Item = &String> {
+        self.compounds.iter().flat_map(|c| c.classes.iter())
+    }
+}
+
+/// The value without the quotes around it, if it is quoted.
+pub fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value)
+}
@@ -1,101 +1,703 @@
-use crate::ui::consts::{ATTRIBUTE_PATH, ELEMENTS, STYLE_PATH};
-use crate::ui::xml::tree::{Child, Node, UnnamedNode};
+use crate::ui::consts::{
+    ATTRIBUTE_PATH, CHILD_PATH, ELEMENTS, HANDLE_PATH, NON_RAW_KEYWORDS, RUNTIME_ALIAS,
+    STRINGS_PATH, STYLE_PATH, THEMES_PATH, UI_ELEMENT_PATH,
+};
+use crate::ui::lang;
+use crate::ui::linker::module_name;
+use crate::ui::rust::{self, Block, Expr, Ident, Item, Path, Stmt, Type};
+use crate::ui::style::cascade::Styles;
+use crate::ui::style::tree::{unquote, Target};
+use crate::ui::xml::lexer::{Literal, Position};
+use crate::ui::xml::markup::MarkupError;
+use crate::ui::xml::tree::{Child, NamedNode, Node, UnnamedNode};
 use mvutils::utils::Recover;
 
-pub fn generate(mut tree: Vec<Node>) -> String {
-    let root = if tree.len() == 1 {
-        tree.pop().unwrap()
+/// An element with an id which gets a typed field in the struct of its UI file.
+struct Handle {
+    field: Ident,
+    ty: Path,
+    /// The position of the element, to find it again while generating it.
+    position: Position,
+}
+
+/// What the elements of a tree are generated with.
+struct Scope<'a> {
+    handles: &'a [Handle],
+    styles: &'a Styles,
+    /// The tag, id and classes of the named elements the current one is nested in.
+    ancestors: Vec<(String, String, Vec<String>)>,
+}
+
+impl<'a> Scope<'a> {
+    fn new(handles: &'a [Handle], styles: &'a Styles) -> Self {
+        Scope {
+            handles,
+            styles,
+            ancestors: Vec::new(),
+        }
+    }
+}
+
+/// Generates a self-contained module for the tree. The runtime crate is imported from `runtime`
+/// and every element is referred to by an absolute path, so the code doesn't depend on what the
+/// parent module imports.
+///
+/// Besides `generate()`, the module contains a struct named after the module with the root
+/// element and a typed handle for every element with an id, built by its `build()` function.
+///
+/// Fails on markup which has no valid code, such as an attribute named `self`.
+pub fn generate(
+    tree: Vec<Node>,
+    runtime: &str,
+    module: &str,
+    styles: &Styles,
+) -> Result<String, MarkupError> {
+    let name = struct_name(module);
+    let root = root(tree, &name.to_string());
+    let mut handles = Vec::new();
+    collect_handles(&root, &name, false, &mut handles)?;
+    let element = generate_element(root, &mut Scope::new(&handles, styles))?;
+
+    let mut items = imports(runtime, false);
+    let generate = if handles.is_empty() {
+        element
     } else {
-        Node::Unnamed(UnnamedNode {
-            children: tree.into_iter().map(|n| Child::Node(n)).collect(),
+        // Handles are taken while the tree is built, so the elements are built by a function
+        // returning them together with the root.
+        let mut types = vec![Type::Impl(Path::parse(UI_ELEMENT_PATH))];
+        types.extend(handles.iter().map(handle_type));
+        let mut stmts: Vec<Stmt> = handles
+            .iter()
+            .map(|h| Stmt::declare(h.field.clone()))
+            .collect();
+        stmts.push(Stmt::let_("root", Expr::Block(element)));
+        let mut values = vec![Expr::path("root")];
+        values.extend(
+            handles
+                .iter()
+                .map(|h| Expr::Path(Path::of(h.field.clone()))),
+        );
+        items.push(Item::Fn {
+            public: false,
+            docs: None,
+            name: Ident::new("elements"),
+            params: Vec::new(),
+            ret: Type::Tuple(types),
+            body: Block {
+                stmts,
+                tail: Some(Box::new(Expr::Tuple(values))),
+            },
+        });
+
+        let mut names = vec![Ident::new("root")];
+        names.extend(handles.iter().map(|_| Ident::placeholder()));
+        Block {
+            stmts: vec![Stmt::let_tuple(names, Expr::path("elements").call(vec![]))],
+            tail: Some(Box::new(Expr::path("root"))),
+        }
+    };
+    items.push(Item::Fn {
+        public: true,
+        docs: None,
+        name: Ident::new("generate"),
+        params: Vec::new(),
+        ret: Type::Impl(Path::parse(UI_ELEMENT_PATH)),
+        body: generate,
+    });
+    items.extend(handle_struct(name, &handles));
+
+    Ok(rust::print_items(&items))
+}
+
+/// The struct holding the root and the handles, and its `build()` function.
+fn handle_struct(name: Ident, handles: &[Handle]) -> [Item; 2] {
+    let root = Ident::new("root");
+    let boxed = Type::Generic(
+        Path::parse("Box"),
+        vec![Type::Dyn(Path::parse(UI_ELEMENT_PATH))],
+    );
+    let mut fields = vec![(root.clone(), boxed)];
+    fields.extend(handles.iter().map(|h| (h.field.clone(), handle_type(h))));
+
+    let mut stmts = Vec::new();
+    let root_value = if handles.is_empty() {
+        Expr::path("generate").call(vec![])
+    } else {
+        let names = fields.iter().map(|(field, _)| field.clone()).collect();
+        stmts.push(Stmt::let_tuple(names, Expr::path("elements").call(vec![])));
+        Expr::Path(Path::of(root.clone()))
+    };
+    let mut values = vec![(root, Expr::path("Box::new").call(vec![root_value]))];
+    values.extend(
+        handles
+            .iter()
+            .map(|h| (h.field.clone(), Expr::Path(Path::of(h.field.clone())))),
+    );
+
+    [
+        Item::Struct {
+            name: name.clone(),
+            fields,
+        },
+        Item::Impl {
+            ty: name.clone(),
+            items: vec![Item::Fn {
+                public: true,
+                docs: None,
+                name: Ident::new("build"),
+                params: Vec::new(),
+                ret: Type::Path(Path::parse("Self")),
+                body: Block {
+                    stmts,
+                    tail: Some(Box::new(Expr::Struct(Path::of(name), values))),
+                },
+            }],
+        },
+    ]
+}
+
+fn handle_type(handle: &Handle) -> Type {
+    Type::Generic(
+        Path::parse(HANDLE_PATH),
+        vec![Type::Path(handle.ty.clone())],
+    )
+}
+
+/// The struct name for a module, e.g. `MainMenu` for `main_menu`.
+pub fn struct_name(module: &str) -> Ident {
+    let mut name: String = module
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+                .collect::<String>()
         })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert_str(0, "Ui");
+    }
+    if NON_RAW_KEYWORDS.contains(&name.as_str()) {
+        name.push('_');
+    }
+    Ident::new(&name)
+}
+
+/// Finds the elements with an id which get a handle. Ids of generic elements, of elements which
+/// are only built under a `cfg` condition, ids which would give the same field name as an
+/// earlier one, and an id named like the root field are skipped with a warning.
+fn collect_handles(
+    node: &Node,
+    name: &Ident,
+    gated: bool,
+    handles: &mut Vec<Handle>,
+) -> Result<(), MarkupError> {
+    let gated = gated || !element_cfg(node).is_empty();
+    let children = match node {
+        Node::Named(node) => {
+            if !node.id.is_empty() {
+                if let Some(handle) = handle(node, name, gated, handles)? {
+                    handles.push(handle);
+                }
+            }
+            &node.children
+        }
+        Node::Unnamed(node) => &node.children,
+    };
+    for child in children {
+        if let Child::Node(child) = child {
+            collect_handles(child, name, gated, handles)?;
+        }
+    }
+    Ok(())
+}
+
+fn handle(
+    node: &NamedNode,
+    name: &Ident,
+    gated: bool,
+    handles: &[Handle],
+) -> Result<Option<Handle>, MarkupError> {
+    let elements = ELEMENTS.read().recover();
+    let Some(element) = elements.iter().find(|e| e.tag == node.tag) else {
+        return Ok(None);
+    };
+    let skip = |reason: &str| {
+        eprintln!(
+            "No handle is generated for '{}' in {}: {}",
+            node.id, name, reason
+        );
+        Ok(None)
+    };
+
+    if !element.generics.is_empty() {
+        return skip("the element is generic");
+    }
+    if gated {
+        return skip("the element is only built under a cfg condition");
+    }
+    let Some(field) = module_name(&node.id) else {
+        return skip("the id contains no usable characters");
     };
+    let field = Ident::new(&field);
+    if field == Ident::new("root") {
+        return skip("the field name is taken by the root element");
+    }
+    if handles.iter().any(|h| h.field == field) {
+        return skip("an earlier element has the same field name");
+    }
+
+    let ty = Path::try_parse(&element.path).map_err(|message| MarkupError {
+        message,
+        position: node.position,
+    })?;
+    Ok(Some(Handle {
+        field,
+        ty: ty.absolute(&[RUNTIME_ALIAS]),
+        position: node.position,
+    }))
+}
 
-    let code = generate_element(root);
+/// Generates a block expression evaluating to the element of the tree, with the same imports as
+/// [`generate`] scoped to the block. Used for markup written inline in Rust code, where unused
+/// imports would be reported in the user's crate.
+pub fn expression(tree: Vec<Node>, runtime: &str, styles: &Styles) -> Result<String, MarkupError> {
+    let mut stmts: Vec<Stmt> = imports(runtime, true).into_iter().map(Stmt::item).collect();
+    stmts.push(Stmt::let_(
+        "element",
+        Expr::Block(generate_element(
+            root(tree, "ui!"),
+            &mut Scope::new(&[], styles),
+        )?),
+    ));
+    Ok(rust::print_expr(&Expr::Block(Block {
+        stmts,
+        tail: Some(Box::new(Expr::path("element"))),
+    })))
+}
+
+/// Wraps multiple top level nodes in an unnamed node. A root element behind a `cfg` condition
+/// can't be left out, which is warned about.
+fn root(mut tree: Vec<Node>, name: &str) -> Node {
+    if tree.len() == 1 {
+        let root = tree.pop().unwrap();
+        let cfg = element_cfg(&root);
+        if !cfg.is_empty() {
+            eprintln!(
+                "Warning: The root element of {} is only built under #[{}], the generated code \
+                 doesn't compile without it",
+                name,
+                cfg.join("], #[")
+            );
+        }
+        root
+    } else {
+        Node::Unnamed(UnnamedNode {
+            children: tree.into_iter().map(Child::Node).collect(),
+            position: Position { line: 1, column: 1 },
+        })
+    }
+}
 
-    format!("pub fn generate() -> impl UiElement {{\n{code}\n}}")
+/// Imports the runtime, and the element trait anonymously for its methods, which might not be
+/// needed. Everything else is referred to by its full path, so nothing clashes with the struct
+/// generated for the file.
+fn imports(runtime: &str, allow_unused: bool) -> Vec<Item> {
+    vec![
+        Item::Use {
+            path: Path::parse(runtime).absolute(&[]),
+            alias: Some(Ident::new(RUNTIME_ALIAS)),
+            allow_unused,
+        },
+        Item::Use {
+            path: Path::parse(UI_ELEMENT_PATH),
+            alias: Some(Ident::placeholder()),
+            allow_unused: true,
+        },
+    ]
 }
 
-fn generate_element(node: Node) -> String {
-    let tag = match &node {
-        Node::Named(node) => node.tag.as_str(),
-        Node::Unnamed(_) => "",
+fn generate_element(node: Node, scope: &mut Scope) -> Result<Block, MarkupError> {
+    let (tag, position) = match &node {
+        Node::Named(node) => (node.tag.as_str(), node.position),
+        Node::Unnamed(node) => ("", node.position),
     };
 
     let element = ELEMENTS
         .read()
         .recover()
         .iter()
-        .find(|e| e.0 == tag)
-        .expect(&format!("Tag '{tag}' is not defined"))
-        .1
-        .clone();
+        .find(|e| e.tag == tag)
+        .ok_or_else(|| MarkupError {
+            message: format!("Unknown element '{tag}'"),
+            position,
+        })?
+        .type_path()
+        .map_err(|message| MarkupError { message, position })?
+        .absolute(&[RUNTIME_ALIAS]);
 
-    let mut code = "{\n".to_string();
+    let mut stmts = Vec::new();
+    let named = matches!(node, Node::Named(_));
 
     let children = match node {
         Node::Named(node) => {
-            code.push_str(&format!(
-                "let mut attributes = {}::new();\n",
-                ATTRIBUTE_PATH
-            ));
+            let attributes = || Expr::path("attributes");
+            let assigned = !node.id.is_empty() || !node.class.is_empty();
+            stmts.push(
+                let_(
+                    "attributes",
+                    Expr::new(Path::parse(ATTRIBUTE_PATH), vec![]),
+                    assigned || !node.attributes.is_empty(),
+                )
+                .at(node.position),
+            );
 
             if !node.id.is_empty() {
-                code.push_str(&format!("attributes.id = Some({});\n", node.id));
+                stmts.push(
+                    Stmt::assign(attributes().field("id"), some(Expr::Str(node.id.clone())))
+                        .at(node.id_position.unwrap_or(node.position)),
+                );
             }
 
             if !node.class.is_empty() {
-                code.push_str(&format!(
-                    "attributes.class = Some({});\n",
-                    node.class.join(" ")
-                ));
+                stmts.push(
+                    Stmt::assign(
+                        attributes().field("class"),
+                        some(Expr::Str(node.class.join(" "))),
+                    )
+                    .at(node.class_position.unwrap_or(node.position)),
+                );
             }
 
-            for attribute in node.attributes {
-                if attribute.name == "type" {
-                    code.push_str(&format!("attributes.ty = Some({});\n", attribute.value));
-                } else {
-                    code.push_str(&format!(
-                        "attributes.{} = Some({});\n",
-                        attribute.name, attribute.value
-                    ));
-                }
+            for attribute in &node.attributes {
+                let name = match attribute.name.as_str() {
+                    "type" => "ty",
+                    name => name,
+                };
+                let field = Ident::parse(name).map_err(|e| MarkupError {
+                    message: format!("Attribute {e}"),
+                    position: attribute.position,
+                })?;
+                let value = match &attribute.value {
+                    Literal::String(s) => string(s.clone(), attribute.position)?,
+                    Literal::Int(i) => Expr::Int(*i),
+                    Literal::Float(f) => Expr::Float(*f),
+                };
+                stmts.push(
+                    Stmt::assign(Expr::Field(Box::new(attributes()), field), some(value))
+                        .at(attribute.position),
+                );
             }
 
-            code.push_str(&format!("let mut style = {}::new();\n", STYLE_PATH));
+            let style = calculate_style(&node, scope)?;
+            stmts.push(
+                let_(
+                    "style",
+                    Expr::new(Path::parse(STYLE_PATH), vec![]),
+                    !style.is_empty(),
+                )
+                .at(node.position),
+            );
+            stmts.extend(style);
 
-            code.push_str(&calculate_style(node.tag, node.id, node.class));
+            stmts.push(
+                let_(
+                    "element",
+                    Expr::new(element.clone(), vec![attributes(), Expr::path("style")]),
+                    !node.children.is_empty(),
+                )
+                .at(node.position),
+            );
 
-            code.push_str(&format!(
-                "let mut element = {element}::new(attributes, style);\n"
-            ));
+            if let Some(handle) = scope.handles.iter().find(|h| h.position == node.position) {
+                stmts.push(
+                    Stmt::assign(
+                        Expr::Path(Path::of(handle.field.clone())),
+                        Expr::new(
+                            Path::parse(HANDLE_PATH),
+                            vec![Expr::Ref(Box::new(Expr::path("element")))],
+                        ),
+                    )
+                    .at(node.id_position.unwrap_or(node.position)),
+                );
+            }
 
+            scope.ancestors.push((node.tag, node.id, node.class));
             node.children
         }
         Node::Unnamed(node) => {
-            code.push_str(&format!("let mut element = {element}::new();\n"));
+            let mutable = !node.children.is_empty();
+            stmts.push(let_("element", Expr::new(element, vec![]), mutable).at(node.position));
 
             node.children
         }
     };
 
     for child in children {
-        match child {
-            Child::Node(node) => code.push_str(&format!(
-                "element.add_child(Child::Element({}.into()));\n",
-                generate_element(node)
-            )),
-            Child::String(str) => code.push_str(&format!(
-                "element.add_child(Child::String(\"{str}\".into()));\n"
-            )),
+        let cfg = match &child {
+            Child::Node(node) => element_cfg(node),
+            Child::String(_) => Vec::new(),
+        };
+        let child = match child {
+            Child::Node(node) => {
+                Expr::Path(Path::parse(CHILD_PATH).join("Element"))
+                    .call(vec![
+                        Expr::Block(generate_element(node, scope)?).method("into", vec![])
+                    ])
+            }
+            Child::String(str) => Expr::Path(Path::parse(CHILD_PATH).join("String"))
+                .call(vec![string(str, position)?.method("into", vec![])]),
+        };
+        stmts.push(Stmt::expr(Expr::path("element").method("add_child", vec![child])).cfg(cfg));
+    }
+
+    if named {
+        scope.ancestors.pop();
+    }
+
+    Ok(Block {
+        stmts,
+        tail: Some(Box::new(Expr::path("element"))),
+    })
+}
+
+/// A string literal, or the lookup of a translated string if the text refers to one. Fails if
+/// the key of the string gives no name.
+fn string(text: String, position: Position) -> Result<Expr, MarkupError> {
+    let Some(key) = lang::reference(&text) else {
+        return Ok(Expr::Str(text));
+    };
+    let variant = lang::variant(key).ok_or_else(|| MarkupError {
+        message: format!("String '{key}' contains no usable characters"),
+        position,
+    })?;
+    Ok(
+        Expr::Path(Path::parse(STRINGS_PATH).join("get")).call(vec![Expr::Path(
+            Path::parse(STRINGS_PATH)
+                .join("Key")
+                .join(&variant.to_string()),
+        )]),
+    )
+}
+
+/// A `let` statement, only mutable if the variable is assigned to afterwards, so the generated
+/// code doesn't warn about unused `mut`s.
+/// The `cfg` conditions the element of a node is defined under.
+fn element_cfg(node: &Node) -> Vec<String> {
+    let Node::Named(node) = node else {
+        return Vec::new();
+    };
+    ELEMENTS
+        .read()
+        .recover()
+        .iter()
+        .find(|e| e.tag == node.tag)
+        .map(|e| e.cfg.clone())
+        .unwrap_or_default()
+}
+
+fn let_(name: &str, value: Expr, mutable: bool) -> Stmt {
+    if mutable {
+        Stmt::let_mut(name, value)
+    } else {
+        Stmt::let_(name, value)
+    }
+}
+
+fn some(value: Expr) -> Expr {
+    Expr::path("Some").call(vec![value])
+}
+
+/// Sets the properties the stylesheets give the element on its style. Properties which differ
+/// between themes are looked up by the index of the selected theme, with `None` for the themes
+/// which don't set them. Numbers too large for a float, `inf` and `NaN` are rejected, as they
+/// have no literal.
+fn calculate_style(node: &NamedNode, scope: &Scope) -> Result<Vec<Stmt>, MarkupError> {
+    let ancestors: Vec<Target> = scope
+        .ancestors
+        .iter()
+        .map(|(tag, id, classes)| Target { tag, id, classes })
+        .collect();
+    let target = Target {
+        tag: &node.tag,
+        id: &node.id,
+        classes: &node.class,
+    };
+
+    let mut stmts = Vec::new();
+    for (name, values) in scope.styles.properties(&target, &ancestors) {
+        let field = match module_name(name).as_deref() {
+            Some("type") => "ty".to_string(),
+            Some(field) => field.to_string(),
+            None => {
+                eprintln!(
+                    "Property '{}' is skipped: it contains no usable characters",
+                    name
+                );
+                continue;
+            }
+        };
+        if let Some(value) = values
+            .iter()
+            .flatten()
+            .find(|v| v.parse::<f64>().is_ok_and(|f| !f.is_finite()))
+        {
+            return Err(MarkupError {
+                message: format!(
+                    "Property '{name}' of '{}' is set to '{value}', which isn't a finite number",
+                    node.tag
+                ),
+                position: node.position,
+            });
+        }
+        // Numbers in one lookup have to be of the same type.
+        let float = values
+            .iter()
+            .flatten()
+            .any(|v| v.parse::<i64>().is_err() && v.parse::<f64>().is_ok());
+        let value = match values.as_slice() {
+            [Some(first), rest @ ..] if rest.iter().all(|v| *v == Some(first)) => {
+                some(style_value(first, float, node.position)?)
+            }
+            _ => Expr::Array(
+                values
+                    .iter()
+                    .map(|v| match v {
+                        Some(v) => style_value(v, float, node.position).map(some),
+                        None => Ok(Expr::path("None")),
+                    })
+                    .collect::<Result<_, _>>()?,
+            )
+            .index(Expr::Path(Path::parse(THEMES_PATH).join("index")).call(vec![])),
+        };
+        stmts.push(Stmt::assign(Expr::path("style").field(&field), value).at(node.position));
+    }
+    Ok(stmts)
+}
+
+/// A value as written in a stylesheet, which is a number, a string with or without quotes, or
+/// the key of a translated string.
+fn style_value(value: &str, float: bool, position: Position) -> Result<Expr, MarkupError> {
+    match (value.parse::<i64>(), value.parse::<f64>()) {
+        (Ok(i), _) if !float => Ok(Expr::Int(i)),
+        (_, Ok(f)) => Ok(Expr::Float(f)),
+        _ => string(unquote(value).to_string(), position),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::meta::file::ElementMeta;
+
+    #[test]
+    fn imports_the_runtime() {
+        let code = crate::ui::xml::compile("<label/>", "engine::core", "menu", &Styles::default())
+            .unwrap();
+        assert!(code.starts_with("use ::engine::core as runtime;\n"));
+        assert!(code.contains("runtime::ui::Label"));
+    }
+
+    #[test]
+    fn struct_names() {
+        let name = |module| struct_name(module).to_string();
+        assert_eq!(name("main_menu"), "MainMenu");
+        assert_eq!(name("_2d_view"), "Ui2dView");
+        assert_eq!(name("self_"), "Self_");
+    }
+
+    #[test]
+    fn handles() {
+        let ui = "<element><label id=\"title\">Hi</label><button id=\"ok\"/><label id=\"Title\"/></element>";
+        let code = crate::ui::xml::compile(ui, "mvcore", "main_menu", &Styles::default()).unwrap();
+        assert!(code.contains(
+            "fn elements() -> (\
+             impl runtime::ui::elements::UiElement, \
+             runtime::ui::elements::Handle<runtime::ui::Label>, \
+             runtime::ui::elements::Handle<runtime::ui::Button>) {\n    \
+             let title;\n    let ok;\n"
+        ));
+        assert!(code.contains("title = runtime::ui::elements::Handle::new(&element);"));
+        assert!(code.contains("ok = runtime::ui::elements::Handle::new(&element);"));
+        assert_eq!(code.matches("Handle::new").count(), 2);
+        assert!(code.contains("    let (root, _, _) = elements();\n    root\n"));
+        assert!(code.ends_with(
+            "pub struct MainMenu {
+    pub root: Box<dyn runtime::ui::elements::UiElement>,
+    pub title: runtime::ui::elements::Handle<runtime::ui::Label>,
+    pub ok: runtime::ui::elements::Handle<runtime::ui::Button>,
+}
+
+impl MainMenu {
+    pub fn build() -> Self {
+        let (root, title, ok) = elements();
+        MainMenu {
+            root: Box::new(root),
+            title,
+            ok,
         }
     }
+}
+"
+        ));
+    }
 
-    code.push_str("element\n}");
+    #[test]
+    fn no_handles() {
+        let code =
+            crate::ui::xml::compile("<label/>", "mvcore", "menu", &Styles::default()).unwrap();
+        assert!(!code.contains("fn elements()"));
+        assert!(code.ends_with(
+            "pub struct Menu {
+    pub root: Box<dyn runtime::ui::elements::UiElement>,
+}
 
-    code
+impl Menu {
+    pub fn build() -> Self {
+        Menu {
+            root: Box::new(generate()),
+        }
+    }
 }
+"
+        ));
+    }
+
+    #[test]
+    fn cfg_gated_elements() {
+        let mut element = ElementMeta::new("gated_slider".to_string(), "crate::Slider".to_string());
+        element.cfg = vec!["cfg(feature = \"sliders\")".to_string()];
+        ELEMENTS.write().recover().push(element);
+
+        let ui = "<element><gated_slider id=\"volume\"><label id=\"inner\"/></gated_slider><label id=\"title\"/></element>";
+        let code = crate::ui::xml::compile(ui, "mvcore", "menu", &Styles::default()).unwrap();
+        assert!(code.contains(
+            "        #[cfg(feature = \"sliders\")]\n        element.add_child(runtime::ui::elements::Child::Element({"
+        ));
+        assert_eq!(code.matches("#[cfg(").count(), 1);
+        // Only the element which is always built gets a handle.
+        assert!(code.contains("pub title: runtime::ui::elements::Handle<runtime::ui::Label>,"));
+        assert!(!code.contains("volume =") && !code.contains("inner ="));
+    }
 
-fn calculate_style(tag: String, id: String, class: Vec<String>) -> String {
-    String::new()
+    #[test]
+    fn invalid_element_paths() {
+        ELEMENTS.write().recover().push(ElementMeta::new(
+            "broken_knob".to_string(),
+            "crate::Knob<T>".to_string(),
+        ));
+
+        for ui in [
+            "<broken_knob/>",
+            "<element><broken_knob id=\"knob\"/></element>",
+        ] {
+            let error =
+                crate::ui::xml::compile(ui, "mvcore", "menu", &Styles::default()).unwrap_err();
+            assert_eq!(error.message, "'crate::Knob<T>' is not a valid path");
+        }
+    }
 }
@@ -0,0 +1,217 @@
+use crate::ui::config::FormatConfig;
+use crate::ui::xml::markup::{Element, Markup, RawAttribute, RawValue};
+
+/// Prints the markup back out with consistent indentation. Attributes are ordered `id`,
+/// `class` and then as written, and put on separate lines if the tag would get longer than
+/// the configured width. Text is re-indented line by line, which keeps its meaning as the
+/// compiler trims every line of text anyway. Blank lines between nodes are kept, but collapsed
+/// to a single one.
+pub fn format(nodes: &[Markup], config: &FormatConfig) -> String {
+    let mut printer = Printer {
+        out: String::new(),
+        config,
+    };
+    printer.print_children(nodes, 0);
+    printer.out
+}
+
+struct Printer<'a> {
+    out: String,
+    config: &'a FormatConfig,
+}
+
+impl Printer<'_> {
+    fn line(&mut self, depth: usize, line: &str) {
+        if !line.is_empty() {
+            self.out.push_str(&" ".repeat(depth * self.config.indent));
+            self.out.push_str(line);
+        }
+        self.out.push('\n');
+    }
+
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn print_children(&mut self, children: &[Markup], depth: usize) {
+        let start = self.out.len();
+        for (i, child) in children.iter().enumerate() {
+            match child {
+                Markup::Element(element) => self.print_element(element, depth),
+                Markup::Comment(comment) => self.line(depth, &format!("<!--{comment}-->")),
+                Markup::Text(text) => {
+                    let first = i == 0;
+                    let last = i == children.len() - 1;
+                    let lines: Vec<_> = text.lines().map(str::trim).collect();
+                    let Some(begin) = lines.iter().position(|l| !l.is_empty()) else {
+                        if !first && !last && text.matches('\n').count() > 1 {
+                            self.blank_line();
+                        }
+                        continue;
+                    };
+                    let end = lines.iter().rposition(|l| !l.is_empty()).unwrap();
+
+                    if !first && begin > 1 {
+                        self.blank_line();
+                    }
+                    for line in &lines[begin..=end] {
+                        self.line(depth, line);
+                    }
+                    let trailing = text.len() - text.trim_end().len();
+                    if !last && text[text.len() - trailing..].matches('\n').count() > 1 {
+                        self.blank_line();
+                    }
+                }
+            }
+        }
+        if self.out.len() > start && self.out.ends_with("\n\n") {
+            self.out.pop();
+        }
+    }
+
+    fn print_element(&mut self, element: &Element, depth: usize) {
+        let mut attributes: Vec<&RawAttribute> = element.attributes.iter().collect();
+        attributes.sort_by_key(|a| match a.name.as_str() {
+            "id" => 0,
+            "class" => 1,
+            _ => 2,
+        });
+        let attributes: Vec<String> = attributes.into_iter().map(print_attribute).collect();
+
+        let children: Vec<&Markup> = element
+            .children
+            .iter()
+            .filter(|c| !matches!(c, Markup::Text(t) if t.trim().is_empty()))
+            .collect();
+
+        let open: String = std::iter::once(format!("<{}", element.tag))
+            .chain(attributes.iter().map(|a| format!(" {a}")))
+            .collect();
+        let indent = depth * self.config.indent;
+        let wrap = !attributes.is_empty() && indent + open.len() + 2 > self.config.max_width;
+
+        if children.is_empty() && !element.tag.is_empty() {
+            if wrap {
+                self.print_wrapped(&element.tag, &attributes, depth, "/>");
+            } else {
+                self.line(depth, &format!("{open}/>"));
+            }
+            return;
+        }
+
+        if let [Markup::Text(text)] = children.as_slice() {
+            let text = text.trim();
+            let inline = format!("{open}>{text}</{}>", element.tag);
+            if !wrap && !text.contains('\n') && indent + inline.len() <= self.config.max_width {
+                self.line(depth, &inline);
+                return;
+            }
+        }
+
+        if wrap {
+            self.print_wrapped(&element.tag, &attributes, depth, ">");
+        } else {
+            self.line(depth, &format!("{open}>"));
+        }
+        self.print_children(&element.children, depth + 1);
+        self.line(depth, &format!("</{}>", element.tag));
+    }
+
+    fn print_wrapped(&mut self, tag: &str, attributes: &[String], depth: usize, close: &str) {
+        self.line(depth, &format!("<{tag}"));
+        for attribute in attributes {
+            self.line(depth + 1, attribute);
+        }
+        self.line(depth, close);
+    }
+}
+
+/// Prints an attribute with its value in double quotes, unless the value itself contains one.
+fn print_attribute(attribute: &RawAttribute) -> String {
+    match &attribute.value {
+        RawValue::String(value) if value.contains('"') => {
+            format!("{}='{}'", attribute.name, value)
+        }
+        RawValue::String(value) => format!("{}=\"{}\"", attribute.name, value),
+        RawValue::Number(number) => format!("{}={}", attribute.name, number),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::xml::markup::parse;
+
+    fn format_str(markup: &str, max_width: usize) -> String {
+        let config = FormatConfig {
+            indent: 2,
+            max_width,
+        };
+        format(&parse(markup).unwrap(), &config)
+    }
+
+    #[test]
+    fn formats() {
+        let markup = "<column class='a b' id=\"main\">
+                <!-- A comment -->
+            <label>  Hello  </label>
+
+
+
+            <button width=10 label='Say \"hi\"'/>
+            <text>
+                First line
+                  second line
+            </text></column>";
+        assert_eq!(
+            format_str(markup, 100),
+            "<column id=\"main\" class=\"a b\">
+  <!-- A comment -->
+  <label>Hello</label>
+
+  <button width=10 label='Say \"hi\"'/>
+  <text>
+    First line
+    second line
+  </text>
+</column>
+"
+        );
+    }
+
+    #[test]
+    fn wraps_attributes() {
+        assert_eq!(
+            format_str("<row><slider min=0 max=100 step=5/></row>", 20),
+            "<row>
+  <slider
+    min=0
+    max=100
+    step=5
+  />
+</row>
+"
+        );
+    }
+
+    #[test]
+    fn idempotent() {
+        let markup = "<column id='main'>
+            text before <label>x</label>
+
+            <row class=\"wide\" width=1.5><!--c--><button label=\"b\"/>
+
+            </row>
+            <>
+                <slider min=0 max=100 step=5 label=\"A rather long label for a slider\"/>
+            </>
+        </column>
+        <column/>";
+        for max_width in [20, 40, 100] {
+            let once = format_str(markup, max_width);
+            assert_eq!(format_str(&once, max_width), once);
+        }
+    }
+}
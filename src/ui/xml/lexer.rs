@@ -1,6 +1,69 @@
+use crate::ui::xml::markup::MarkupError;
 use std::fmt::Display;
-use std::iter::Peekable;
-use std::str::Chars;
+
+/// A 1-based line and column in a UI file.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// The characters of the input, keeping track of the position of the next one.
+struct Cursor {
+    chars: Vec<char>,
+    index: usize,
+    position: Position,
+}
+
+impl Cursor {
+    fn peek(&self) -> Option<&char> {
+        self.chars.get(self.index)
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        let mut rest = self.chars[self.index..].iter();
+        s.chars().all(|c| rest.next() == Some(&c))
+    }
+
+    /// Skips a comment after its opening `<!--`, up to and including the closing `-->`. Fails
+    /// with an error at `start` if the comment isn't closed.
+    fn skip_comment(&mut self, start: Position) -> Result<(), MarkupError> {
+        while !self.starts_with("-->") {
+            if self.next().is_none() {
+                return Err(MarkupError {
+                    message: "Unterminated comment".to_string(),
+                    position: start,
+                });
+            }
+        }
+        for _ in 0..3 {
+            self.next();
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Cursor {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = *self.chars.get(self.index)?;
+        self.index += 1;
+        if c == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+        Some(c)
+    }
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
@@ -48,17 +111,33 @@ impl Display for Literal {
     }
 }
 
-pub fn tokenize(xml: String) -> Vec<Token> {
+/// Splits the UI file into tokens, each with the position it starts at.
+pub fn tokenize(xml: String) -> Result<Vec<(Token, Position)>, MarkupError> {
     let mut tokens = Vec::new();
-    let mut iter = xml.chars().peekable();
+    let mut iter = Cursor {
+        chars: xml.chars().collect(),
+        index: 0,
+        position: Position { line: 1, column: 1 },
+    };
     let mut inside = false;
 
-    while let Some(c) = iter.next() {
+    loop {
+        let position = iter.position;
+        let Some(c) = iter.next() else {
+            break;
+        };
+        let mut tokens = Spanned {
+            tokens: &mut tokens,
+            position,
+        };
+        let error = |message: String| Err(MarkupError { message, position });
         match c {
             '<' => {
-                if iter.peek() == Some(&'<') {
+                if iter.starts_with("!--") {
+                    iter.skip_comment(position)?;
+                } else if iter.peek() == Some(&'<') {
                     iter.next();
-                    extract_contents(&mut tokens, &mut iter, &mut inside, '<');
+                    extract_contents(&mut tokens, &mut iter, &mut inside, '<')?;
                 } else if iter.peek() == Some(&'/') {
                     iter.next();
                     tokens.push(Token::ClosingTag);
@@ -72,21 +151,19 @@ pub fn tokenize(xml: String) -> Vec<Token> {
                 tokens.push(Token::CloseTag);
                 inside = false;
             }
-            '/' if inside => {
-                if iter.peek() == Some(&'>') {
-                    iter.next();
-                    tokens.push(Token::InlineCloseTag);
-                    inside = false;
-                }
+            '/' if inside && iter.peek() == Some(&'>') => {
+                iter.next();
+                tokens.push(Token::InlineCloseTag);
+                inside = false;
             }
-            '"' if inside => {
+            '"' | '\'' if inside => {
                 let mut value = String::new();
-                while let Some(&next) = iter.peek() {
-                    if next == '"' {
-                        iter.next();
-                        break;
+                loop {
+                    match iter.next() {
+                        Some(next) if next == c => break,
+                        Some(next) => value.push(next),
+                        None => return error("Unterminated string".to_string()),
                     }
-                    value.push(iter.next().unwrap());
                 }
                 tokens.push(Token::Literal(Literal::String(value)));
             }
@@ -118,39 +195,67 @@ pub fn tokenize(xml: String) -> Vec<Token> {
 
                     let number = num.replace("_", "");
                     let literal = if is_float {
-                        Literal::Float(number.parse().unwrap())
+                        number
+                            .parse()
+                            .ok()
+                            .filter(|f: &f64| f.is_finite())
+                            .map(Literal::Float)
                     } else {
-                        Literal::Int(number.parse().unwrap())
+                        number.parse().ok().map(Literal::Int)
                     };
-                    tokens.push(Token::Literal(literal));
+                    match literal {
+                        Some(literal) => tokens.push(Token::Literal(literal)),
+                        None => return error(format!("Invalid number '{num}'")),
+                    }
+                } else if !c.is_whitespace() {
+                    return error(format!("Unexpected '{c}'"));
                 }
             }
             _ if !c.is_whitespace() && !inside => {
-                extract_contents(&mut tokens, &mut iter, &mut inside, c);
+                extract_contents(&mut tokens, &mut iter, &mut inside, c)?;
             }
             _ => {}
         }
     }
 
-    tokens
+    Ok(tokens)
+}
+
+/// Adds tokens with the position the current one started at.
+struct Spanned<'a> {
+    tokens: &'a mut Vec<(Token, Position)>,
+    position: Position,
+}
+
+impl Spanned<'_> {
+    fn push(&mut self, token: Token) {
+        self.tokens.push((token, self.position));
+    }
 }
 
 fn extract_contents(
-    tokens: &mut Vec<Token>,
-    iter: &mut Peekable<Chars>,
+    tokens: &mut Spanned,
+    iter: &mut Cursor,
     inside: &mut bool,
     c: char,
-) {
+) -> Result<(), MarkupError> {
     let mut contents = c.to_string();
     let mut open = false;
     let mut open_closing = false;
+    let mut position = iter.position;
     while let Some(&next) = iter.peek() {
         if next == '<' {
+            position = iter.position;
             iter.next();
-            if let Some(&'<') = iter.peek() {
+            if iter.starts_with("!--") {
+                iter.skip_comment(position)?;
+                continue;
+            } else if let Some(&'<') = iter.peek() {
+                iter.next();
                 contents.push('<');
                 continue;
             } else if let Some(&'/') = iter.peek() {
+                iter.next();
                 open_closing = true;
                 break;
             } else {
@@ -161,6 +266,7 @@ fn extract_contents(
         contents.push(iter.next().unwrap());
     }
     tokens.push(Token::Contents(contents));
+    tokens.position = position;
     if open {
         tokens.push(Token::Tag);
         *inside = true;
@@ -168,4 +274,5 @@ fn extract_contents(
         tokens.push(Token::ClosingTag);
         *inside = true;
     }
+    Ok(())
 }
@@ -0,0 +1,272 @@
+use crate::ui::xml::lexer::Position;
+use std::fmt::Display;
+
+/// A lossless tree of a UI file, keeping comments and the whitespace between nodes. Unlike
+/// [`crate::ui::xml::tree`] it is only used to rewrite the markup, not to generate code.
+#[derive(Debug, Clone)]
+pub enum Markup {
+    Element(Element),
+    /// Text exactly as written, including whitespace and `<<` escapes.
+    Text(String),
+    /// The contents of a `<!-- -->` comment.
+    Comment(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Element {
+    /// The tag name, empty for the unnamed `<>` wrapper.
+    pub tag: String,
+    pub attributes: Vec<RawAttribute>,
+    pub children: Vec<Markup>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RawAttribute {
+    pub name: String,
+    pub value: RawValue,
+}
+
+#[derive(Debug, Clone)]
+pub enum RawValue {
+    String(String),
+    /// A number as written, e.g. `1_000` or `0.5`.
+    Number(String),
+}
+
+#[derive(Debug)]
+pub struct MarkupError {
+    pub message: String,
+    pub position: Position,
+}
+
+impl Display for MarkupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.position, self.message)
+    }
+}
+
+pub fn parse(markup: &str) -> Result<Vec<Markup>, MarkupError> {
+    let mut parser = Parser {
+        chars: markup.chars().collect(),
+        index: 0,
+        position: Position { line: 1, column: 1 },
+    };
+    parser.parse_children(None)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+    position: Position,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        let mut rest = self.chars[self.index..].iter();
+        s.chars().all(|c| rest.next() == Some(&c))
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        if c == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+        Some(c)
+    }
+
+    fn skip(&mut self, s: &str) {
+        for _ in s.chars() {
+            self.next();
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.next();
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, MarkupError> {
+        Err(MarkupError {
+            message: message.into(),
+            position: self.position,
+        })
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), MarkupError> {
+        if self.starts_with(s) {
+            self.skip(s);
+            Ok(())
+        } else {
+            match self.peek() {
+                Some(c) => self.error(format!("Expected '{s}' but found '{c}'")),
+                None => self.error(format!("Expected '{s}' but found EOF")),
+            }
+        }
+    }
+
+    /// Parses nodes up to the closing tag of `closing`, or the end of the input at the top level.
+    fn parse_children(&mut self, closing: Option<&str>) -> Result<Vec<Markup>, MarkupError> {
+        let mut children = Vec::new();
+        let mut text = String::new();
+
+        loop {
+            if self.starts_with("<<") {
+                self.skip("<<");
+                text.push_str("<<");
+                continue;
+            }
+            if (self.peek().is_none() || self.starts_with("<")) && !text.is_empty() {
+                children.push(Markup::Text(std::mem::take(&mut text)));
+            }
+
+            match self.peek() {
+                None => {
+                    return match closing {
+                        Some(tag) => self.error(format!("Expected '</{tag}>' but found EOF")),
+                        None => Ok(children),
+                    };
+                }
+                Some('<') if self.starts_with("<!--") => {
+                    self.skip("<!--");
+                    let mut comment = String::new();
+                    while !self.starts_with("-->") {
+                        match self.next() {
+                            Some(c) => comment.push(c),
+                            None => return self.error("Unterminated comment"),
+                        }
+                    }
+                    self.skip("-->");
+                    children.push(Markup::Comment(comment));
+                }
+                Some('<') if self.starts_with("</") => {
+                    let position = self.position;
+                    self.skip("</");
+                    let name = self.parse_ident();
+                    self.skip_whitespace();
+                    self.expect(">")?;
+                    return match closing {
+                        Some(tag) if tag == name => Ok(children),
+                        Some(tag) => Err(MarkupError {
+                            message: format!(
+                                "Expected closing tag for '{tag}' but found closing tag for '{name}'"
+                            ),
+                            position,
+                        }),
+                        None => Err(MarkupError {
+                            message: format!("Unexpected closing tag for '{name}'"),
+                            position,
+                        }),
+                    };
+                }
+                Some('<') => children.push(Markup::Element(self.parse_element()?)),
+                Some(_) => text.push(self.next().unwrap()),
+            }
+        }
+    }
+
+    fn parse_element(&mut self) -> Result<Element, MarkupError> {
+        self.skip("<");
+        if let Some(c) = self.peek().filter(|c| c.is_numeric()) {
+            return self.error(format!("Expected tag name but found '{c}'"));
+        }
+        let tag = self.parse_ident();
+        let mut attributes = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('>') => {
+                    self.next();
+                    let children = self.parse_children(Some(&tag))?;
+                    return Ok(Element {
+                        tag,
+                        attributes,
+                        children,
+                    });
+                }
+                Some('/') if self.starts_with("/>") && !tag.is_empty() => {
+                    self.skip("/>");
+                    return Ok(Element {
+                        tag,
+                        attributes,
+                        children: Vec::new(),
+                    });
+                }
+                Some(c) if c.is_alphabetic() || c == '_' => {
+                    attributes.push(self.parse_attribute()?);
+                }
+                Some(c) => return self.error(format!("Expected attribute or '>' but found '{c}'")),
+                None => return self.error("Expected attribute or '>' but found EOF"),
+            }
+        }
+    }
+
+    fn parse_attribute(&mut self) -> Result<RawAttribute, MarkupError> {
+        let name = self.parse_ident();
+        self.skip_whitespace();
+        self.expect("=")?;
+        self.skip_whitespace();
+
+        let value = match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                self.next();
+                let mut value = String::new();
+                loop {
+                    match self.next() {
+                        Some(c) if c == quote => break,
+                        Some(c) => value.push(c),
+                        None => return self.error("Unterminated string"),
+                    }
+                }
+                RawValue::String(value)
+            }
+            Some(c) if c.is_numeric() => {
+                let position = self.position;
+                let mut number = String::new();
+                while let Some(c) = self.peek() {
+                    if !c.is_numeric() && c != '_' && c != '.' {
+                        break;
+                    }
+                    number.push(c);
+                    self.next();
+                }
+                if !number
+                    .replace('_', "")
+                    .parse::<f64>()
+                    .is_ok_and(f64::is_finite)
+                {
+                    return Err(MarkupError {
+                        message: format!("Invalid number '{number}'"),
+                        position,
+                    });
+                }
+                RawValue::Number(number)
+            }
+            Some(c) => return self.error(format!("Expected literal but found '{c}'")),
+            None => return self.error("Expected literal but found EOF"),
+        };
+
+        Ok(RawAttribute { name, value })
+    }
+
+    fn parse_ident(&mut self) -> String {
+        let mut ident = String::new();
+        while let Some(c) = self.peek() {
+            if !c.is_alphanumeric() && c != '_' {
+                break;
+            }
+            ident.push(c);
+            self.next();
+        }
+        ident
+    }
+}
@@ -1,10 +1,116 @@
 pub mod codegen;
+pub mod format;
 pub mod lexer;
+pub mod markup;
 pub mod parser;
 pub mod tree;
 
-pub fn compile(ui: String) -> String {
-    let tokens = lexer::tokenize(ui);
-    let tree = parser::parse(tokens);
-    codegen::generate(tree)
+use crate::ui::style::cascade::Styles;
+use crate::ui::xml::markup::MarkupError;
+use crate::ui::xml::tree::Node;
+
+/// Parses a UI file into its elements, with the first syntax error if it has any.
+pub fn parse(ui: &str) -> Result<Vec<Node>, MarkupError> {
+    parser::parse(lexer::tokenize(ui.to_string())?)
+}
+
+/// Compiles a UI file into the module of the given name.
+pub fn compile(
+    ui: &str,
+    runtime: &str,
+    module: &str,
+    styles: &Styles,
+) -> Result<String, MarkupError> {
+    codegen::generate(parse(ui)?, runtime, module, styles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_markup_is_an_error() {
+        for ui in [
+            "<label width=1.2.3/>",
+            "<1abc/>",
+            "<label width=99999999999999999999/>",
+            "<label id=5/>",
+            "<label>",
+            "<label></button>",
+            "<label width=\"5/>",
+            "<label/><!-- open",
+        ] {
+            assert!(parse(ui).is_err(), "{ui}");
+        }
+    }
+
+    #[test]
+    fn valid_markup() {
+        let tree = parse("<element>text <label width=1.5 id=\"a\"/></element>").unwrap();
+        assert_eq!(tree.len(), 1);
+    }
+
+    #[test]
+    fn names_without_code_are_errors() {
+        let styles = Styles::default();
+        let error = compile("<label\n    self=\"x\"/>", "mvcore", "a", &styles).unwrap_err();
+        assert_eq!(
+            error.message,
+            "Attribute 'self' can't be used as an identifier"
+        );
+        assert_eq!((error.position.line, error.position.column), (2, 5));
+        assert!(compile("<label _=1/>", "mvcore", "a", &styles).is_err());
+        assert!(compile("<label type=1 width=1.5/>", "mvcore", "a", &styles).is_ok());
+
+        let error = compile("<nothing_like_this/>", "mvcore", "a", &styles).unwrap_err();
+        assert_eq!(error.message, "Unknown element 'nothing_like_this'");
+    }
+
+    #[test]
+    fn non_finite_numbers_are_errors() {
+        let huge = format!("<label width={}.0/>", "9".repeat(400));
+        assert_eq!(
+            parse(&huge).unwrap_err().message,
+            format!("Invalid number '{}.0'", "9".repeat(400))
+        );
+
+        let sheet = crate::ui::style::parser::parse("label { width: inf; }").unwrap();
+        let styles = Styles::load(&[("a.style".into(), sheet)], None).unwrap();
+        let error = compile("<label/>", "mvcore", "a", &styles).unwrap_err();
+        assert!(
+            error.message.contains("isn't a finite number"),
+            "{}",
+            error.message
+        );
+    }
+
+    #[test]
+    fn unusable_string_keys_are_errors() {
+        let styles = Styles::default();
+        let error = compile(
+            "<label>\n<label text=\"@str/..\"/></label>",
+            "mvcore",
+            "a",
+            &styles,
+        )
+        .unwrap_err();
+        assert_eq!(error.message, "String '..' contains no usable characters");
+        assert_eq!((error.position.line, error.position.column), (2, 8));
+
+        let sheet = crate::ui::style::parser::parse("label { text: \"@str/-\"; }").unwrap();
+        let styles = Styles::load(&[("a.style".into(), sheet)], None).unwrap();
+        let error = compile("<label/>", "mvcore", "a", &styles).unwrap_err();
+        assert_eq!(error.message, "String '-' contains no usable characters");
+    }
+
+    #[test]
+    fn errors_have_positions() {
+        let error = parse("<label>\n    <label width=1.2.3/>\n</label>").unwrap_err();
+        assert_eq!(error.message, "Invalid number '1.2.3'");
+        assert_eq!((error.position.line, error.position.column), (2, 18));
+
+        let error = parse("<label>text <!-- open\n</label>").unwrap_err();
+        assert_eq!(error.message, "Unterminated comment");
+        assert_eq!((error.position.line, error.position.column), (1, 13));
+    }
 }
@@ -1,142 +1,175 @@
-use crate::ui::xml::lexer::{Literal, Token};
+use crate::ui::xml::lexer::{Literal, Position, Token};
+use crate::ui::xml::markup::MarkupError;
 use crate::ui::xml::tree::{Attribute, Child, NamedNode, Node, UnnamedNode};
-use std::iter::Peekable;
-use std::vec::IntoIter;
 
-pub fn parse(tokens: Vec<Token>) -> Vec<Node> {
+/// The tokens of a UI file, yielding only the tokens while keeping the position of the last one.
+struct Tokens {
+    iter: std::vec::IntoIter<(Token, Position)>,
+    position: Position,
+}
+
+impl Iterator for Tokens {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let (token, position) = self.iter.next()?;
+        self.position = position;
+        Some(token)
+    }
+}
+
+impl Tokens {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, MarkupError> {
+        Err(MarkupError {
+            message: message.into(),
+            position: self.position,
+        })
+    }
+
+    /// The next token, or an error saying what was expected instead of the end of the file.
+    fn expect(&mut self, expected: &str) -> Result<Token, MarkupError> {
+        match self.next() {
+            Some(token) => Ok(token),
+            None => self.error(format!("Expected {expected} but found EOF")),
+        }
+    }
+}
+
+pub fn parse(tokens: Vec<(Token, Position)>) -> Result<Vec<Node>, MarkupError> {
     let mut nodes = Vec::new();
-    let mut iter = tokens.into_iter().peekable();
+    let mut iter = Tokens {
+        iter: tokens.into_iter(),
+        position: Position { line: 1, column: 1 },
+    };
 
     while let Some(token) = iter.next() {
         if let Token::Tag = token {
-            nodes.push(parse_node(&mut iter))
+            nodes.push(parse_node(&mut iter)?)
         } else {
-            panic!("UI syntax error: Expected '<' but found '{token}'");
+            return iter.error(format!("Expected '<' but found '{token}'"));
         }
     }
 
-    nodes
+    Ok(nodes)
 }
 
-fn parse_node(iter: &mut Peekable<IntoIter<Token>>) -> Node {
-    let ident = iter
-        .next()
-        .expect("UI syntax error: Expected identifier but found EOF");
-
-    let tag = match ident {
+fn parse_node(iter: &mut Tokens) -> Result<Node, MarkupError> {
+    let position = iter.position;
+    let tag = match iter.expect("identifier")? {
         Token::CloseTag => {
-            let children = parse_children(iter, None);
-            return Node::Unnamed(UnnamedNode { children });
+            let children = parse_children(iter, None)?;
+            return Ok(Node::Unnamed(UnnamedNode { children, position }));
         }
         Token::Ident(i) => i,
-        _ => panic!("UI syntax error: Expected identifier but found {ident}"),
+        token => return iter.error(format!("Expected identifier but found {token}")),
     };
 
     let mut id = String::new();
     let mut class = Vec::new();
+    let mut id_position = None;
+    let mut class_position = None;
     let mut attributes = Vec::new();
 
-    let mut token = iter
-        .next()
-        .expect("UI syntax error: Expected identifier or '<' but found EOF");
+    let mut token = iter.expect("identifier or '>'")?;
     while !(token == Token::CloseTag || token == Token::InlineCloseTag) {
         let Token::Ident(name) = token else {
-            panic!("UI syntax error: Expected identifier but found {token}")
+            return iter.error(format!("Expected identifier but found {token}"));
         };
+        let attribute_position = iter.position;
 
-        let next = iter
-            .next()
-            .expect("UI syntax error: Expected '=' but found EOF");
+        let next = iter.expect("'='")?;
         if next != Token::Equals {
-            panic!("UI syntax error: Expected '=' but found {next}");
+            return iter.error(format!("Expected '=' but found {next}"));
         }
 
-        let next = iter
-            .next()
-            .expect("UI syntax error: Expected '=' but found EOF");
+        let next = iter.expect("literal")?;
         let Token::Literal(value) = next else {
-            panic!("UI syntax error: Expected literal but found {next}")
+            return iter.error(format!("Expected literal but found {next}"));
         };
 
         match name.as_str() {
-            "id" => {
-                let Literal::String(str) = value else {
-                    panic!("UI syntax error: Literal for 'id' must be a string, but {value} was provided");
-                };
-                id = str;
-            }
-            "class" => {
+            "id" | "class" => {
                 let Literal::String(str) = value else {
-                    panic!("UI syntax error: Literal for 'class' must be a string, but {value} was provided");
+                    return Err(MarkupError {
+                        message: format!(
+                            "Literal for '{name}' must be a string, but {value} was provided"
+                        ),
+                        position: attribute_position,
+                    });
                 };
-                class = str.split_whitespace().map(ToString::to_string).collect();
+                if name == "id" {
+                    id = str;
+                    id_position = Some(attribute_position);
+                } else {
+                    class = str.split_whitespace().map(ToString::to_string).collect();
+                    class_position = Some(attribute_position);
+                }
             }
-            _ => attributes.push(Attribute { name, value }),
+            _ => attributes.push(Attribute {
+                name,
+                value,
+                position: attribute_position,
+            }),
         }
 
-        token = iter
-            .next()
-            .expect("UI syntax error: Expected identifier or '<' but found EOF");
+        token = iter.expect("identifier or '>'")?;
     }
 
     let children = if token == Token::CloseTag {
-        parse_children(iter, Some(tag.clone()))
+        parse_children(iter, Some(tag.clone()))?
     } else {
         Vec::new()
     };
 
-    Node::Named(NamedNode {
+    Ok(Node::Named(NamedNode {
         tag,
         id,
         class,
         attributes,
         children,
-    })
+        position,
+        id_position,
+        class_position,
+    }))
 }
 
-fn parse_children(iter: &mut Peekable<IntoIter<Token>>, closing: Option<String>) -> Vec<Child> {
+fn parse_children(iter: &mut Tokens, closing: Option<String>) -> Result<Vec<Child>, MarkupError> {
     let mut children = Vec::new();
     while let Some(token) = iter.next() {
         match token {
-            Token::Tag => children.push(Child::Node(parse_node(iter))),
+            Token::Tag => children.push(Child::Node(parse_node(iter)?)),
             Token::Contents(c) => children.push(Child::String(
                 c.lines().map(|l| l.trim()).collect::<Vec<_>>().join(" "),
             )),
             Token::ClosingTag => {
-                let next = iter
-                    .next()
-                    .expect("UI syntax error: Expected identifier or '>' but found EOF");
+                let mut next = iter.expect("identifier or '>'")?;
                 if let Some(tag) = closing {
                     let Token::Ident(name) = next else {
-                        panic!("UI syntax error: Expected identifier but found {next}");
+                        return iter.error(format!("Expected identifier but found {next}"));
                     };
                     if tag != name {
-                        panic!("UI syntax error: Expected closing tag for {tag} but found closing tag for {name}");
-                    }
-                    let next = iter
-                        .next()
-                        .expect("UI syntax error: Expected '>' but found EOF");
-                    if next != Token::CloseTag {
-                        panic!("UI syntax error: Expected '>' but found {next}");
-                    }
-                } else {
-                    if next != Token::CloseTag {
-                        panic!("UI syntax error: Expected '>' but found {next}");
+                        return iter.error(format!(
+                            "Expected closing tag for '{tag}' but found closing tag for '{name}'"
+                        ));
                     }
+                    next = iter.expect("'>'")?;
+                }
+                if next != Token::CloseTag {
+                    return iter.error(format!("Expected '>' but found {next}"));
                 }
-                return children;
+                return Ok(children);
             }
             t => {
-                panic!(
-                    "UI syntax error: Expected contents or '</{}>' but found {}",
-                    closing.unwrap_or(String::new()),
+                return iter.error(format!(
+                    "Expected contents or '</{}>' but found {}",
+                    closing.unwrap_or_default(),
                     t
-                );
+                ));
             }
         }
     }
-    panic!(
-        "UI syntax error: Expected '</{}>' but found EOF",
-        closing.unwrap_or(String::new())
-    );
+    iter.error(format!(
+        "Expected '</{}>' but found EOF",
+        closing.unwrap_or_default()
+    ))
 }
@@ -1,4 +1,4 @@
-use crate::ui::xml::lexer::Literal;
+use crate::ui::xml::lexer::{Literal, Position};
 
 #[derive(Debug, Clone)]
 pub enum Node {
@@ -6,6 +6,15 @@ pub enum Node {
     Unnamed(UnnamedNode),
 }
 
+impl Node {
+    pub fn position(&self) -> Position {
+        match self {
+            Node::Named(node) => node.position,
+            Node::Unnamed(node) => node.position,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Child {
     Node(Node),
@@ -19,15 +28,22 @@ pub struct NamedNode {
     pub class: Vec<String>,
     pub attributes: Vec<Attribute>,
     pub children: Vec<Child>,
+    /// The position of the opening `<`.
+    pub position: Position,
+    /// The positions of the `id` and `class` attributes, if present.
+    pub id_position: Option<Position>,
+    pub class_position: Option<Position>,
 }
 
 #[derive(Debug, Clone)]
 pub struct UnnamedNode {
     pub children: Vec<Child>,
+    pub position: Position,
 }
 
 #[derive(Debug, Clone)]
 pub struct Attribute {
     pub name: String,
     pub value: Literal,
+    pub position: Position,
 }